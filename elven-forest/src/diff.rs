@@ -0,0 +1,176 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Result;
+use elven_parser::{
+    consts::{self as c, ShFlags, ShType},
+    read::{ElfReadError, ElfReader},
+};
+use tabled::Tabled;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SectionSummary {
+    pub r#type: ShType,
+    pub size: u64,
+    pub flags: ShFlags,
+}
+
+impl std::fmt::Display for SectionSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} ({})", self.r#type, self.size, self.flags)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Tabled)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DiffRow {
+    pub status: &'static str,
+    pub kind: &'static str,
+    pub name: String,
+    pub old: String,
+    pub new: String,
+}
+
+pub fn section_summaries(elf: &ElfReader<'_>) -> Result<BTreeMap<String, SectionSummary>> {
+    elf.section_headers()?
+        .iter()
+        .map(|sh| {
+            let name = elf.sh_string(sh.name)?.to_string();
+            let summary = SectionSummary {
+                r#type: sh.r#type,
+                size: sh.size,
+                flags: sh.flags,
+            };
+            Ok((name, summary))
+        })
+        .collect()
+}
+
+/// The names of all named symbols. Stripped files just don't have any.
+pub fn symbol_names(elf: &ElfReader<'_>) -> Result<BTreeSet<String>> {
+    let symbols = match elf.symbols() {
+        Err(ElfReadError::SectionTypeNotFound(_)) => &[],
+        symbols => symbols?,
+    };
+
+    let mut names = BTreeSet::new();
+    for sym in symbols {
+        if sym.info.r#type() == c::STT_SECTION || sym.info.r#type() == c::STT_FILE {
+            continue;
+        }
+        let name = elf.string(sym.name)?;
+        if !name.is_empty() {
+            names.insert(name.to_string());
+        }
+    }
+    Ok(names)
+}
+
+pub fn diff_files(old: &ElfReader<'_>, new: &ElfReader<'_>) -> Result<Vec<DiffRow>> {
+    let mut rows = diff_sections(&section_summaries(old)?, &section_summaries(new)?);
+    rows.extend(diff_symbols(&symbol_names(old)?, &symbol_names(new)?));
+    Ok(rows)
+}
+
+pub fn diff_sections(
+    old: &BTreeMap<String, SectionSummary>,
+    new: &BTreeMap<String, SectionSummary>,
+) -> Vec<DiffRow> {
+    let names = old.keys().chain(new.keys()).collect::<BTreeSet<_>>();
+
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let (status, old, new) = match (old.get(name), new.get(name)) {
+                (Some(old), Some(new)) if old == new => return None,
+                (Some(old), Some(new)) => ("~", old.to_string(), new.to_string()),
+                (Some(old), None) => ("-", old.to_string(), String::new()),
+                (None, Some(new)) => ("+", String::new(), new.to_string()),
+                (None, None) => unreachable!(),
+            };
+            Some(DiffRow {
+                status,
+                kind: "section",
+                name: name.clone(),
+                old,
+                new,
+            })
+        })
+        .collect()
+}
+
+pub fn diff_symbols(old: &BTreeSet<String>, new: &BTreeSet<String>) -> Vec<DiffRow> {
+    let removed = old.difference(new).map(|name| ("-", name));
+    let added = new.difference(old).map(|name| ("+", name));
+
+    let mut rows = removed
+        .chain(added)
+        .map(|(status, name)| DiffRow {
+            status,
+            kind: "symbol",
+            name: name.clone(),
+            old: String::new(),
+            new: String::new(),
+        })
+        .collect::<Vec<_>>();
+    rows.sort_by(|a, b| a.name.cmp(&b.name));
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use elven_parser::consts::{self as c, ShFlags};
+
+    use super::{diff_sections, diff_symbols, SectionSummary};
+
+    fn summary(size: u64) -> SectionSummary {
+        SectionSummary {
+            r#type: c::SHT_PROGBITS.into(),
+            size,
+            flags: ShFlags::SHF_ALLOC,
+        }
+    }
+
+    #[test]
+    fn sections() {
+        let old = BTreeMap::from([
+            (".text".to_owned(), summary(16)),
+            (".data".to_owned(), summary(8)),
+            (".old".to_owned(), summary(1)),
+        ]);
+        let new = BTreeMap::from([
+            (".text".to_owned(), summary(32)),
+            (".data".to_owned(), summary(8)),
+            (".new".to_owned(), summary(1)),
+        ]);
+
+        let rows = diff_sections(&old, &new)
+            .into_iter()
+            .map(|row| (row.status, row.name))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            [
+                ("+", ".new".to_owned()),
+                ("-", ".old".to_owned()),
+                ("~", ".text".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn symbols() {
+        let old = BTreeSet::from(["main".to_owned(), "removed".to_owned()]);
+        let new = BTreeSet::from(["main".to_owned(), "added".to_owned()]);
+
+        let rows = diff_symbols(&old, &new)
+            .into_iter()
+            .map(|row| (row.status, row.name))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            [("+", "added".to_owned()), ("-", "removed".to_owned())]
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use std::fmt::Write;
+
+/// Formats bytes like `xxd -c16`, with offsets relative to the start of `data`.
+pub fn hex_dump(data: &[u8]) -> String {
+    let mut out = String::new();
+
+    for (line, chunk) in data.chunks(16).enumerate() {
+        write!(out, "{:08x}:", line * 16).unwrap();
+
+        for i in 0..16 {
+            if i % 2 == 0 {
+                out.push(' ');
+            }
+            match chunk.get(i) {
+                Some(byte) => write!(out, "{byte:02x}").unwrap(),
+                None => out.push_str("  "),
+            }
+        }
+
+        out.push_str("  ");
+        out.extend(chunk.iter().map(|&byte| {
+            if byte.is_ascii_graphic() || byte == b' ' {
+                byte as char
+            } else {
+                '.'
+            }
+        }));
+        out.push('\n');
+    }
+
+    out
+}
+
+/// Finds runs of at least four printable ASCII characters, like `strings(1)`.
+/// Returns their offsets relative to the start of `data`.
+pub fn strings(data: &[u8]) -> Vec<(usize, &str)> {
+    const MIN_LEN: usize = 4;
+
+    let is_printable = |byte: &u8| (0x20..=0x7e).contains(byte);
+
+    let mut strings = Vec::new();
+    let mut start = 0;
+    while start < data.len() {
+        let len = data[start..].iter().take_while(|b| is_printable(b)).count();
+        if len >= MIN_LEN {
+            // Printable ASCII is always valid UTF-8.
+            let string = std::str::from_utf8(&data[start..][..len]).unwrap();
+            strings.push((start, string));
+        }
+        start += len + 1;
+    }
+
+    strings
+}
+
+#[cfg(test)]
+mod tests {
+    use elven_parser::read::ElfReader;
+
+    use super::{hex_dump, strings};
+    use crate::test_data::load_test_file;
+
+    #[test]
+    fn formats_like_xxd() {
+        let dump = hex_dump(b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0\x03\0>\0");
+        assert_eq!(
+            dump,
+            "00000000: 7f45 4c46 0201 0100 0000 0000 0000 0000  .ELF............\n\
+             00000010: 0300 3e00                                ..>.\n"
+        );
+    }
+
+    #[test]
+    fn hello_world_text() {
+        let mmap = load_test_file("hello_world");
+        let elf = ElfReader::new(&mmap).unwrap();
+
+        let text = elf.section_header_by_name(b".text").unwrap();
+        let dump = hex_dump(elf.section_content(text).unwrap());
+
+        let first_line = dump.lines().next().unwrap();
+        let (offset, rest) = first_line.split_once(": ").unwrap();
+        assert_eq!(offset, "00000000");
+
+        let first_bytes = u32::from_str_radix(&rest[..9].replace(' ', ""), 16).unwrap();
+        assert_ne!(first_bytes, 0);
+    }
+
+    #[test]
+    fn finds_strings() {
+        let data = b"\0abc\0.text\0\x01\xffhello world\nend";
+        assert_eq!(strings(data), [(5, ".text"), (13, "hello world")]);
+    }
+
+    #[test]
+    fn hello_world_rodata() {
+        let mmap = load_test_file("hello_world");
+        let elf = ElfReader::new(&mmap).unwrap();
+
+        let rodata = elf.section_header_by_name(b".rodata").unwrap();
+        let in_rodata = strings(elf.section_content(rodata).unwrap());
+        assert!(
+            in_rodata.iter().any(|&(_, s)| s == "Hello, World!"),
+            "{in_rodata:?}"
+        );
+
+        // In the whole file, the offset is the one of `.rodata`.
+        let in_file = strings(&mmap);
+        let &(offset, _) = in_file
+            .iter()
+            .find(|&&(_, s)| s == "Hello, World!")
+            .unwrap_or_else(|| panic!("{in_file:?}"));
+        assert_ne!(offset, 0);
+        assert_eq!(offset, rodata.offset.usize());
+    }
+}
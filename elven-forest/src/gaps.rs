@@ -0,0 +1,76 @@
+use anyhow::Result;
+use elven_parser::{consts as c, read::ElfReader, Offset};
+use tabled::Tabled;
+
+#[derive(Debug, PartialEq, Eq, Tabled)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct GapRow {
+    /// The section name, or `<gap>` for the unused bytes before the next section.
+    pub name: String,
+    pub offset: Offset,
+    pub size: u64,
+}
+
+/// The sections with content in the file, with their name, offset and size.
+pub fn file_sections(elf: &ElfReader<'_>) -> Result<Vec<(String, Offset, u64)>> {
+    elf.section_headers()?
+        .iter()
+        .filter(|sh| !matches!(sh.r#type.0, c::SHT_NULL | c::SHT_NOBITS))
+        .map(|sh| Ok((elf.sh_string(sh.name)?.to_string(), sh.offset, sh.size)))
+        .collect()
+}
+
+/// Sorts the sections by offset and inserts a `<gap>` row wherever a section doesn't start
+/// right after the previous one ends. Overlapping sections have no gap.
+pub fn gaps(mut sections: Vec<(String, Offset, u64)>) -> Vec<GapRow> {
+    sections.sort_by_key(|&(_, offset, size)| (offset, size));
+
+    let mut rows = Vec::new();
+    let mut end: Option<u64> = None;
+    for (name, offset, size) in sections {
+        if let Some(end) = end.filter(|&end| end < offset.u64()) {
+            rows.push(GapRow {
+                name: "<gap>".to_owned(),
+                offset: Offset(end),
+                size: offset.u64() - end,
+            });
+        }
+
+        let section_end = offset.u64().saturating_add(size);
+        end = Some(end.map_or(section_end, |end| end.max(section_end)));
+        rows.push(GapRow { name, offset, size });
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use elven_parser::Offset;
+
+    use super::gaps;
+
+    #[test]
+    fn gaps_between_sections() {
+        let sections = vec![
+            (".data".to_owned(), Offset(0x100), 8),
+            (".text".to_owned(), Offset(0x40), 0x10),
+            (".rodata".to_owned(), Offset(0x50), 0x20),
+            (".comment".to_owned(), Offset(0x108), 4),
+        ];
+
+        let rows = gaps(sections)
+            .into_iter()
+            .map(|row| (row.name, row.offset.u64(), row.size))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            [
+                (".text".to_owned(), 0x40, 0x10),
+                (".rodata".to_owned(), 0x50, 0x20),
+                ("<gap>".to_owned(), 0x70, 0x90),
+                (".data".to_owned(), 0x100, 8),
+                (".comment".to_owned(), 0x108, 4),
+            ]
+        );
+    }
+}
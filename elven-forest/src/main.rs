@@ -3,74 +3,308 @@ use std::{
     fs::File,
     path::{Path, PathBuf},
 };
+mod diff;
+mod dump;
+mod gaps;
 mod size;
+#[cfg(test)]
+mod test_data;
 
-use anyhow::Context;
+use anyhow::{bail, Context};
+use bstr::BStr;
 use clap::Parser;
 use elven_parser::{
-    consts::{self as c, DynamicTag, PhFlags, PhType, ShFlags, ShType, SymbolVisibility, RX86_64},
-    read::{ElfReadError, ElfReader, Sym, SymInfo},
+    consts::{
+        self as c, CoreNoteType, Data, DynFlags, DynFlags1, DynamicTag, NoteType, PhFlags, PhType,
+        RAArch64, RRiscv, ShFlags, ShType, SymbolVisibility, RX86_64,
+    },
+    eh_frame::EhFrameEntry,
+    read::{ElfReadError, ElfReader, Note, Phdr, StringIdx, Sym, SymInfo, SymbolSource},
     Addr, Offset,
 };
 use memmap2::Mmap;
-use tabled::{object::Rows, Disable, Style, Table, Tabled};
+use tabled::{locator::ByColumnName, object::Rows, Disable, Modify, Style, Table, Tabled, Width};
 
 #[derive(Parser)]
 struct Opts {
-    #[arg(long("file-header"), long("header"))]
+    #[arg(long("file-header"), visible_alias("header"))]
     header: bool,
-    #[arg(short('l'), long("program-headers"), long("segments"))]
+    #[arg(short('l'), long("program-headers"), visible_alias("segments"))]
     program_headers: bool,
-    #[arg(short('S'), long("section-headers"), long("sections"))]
+    #[arg(short('S'), long("section-headers"), visible_alias("sections"))]
     section_headers: bool,
-    #[arg(short('s'), long("symbols"), long("syms"))]
+    #[arg(short('s'), long("symbols"), visible_alias("syms"))]
     symbols: bool,
     #[arg(short('r'), long("relocs"))]
     relocs: bool,
+    /// Demangle Rust symbol names.
+    #[arg(short('C'), long("demangle"), overrides_with("no_demangle"))]
+    demangle: bool,
+    #[arg(long("no-demangle"), overrides_with("demangle"))]
+    no_demangle: bool,
+    /// Don't truncate long names in tables, like `readelf --wide`.
+    #[arg(short('W'), long("wide"), conflicts_with("max_width"))]
+    wide: bool,
+    /// Truncate long names in tables to this many characters.
+    #[arg(long("max-width"), value_name = "N", default_value_t = DEFAULT_MAX_WIDTH)]
+    max_width: usize,
     /// Not in readelf.
     #[arg(short('d'), long("dyns"))]
     dyns: bool,
+    #[arg(short('V'), long("version-info"))]
+    version_info: bool,
+    #[arg(short('n'), long("notes"))]
+    notes: bool,
+    /// Show where the symbols are in the file with `--symbols`, for hex editors.
+    /// Not in readelf.
+    #[arg(long("file-offsets"))]
+    file_offsets: bool,
+    /// Print the stubs of the procedure linkage table and the functions they call.
+    #[arg(long("plt"))]
+    plt: bool,
+    /// Print the sections in file order with the padding between them. Not in readelf.
+    #[arg(long("gaps"))]
+    gaps: bool,
+    /// List the allocated sections that aren't mapped by any `PT_LOAD` segment. Not in readelf.
+    #[arg(long("uncovered"))]
+    uncovered: bool,
+    /// Print the CIE and FDE records of `.eh_frame`.
+    #[arg(long("eh-frame"))]
+    eh_frame: bool,
+    /// Dump the content of the section with this name. Can be given multiple times.
+    #[arg(long("hex-dump"), value_name = "NAME")]
+    hex_dump: Vec<String>,
+    /// Print the strings in the section with this name. Can be given multiple times.
+    #[arg(long("string-dump"), value_name = "NAME")]
+    string_dump: Vec<String>,
+    /// Write the content of the section with this name to a file. Can be given multiple times.
+    #[arg(
+        long("extract-section"),
+        value_name = "NAME:FILE",
+        value_parser = parse_extract_section
+    )]
+    extract_section: Vec<(String, PathBuf)>,
+    /// Print how much each symbol contributes to the size of `.text`.
     #[arg(long("text-bloat"))]
     text_bloat: bool,
+    /// Analyze this section instead of `.text` with `--text-bloat`.
+    #[arg(long("section"), value_name = "NAME", requires("text_bloat"))]
+    section: Option<String>,
+    /// Analyze every executable section separately with `--text-bloat`.
+    #[arg(
+        long("all-sections"),
+        requires("text_bloat"),
+        conflicts_with("section")
+    )]
+    all_sections: bool,
+    /// Sum up the sizes per crate with `--text-bloat`.
+    #[arg(long("by-crate"), requires("text_bloat"))]
+    by_crate: bool,
     #[arg(long("csv"))]
     csv: bool,
+    /// Compare the sections and symbols of exactly two files.
+    /// With `--text-bloat`, compare the symbol sizes instead.
+    #[arg(long("diff"), conflicts_with_all(["all_sections", "by_crate"]))]
+    diff: bool,
+    /// Print all enabled tables as one JSON object instead.
+    #[cfg(feature = "serde")]
+    #[arg(long("json"), conflicts_with_all(["hex_dump", "string_dump", "text_bloat"]))]
+    json: bool,
     files: Vec<PathBuf>,
 }
 
 fn main() -> anyhow::Result<()> {
     let opts = Opts::parse();
 
+    if opts.diff {
+        let [old, new] = opts.files.as_slice() else {
+            bail!("--diff needs exactly two files");
+        };
+        let mut output = Output::new(&opts);
+        print_diff(&opts, &mut output, old, new)?;
+        return output.finish();
+    }
+
     for obj in &opts.files {
+        let mut output = Output::new(&opts);
+
         if opts.files.len() > 1 {
-            println!("{}", obj.display());
+            output.file(obj);
         }
 
-        print_file(&opts, obj).with_context(|| format!("Failed to print {}", obj.display()))?;
+        print_file(&opts, &mut output, obj)
+            .with_context(|| format!("Failed to print {}", obj.display()))?;
+
+        output.finish()?;
     }
 
     Ok(())
 }
 
+/// How many characters of symbol and section names are shown without `--wide`.
+const DEFAULT_MAX_WIDTH: usize = 40;
+/// The columns that are truncated, the others are short anyways.
+const NAME_COLUMNS: [&str; 2] = ["name", "symbol"];
+
 #[derive(Tabled)]
 struct HeaderTable<'a>(&'static str, &'a dyn Display);
 
+/// Where the tables go: printed as text, or collected into one JSON object per file.
+enum Output {
+    /// With the width that names are truncated to, `None` with `--wide`.
+    Text(Option<usize>),
+    #[cfg(feature = "serde")]
+    Json(serde_json::Map<String, serde_json::Value>),
+}
+
+#[cfg(feature = "serde")]
+trait Row: Tabled + serde::Serialize {}
+#[cfg(feature = "serde")]
+impl<T: Tabled + serde::Serialize> Row for T {}
+
+#[cfg(not(feature = "serde"))]
+trait Row: Tabled {}
+#[cfg(not(feature = "serde"))]
+impl<T: Tabled> Row for T {}
+
+impl Output {
+    fn new(opts: &Opts) -> Self {
+        #[cfg(feature = "serde")]
+        if opts.json {
+            return Self::Json(serde_json::Map::new());
+        }
+        Self::Text((!opts.wide).then_some(opts.max_width))
+    }
+
+    fn file(&mut self, path: &Path) {
+        match self {
+            Self::Text(_) => println!("{}", path.display()),
+            #[cfg(feature = "serde")]
+            Self::Json(object) => {
+                object.insert("file".into(), path.display().to_string().into());
+            }
+        }
+    }
+
+    fn header(&mut self, rows: Vec<HeaderTable<'_>>, interpreter: Option<&BStr>) {
+        match self {
+            Self::Text(_) => {
+                println!("\nHeader");
+                let mut table = Table::new(rows);
+                // No header
+                table.with(Disable::row(Rows::first()));
+                print_table(table);
+
+                if let Some(interpreter) = interpreter {
+                    println!("[Requesting program interpreter: {interpreter}]");
+                }
+            }
+            #[cfg(feature = "serde")]
+            Self::Json(object) => {
+                let mut rows = rows;
+                if let Some(interpreter) = &interpreter {
+                    rows.push(HeaderTable("interpreter", interpreter));
+                }
+                let header = rows
+                    .into_iter()
+                    .map(|HeaderTable(key, value)| (key.to_owned(), value.to_string().into()))
+                    .collect();
+                object.insert("header".into(), serde_json::Value::Object(header));
+            }
+        }
+    }
+
+    fn table<T: Row>(&mut self, key: &str, title: &str, rows: Vec<T>) -> anyhow::Result<()> {
+        self.table_without(key, title, rows, &[])
+    }
+
+    /// Like [`Output::table`], but leaves out the `hidden` columns of the text table.
+    #[cfg_attr(not(feature = "serde"), allow(unused_variables))]
+    fn table_without<T: Row>(
+        &mut self,
+        key: &str,
+        title: &str,
+        rows: Vec<T>,
+        hidden: &[&str],
+    ) -> anyhow::Result<()> {
+        match self {
+            Self::Text(max_width) => {
+                println!("\n{title}");
+                let mut table = Table::new(rows);
+                for &column in hidden {
+                    table.with(Disable::column(ByColumnName::new(column)));
+                }
+                if let Some(max_width) = *max_width {
+                    for column in NAME_COLUMNS {
+                        table.with(
+                            Modify::new(ByColumnName::new(column))
+                                .with(Width::truncate(max_width).suffix("…")),
+                        );
+                    }
+                }
+                print_table(table);
+            }
+            #[cfg(feature = "serde")]
+            Self::Json(object) => {
+                object.insert(key.into(), serde_json::to_value(rows)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// A single number, like the sum of a table.
+    fn total(&mut self, key: &str, title: &str, total: u64) {
+        match self {
+            Self::Text(_) => println!("{title}: {total}"),
+            #[cfg(feature = "serde")]
+            Self::Json(object) => {
+                object.insert(key.into(), total.into());
+            }
+        }
+    }
+
+    fn finish(self) -> anyhow::Result<()> {
+        match self {
+            Self::Text(_) => println!(),
+            #[cfg(feature = "serde")]
+            Self::Json(object) => {
+                println!("{}", serde_json::to_string_pretty(&object)?);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Serializes the types that only know how to display themselves as their display string.
+#[cfg(feature = "serde")]
+fn display<T: Display, S: serde::Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
 // Not really an addr but just display it as hex.
 type Hex = Addr;
 
 #[derive(Tabled)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 struct SectionTable {
     name: String,
     #[tabled(rename = "type")]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "display"))]
     r#type: ShType,
     size: u64,
     offset: Offset,
+    /// Letters like `readelf` in the table, the bits in JSON.
+    #[tabled(display_with = "shflags_letters")]
     flags: ShFlags,
 }
 
 #[derive(Tabled)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 struct ProgramHeaderTable {
     #[tabled(rename = "type")]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "display"))]
     r#type: PhType,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "display"))]
     flags: PhFlags,
     offset: Offset,
     virtual_addr: Addr,
@@ -83,43 +317,182 @@ struct ProgramHeaderTable {
 }
 
 #[derive(Tabled)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+struct SegmentMappingTable {
+    segment: String,
+    sections: String,
+}
+
+#[derive(Tabled)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+struct WarningTable {
+    warning: String,
+}
+
+#[derive(Tabled)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 struct SymbolTable {
+    /// `S` for `.symtab` and `D` for `.dynsym`.
+    source: &'static str,
     name: String,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "display"))]
     info: SymInfo,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "display"))]
     other: SymbolVisibility,
     section: String,
     value: Addr,
     size: u64,
+    /// Only with `--file-offsets`, empty for symbols without content in the file.
+    #[tabled(display_with = "display_option")]
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    adj_offset: Option<Offset>,
 }
 
 #[derive(Tabled)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 struct RelaTable {
     section: String,
     symbol: String,
     offset: Addr,
     #[tabled(rename = "type")]
-    r#type: RX86_64,
-    addend: i64,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "display"))]
+    r#type: RelocationType,
+    addend: Addend,
+}
+
+/// `SHT_REL` relocations don't have an explicit addend.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+struct Addend(Option<i64>);
+
+impl Display for Addend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(addend) => write!(f, "{addend}"),
+            None => f.write_str("-"),
+        }
+    }
+}
+
+/// Relocation types are specific to the machine.
+enum RelocationType {
+    X86_64(RX86_64),
+    AArch64(RAArch64),
+    Riscv(RRiscv),
+    Other(u32),
+}
+
+impl RelocationType {
+    fn new(machine: c::Machine, r#type: u32) -> Self {
+        match machine.0 {
+            c::EM_X86_64 => Self::X86_64(RX86_64(r#type)),
+            c::EM_AARCH64 => Self::AArch64(RAArch64(r#type)),
+            c::EM_RISCV => Self::Riscv(RRiscv(r#type)),
+            _ => Self::Other(r#type),
+        }
+    }
+}
+
+impl Display for RelocationType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::X86_64(r#type) => write!(f, "{type}"),
+            Self::AArch64(r#type) => write!(f, "{type}"),
+            Self::Riscv(r#type) => write!(f, "{type}"),
+            Self::Other(r#type) => write!(f, "{type}"),
+        }
+    }
 }
 
 #[derive(Tabled)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 struct DynTable {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "display"))]
     tag: DynamicTag,
-    value: Addr,
+    value: String,
+}
+
+#[derive(Tabled)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+struct EhFrameTable {
+    offset: Offset,
+    kind: &'static str,
+    length: u64,
+    cie: String,
+    pc_range: String,
+    augmentation: String,
+}
+
+#[derive(Tabled)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+struct EhFrameHdrTable {
+    initial_pc: Addr,
+    fde: Addr,
+    /// The offset of the FDE in `.eh_frame`, as shown in the `.eh_frame` table.
+    fde_offset: Offset,
+}
+
+#[derive(Tabled)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+struct NeededTable {
+    library: String,
+}
+
+#[derive(Tabled)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+struct VersionNeedTable {
+    library: String,
+    version: String,
+    index: u16,
+}
+
+#[derive(Tabled)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+struct UncoveredTable {
+    name: String,
+    addr: Addr,
+    size: u64,
+}
+
+#[derive(Tabled)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+struct NoteTable {
+    /// The section, or the segment index for files without sections.
+    location: String,
+    owner: String,
+    #[tabled(rename = "type")]
+    r#type: String,
+    size: u32,
+    description: String,
+}
+
+#[derive(Tabled)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+struct PltTable {
+    addr: Addr,
+    got_slot: Addr,
+    symbol: String,
 }
 
-fn print_file(opts: &Opts, path: &Path) -> anyhow::Result<()> {
+#[derive(Tabled)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+struct VersionDefTable {
+    version: String,
+    index: u16,
+    flags: String,
+    /// The versions this one inherits from, comma separated.
+    parents: String,
+}
+
+fn print_file(opts: &Opts, output: &mut Output, path: &Path) -> anyhow::Result<()> {
     let file = File::open(path)?;
     let mmap = unsafe { Mmap::map(&file) }?;
 
     let elf = ElfReader::new(&mmap)?;
 
     if opts.header {
-        println!("\nHeader");
-
         let header = elf.header()?;
         let ident = header.ident;
-        let header_tab = vec![
+        let mut header_tab = vec![
             HeaderTable("class", &ident.class),
             HeaderTable("data", &ident.data),
             HeaderTable("version", &ident.version),
@@ -132,15 +505,33 @@ fn print_file(opts: &Opts, path: &Path) -> anyhow::Result<()> {
             HeaderTable("section header size", &header.shentsize),
         ];
 
-        let mut table = Table::new(header_tab);
-        // No header
-        table.with(Disable::row(Rows::first()));
-        print_table(table);
+        let build_id = elf.gnu_build_id()?.map(hex_string);
+        if let Some(build_id) = &build_id {
+            header_tab.push(HeaderTable("build id", build_id));
+        }
+
+        let debuglink = elf.gnu_debuglink()?;
+        let debuglink_crc = debuglink.map(|link| format!("{:08x}", link.crc));
+        if let (Some(link), Some(crc)) = (&debuglink, &debuglink_crc) {
+            header_tab.push(HeaderTable("debug link", &link.filename));
+            header_tab.push(HeaderTable("debug link crc", crc));
+        }
+
+        output.header(header_tab, elf.interpreter()?);
+
+        let warnings = elf
+            .validate()?
+            .into_iter()
+            .map(|warning| WarningTable {
+                warning: warning.to_string(),
+            })
+            .collect::<Vec<_>>();
+        if !warnings.is_empty() {
+            output.table("warnings", "Warnings", warnings)?;
+        }
     }
 
     if opts.section_headers {
-        println!("\nSections");
-
         let mut sections = elf
             .section_headers()?
             .iter()
@@ -156,20 +547,18 @@ fn print_file(opts: &Opts, path: &Path) -> anyhow::Result<()> {
             })
             .collect::<Result<Vec<_>, ElfReadError>>()?;
 
-        sections.sort_by(|a, b| b.size.cmp(&a.size));
+        sections.sort_by_key(|s| std::cmp::Reverse(s.size));
 
-        print_table(Table::new(sections));
+        output.table("sections", "Sections", sections)?;
     }
 
     if opts.program_headers {
-        println!("\nProgram headers");
-
         let sections = elf
             .program_headers()?
             .iter()
             .map(|ph| {
                 let (inside_section, inside_section_offset) =
-                    section_name_of_offset(elf, ph.offset)?;
+                    section_name_of_offset(&elf, ph.offset)?;
 
                 Ok(ProgramHeaderTable {
                     r#type: ph.r#type,
@@ -186,17 +575,33 @@ fn print_file(opts: &Opts, path: &Path) -> anyhow::Result<()> {
             })
             .collect::<Result<Vec<_>, ElfReadError>>()?;
 
-        print_table(Table::new(sections));
+        output.table("program_headers", "Program headers", sections)?;
+
+        let mapping = elf
+            .program_headers()?
+            .iter()
+            .enumerate()
+            .map(|(idx, ph)| {
+                let sections = segment_sections(&elf, ph)?
+                    .into_iter()
+                    .map(|name| name.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                Ok(SegmentMappingTable {
+                    segment: format!("{idx:02}"),
+                    sections,
+                })
+            })
+            .collect::<Result<Vec<_>, ElfReadError>>()?;
+
+        output.table("segment_sections", "Section to segment mapping", mapping)?;
     }
 
     if opts.symbols {
-        println!("\nSymbols");
-
         let symbols = elf
-            .symbols()?
-            .iter()
-            .map(|sym| {
-                let name = sym_display_name(elf, sym)?;
+            .iter_all_symbols()?
+            .map(|(sym, source)| {
+                let name = sym_display_name(&elf, sym, source, opts.demangle)?;
                 let section = match sym.shndx.0 {
                     c::SHN_ABS | c::SHN_COMMON => String::new(),
                     _ => elf
@@ -204,35 +609,51 @@ fn print_file(opts: &Opts, path: &Path) -> anyhow::Result<()> {
                         .to_string(),
                 };
 
+                let adj_offset = if opts.file_offsets {
+                    symbol_file_offset(&elf, sym)?
+                } else {
+                    None
+                };
+
                 Ok(SymbolTable {
+                    source: match source {
+                        SymbolSource::Static => "S",
+                        SymbolSource::Dynamic => "D",
+                    },
                     name,
                     info: sym.info,
                     other: sym.other,
                     section,
                     size: sym.size,
                     value: sym.value,
+                    adj_offset,
                 })
             })
             .collect::<Result<Vec<_>, ElfReadError>>()?;
 
-        print_table(Table::new(symbols));
+        let hidden: &[_] = if opts.file_offsets {
+            &[]
+        } else {
+            &["adj_offset"]
+        };
+        output.table_without("symbols", "Symbols", symbols, hidden)?;
     }
 
     if opts.relocs {
-        println!("\nRelocations");
+        let machine = elf.header()?.machine;
 
         let relas = elf
-            .relas()?
-            .map(|(sh, rela)| {
+            .relocations()?
+            .map(|(sh, reloc)| {
                 let section = elf.sh_string(sh.name)?.to_string();
 
-                let sym = elf.symbol(rela.info.sym())?;
+                let sym = elf.symbol(reloc.info().sym())?;
 
-                let symbol = sym_display_name(elf, sym)?;
+                let symbol = sym_display_name(&elf, sym, SymbolSource::Static, opts.demangle)?;
 
-                let offset = rela.offset;
-                let r#type = c::RX86_64(rela.info.r#type());
-                let addend = rela.addend;
+                let offset = reloc.offset();
+                let r#type = RelocationType::new(machine, reloc.info().r#type());
+                let addend = Addend(reloc.addend());
 
                 Ok(RelaTable {
                     section,
@@ -244,32 +665,404 @@ fn print_file(opts: &Opts, path: &Path) -> anyhow::Result<()> {
             })
             .collect::<Result<Vec<_>, ElfReadError>>()?;
 
-        print_table(Table::new(relas));
+        output.table("relocations", "Relocations", relas)?;
     }
 
     if opts.dyns {
         if let Ok(dyns) = elf.dyn_entries() {
-            println!("\nDynamic entries");
+            let dyns = dyns
+                .iter()
+                .map(|dy| DynTable {
+                    tag: dy.tag,
+                    value: decode_dyn_val(dy.tag, dy.val, &elf),
+                })
+                .collect();
+            output.table("dynamic", "Dynamic entries", dyns)?;
 
-            let dyns = dyns.iter().map(|dy| DynTable {
-                tag: dy.tag,
-                value: Addr(dy.val),
-            });
-            print_table(Table::new(dyns));
+            let needed = elf
+                .dyn_needed_libraries()?
+                .map(|library| {
+                    Ok(NeededTable {
+                        library: library?.to_string(),
+                    })
+                })
+                .collect::<Result<Vec<_>, ElfReadError>>()?;
+            output.table("needed", "Needed libraries", needed)?;
         }
     }
 
-    if opts.text_bloat {
-        size::analyze_text_bloat(elf, opts.csv)?;
+    if opts.version_info {
+        let verneeds = match elf.verneed_entries() {
+            Err(ElfReadError::SectionTypeNotFound(_)) => None,
+            verneeds => Some(verneeds?),
+        };
+        if let Some(verneeds) = verneeds {
+            let mut versions = Vec::new();
+            for (need, auxs) in verneeds {
+                let library = elf.dyn_string(need.file)?.to_string();
+                for aux in auxs {
+                    versions.push(VersionNeedTable {
+                        library: library.clone(),
+                        version: elf.dyn_string(aux.name)?.to_string(),
+                        index: aux.other,
+                    });
+                }
+            }
+
+            output.table("version_needs", "Version needs", versions)?;
+        }
+
+        let verdefs = match elf.verdef_entries() {
+            Err(ElfReadError::SectionTypeNotFound(_)) => None,
+            verdefs => Some(verdefs?),
+        };
+        if let Some(verdefs) = verdefs {
+            let mut versions = Vec::new();
+            for (def, auxs) in verdefs {
+                let mut names = auxs
+                    .map(|aux| Ok(elf.dyn_string(aux.name)?.to_string()))
+                    .collect::<Result<Vec<_>, ElfReadError>>()?;
+                if names.is_empty() {
+                    continue;
+                }
+                let version = names.remove(0);
+
+                let mut flags = Vec::new();
+                if def.flags & c::VER_FLG_BASE != 0 {
+                    flags.push("BASE");
+                }
+                if def.flags & c::VER_FLG_WEAK != 0 {
+                    flags.push("WEAK");
+                }
+
+                versions.push(VersionDefTable {
+                    version,
+                    index: def.ndx,
+                    flags: flags.join(","),
+                    parents: names.join(","),
+                });
+            }
+
+            output.table("version_defs", "Version definitions", versions)?;
+        }
+    }
+
+    if opts.notes {
+        let data = elf.header()?.ident.data;
+        let mut notes = Vec::new();
+        let sections = elf
+            .section_headers()?
+            .iter()
+            .filter(|sh| sh.r#type == c::SHT_NOTE)
+            .collect::<Vec<_>>();
+
+        if sections.is_empty() {
+            // Without section headers, the notes can still be found through the segments.
+            for (idx, ph) in elf.program_headers()?.iter().enumerate() {
+                if ph.r#type == c::PT_NOTE {
+                    let location = format!("segment {idx:02}");
+                    notes.extend(
+                        elf.notes_in_segment(ph)?
+                            .map(|n| note_row(&location, n, data)),
+                    );
+                }
+            }
+        } else {
+            for sh in sections {
+                let location = elf.sh_string(sh.name)?.to_string();
+                notes.extend(
+                    elf.notes_in_section(sh)?
+                        .map(|n| note_row(&location, n, data)),
+                );
+            }
+        }
+
+        output.table("notes", "Notes", notes)?;
+    }
+
+    if opts.plt {
+        let entries = elf
+            .plt_entries()?
+            .map(|entry| PltTable {
+                addr: entry.addr,
+                got_slot: entry.got_slot,
+                symbol: entry.symbol.map(ToString::to_string).unwrap_or_default(),
+            })
+            .collect::<Vec<_>>();
+
+        output.table("plt", "PLT entries", entries)?;
+    }
+
+    if opts.gaps {
+        let rows = gaps::gaps(gaps::file_sections(&elf)?);
+        let total = rows
+            .iter()
+            .filter(|row| row.name == "<gap>")
+            .map(|row| row.size)
+            .sum();
+
+        output.table("gaps", "Sections and gaps in the file", rows)?;
+        output.total("gap_total", "Total gap bytes", total);
+    }
+
+    if opts.uncovered {
+        let rows = elf
+            .sections_outside_load_segments()?
+            .into_iter()
+            .map(|(_, sh)| {
+                Ok(UncoveredTable {
+                    name: elf.sh_string(sh.name)?.to_string(),
+                    addr: sh.addr,
+                    size: sh.size,
+                })
+            })
+            .collect::<Result<Vec<_>, ElfReadError>>()?;
+
+        output.table("uncovered", "Sections outside of PT_LOAD segments", rows)?;
+    }
+
+    if opts.eh_frame {
+        let entries = elf
+            .eh_frame_entries()?
+            .map(|entry| {
+                Ok(match entry? {
+                    EhFrameEntry::Cie(cie) => EhFrameTable {
+                        offset: cie.offset,
+                        kind: "CIE",
+                        length: cie.length,
+                        cie: String::new(),
+                        pc_range: String::new(),
+                        augmentation: format!(
+                            "\"{}\" cf={} df={} ra={}",
+                            cie.augmentation,
+                            cie.code_alignment,
+                            cie.data_alignment,
+                            cie.return_address_register
+                        ),
+                    },
+                    EhFrameEntry::Fde(fde) => EhFrameTable {
+                        offset: fde.offset,
+                        kind: "FDE",
+                        length: fde.length,
+                        cie: fde.cie_offset.to_string(),
+                        pc_range: format!(
+                            "{}..{}",
+                            fde.pc_begin,
+                            // Garbage FDEs can have any range, this is only for display.
+                            Addr(fde.pc_begin.u64().wrapping_add(fde.pc_range))
+                        ),
+                        augmentation: String::new(),
+                    },
+                })
+            })
+            .collect::<Result<Vec<_>, ElfReadError>>()?;
+
+        output.table("eh_frame", "Exception handling frames", entries)?;
+
+        if let Some(hdr) = elf.eh_frame_hdr()? {
+            let entries = hdr
+                .entries()
+                .map(|entry| {
+                    let entry = entry?;
+                    Ok(EhFrameHdrTable {
+                        initial_pc: entry.initial_pc,
+                        fde: entry.fde,
+                        fde_offset: Offset(entry.fde.u64().wrapping_sub(hdr.eh_frame_ptr.u64())),
+                    })
+                })
+                .collect::<Result<Vec<_>, ElfReadError>>()?;
+
+            output.table(
+                "eh_frame_hdr",
+                "Exception handling frame search table",
+                entries,
+            )?;
+        }
     }
 
-    println!();
+    for name in &opts.hex_dump {
+        let sh = elf.section_header_by_name(name.as_bytes())?;
+        let content = elf.section_content(sh)?;
+
+        println!("\nHex dump of section {name}");
+        print!("{}", dump::hex_dump(content));
+    }
+
+    for name in &opts.string_dump {
+        let sh = elf.section_header_by_name(name.as_bytes())?;
+        let content = elf.section_content(sh)?;
+
+        println!("\nString dump of section {name}");
+        for (offset, string) in dump::strings(content) {
+            println!("[{offset:6x}] {string}");
+        }
+    }
+
+    for (name, out_path) in &opts.extract_section {
+        let sh = elf.section_header_by_name(name.as_bytes())?;
+        // `SHT_NOBITS` sections have no content in the file, but are zeroed in memory.
+        let content = if sh.r#type.0 == c::SHT_NOBITS {
+            vec![0; sh.size.try_into()?]
+        } else {
+            elf.section_content(sh)?.to_vec()
+        };
+
+        std::fs::write(out_path, content)
+            .with_context(|| format!("Failed to write section {name} to {}", out_path.display()))?;
+    }
+
+    if opts.text_bloat {
+        let sections = if opts.all_sections {
+            elf.section_headers()?
+                .iter()
+                .filter(|sh| {
+                    sh.flags
+                        .contains(ShFlags::SHF_ALLOC | ShFlags::SHF_EXECINSTR)
+                })
+                .collect()
+        } else {
+            let name = opts.section.as_deref().unwrap_or(".text");
+            let sh = elf
+                .section_header_by_name(name.as_bytes())
+                .with_context(|| format!("{name} not found"))?;
+            vec![sh]
+        };
+        if opts.by_crate {
+            size::analyze_crate_bloat(&elf, &sections, opts.csv)?;
+        } else {
+            size::analyze_bloat(&elf, &sections, opts.csv)?;
+        }
+    }
 
     Ok(())
 }
 
+fn print_diff(opts: &Opts, output: &mut Output, old: &Path, new: &Path) -> anyhow::Result<()> {
+    let old_file = File::open(old).with_context(|| format!("Failed to open {}", old.display()))?;
+    let old_mmap = unsafe { Mmap::map(&old_file) }?;
+    let new_file = File::open(new).with_context(|| format!("Failed to open {}", new.display()))?;
+    let new_mmap = unsafe { Mmap::map(&new_file) }?;
+    let old = ElfReader::new(&old_mmap)?;
+    let new = ElfReader::new(&new_mmap)?;
+
+    if opts.text_bloat {
+        let section = opts.section.as_deref().unwrap_or(".text");
+        return size::analyze_text_bloat_diff(&old, &new, section, opts.csv);
+    }
+
+    let rows = diff::diff_files(&old, &new)?;
+
+    output.table("diff", "Differences", rows)
+}
+
+fn note_row(location: &str, note: Note<'_>, data: Data) -> NoteTable {
+    let owner = note.name.strip_suffix(b"\0").unwrap_or(note.name);
+
+    let (r#type, description) = match (owner, note.note_type) {
+        (b"GNU", c::NT_GNU_BUILD_ID) => (
+            NoteType(note.note_type).to_string(),
+            format!("Build ID: {}", hex_string(note.desc)),
+        ),
+        (b"GNU", c::NT_GNU_ABI_TAG) if note.desc.len() == 16 => {
+            let mut words = note.desc.chunks_exact(4).map(|word| {
+                let word = word.try_into().unwrap();
+                if data == c::ELFDATA2MSB {
+                    u32::from_be_bytes(word)
+                } else {
+                    u32::from_le_bytes(word)
+                }
+            });
+            let mut word = || words.next().unwrap();
+            let (os, major, minor, subminor) = (word(), word(), word(), word());
+            let os = match os {
+                0 => "Linux".to_owned(),
+                1 => "Hurd".to_owned(),
+                2 => "Solaris".to_owned(),
+                3 => "FreeBSD".to_owned(),
+                os => format!("<unknown {os}>"),
+            };
+            (
+                NoteType(note.note_type).to_string(),
+                format!("OS: {os}, ABI: {major}.{minor}.{subminor}"),
+            )
+        }
+        (b"GNU", _) => (NoteType(note.note_type).to_string(), hex_string(note.desc)),
+        (b"CORE", _) => (
+            CoreNoteType(note.note_type).to_string(),
+            hex_string(note.desc),
+        ),
+        _ => (format!("{:#x}", note.note_type), hex_string(note.desc)),
+    };
+
+    NoteTable {
+        location: location.to_owned(),
+        owner: BStr::new(owner).to_string(),
+        r#type,
+        size: note.descsz,
+        description,
+    }
+}
+
+fn parse_extract_section(value: &str) -> Result<(String, PathBuf), String> {
+    match value.split_once(':') {
+        Some((name, path)) if !name.is_empty() && !path.is_empty() => {
+            Ok((name.to_owned(), PathBuf::from(path)))
+        }
+        _ => Err("expected NAME:FILE".to_owned()),
+    }
+}
+
+/// The value of a dynamic entry like `readelf -d` shows it, with the names of flags, the strings
+/// of string table offsets and the relocation type of `DT_PLTREL`.
+fn decode_dyn_val(tag: DynamicTag, val: u64, elf: &ElfReader<'_>) -> String {
+    let flags = |flags: String, known: u64| {
+        let unknown = val & !known;
+        if known == 0 {
+            Addr(val).to_string()
+        } else if unknown == 0 {
+            flags
+        } else {
+            format!("{flags} | {}", Addr(unknown))
+        }
+    };
+
+    match tag.0 {
+        c::DT_FLAGS => {
+            let known = DynFlags::from_bits_truncate(val);
+            flags(known.to_string(), known.bits())
+        }
+        c::DT_FLAGS_1 => {
+            let known = DynFlags1::from_bits_truncate(val);
+            flags(known.to_string(), known.bits())
+        }
+        c::DT_NEEDED | c::DT_SONAME | c::DT_RPATH | c::DT_RUNPATH => u32::try_from(val)
+            .ok()
+            .and_then(|idx| elf.dyn_string(StringIdx(idx)).ok())
+            .map_or_else(|| Addr(val).to_string(), ToString::to_string),
+        c::DT_PLTREL => match val {
+            c::DT_RELA => "RELA".to_owned(),
+            c::DT_REL => "REL".to_owned(),
+            _ => Addr(val).to_string(),
+        },
+        _ => Addr(val).to_string(),
+    }
+}
+
+/// The names of the allocated sections whose address is inside the memory of the segment.
+fn segment_sections<'a>(elf: &ElfReader<'a>, ph: &Phdr) -> Result<Vec<&'a BStr>, ElfReadError> {
+    let start = ph.vaddr.u64();
+    let end = start.saturating_add(ph.memsz);
+
+    elf.section_headers()?
+        .iter()
+        .filter(|sh| sh.flags.contains(ShFlags::SHF_ALLOC))
+        .filter(|sh| (start..end).contains(&sh.addr.u64()))
+        .map(|sh| elf.sh_string(sh.name))
+        .collect()
+}
+
 fn section_name_of_offset(
-    elf: ElfReader<'_>,
+    elf: &ElfReader<'_>,
     offset: Offset,
 ) -> Result<(String, Offset), ElfReadError> {
     for sh in elf.section_headers()?.iter() {
@@ -287,16 +1080,116 @@ fn section_name_of_offset(
     Ok((String::new(), offset))
 }
 
-fn sym_display_name(elf: ElfReader<'_>, sym: &Sym) -> Result<String, ElfReadError> {
-    Ok(if sym.info.r#type() == c::STT_SECTION {
+/// The offset of the symbol in the file, the inverse of [`section_name_of_offset`].
+/// `None` if the symbol is not in a section with content.
+fn symbol_file_offset(elf: &ElfReader<'_>, sym: &Sym) -> Result<Option<Offset>, ElfReadError> {
+    if matches!(sym.shndx.0, c::SHN_UNDEF | c::SHN_ABS | c::SHN_COMMON) {
+        return Ok(None);
+    }
+
+    let sh = elf.section_header(sym.shndx)?;
+    if sh.r#type == c::SHT_NOBITS {
+        return Ok(None);
+    }
+
+    Ok(sym
+        .value
+        .u64()
+        .checked_sub(sh.addr.u64())
+        .and_then(|rel| sh.offset.checked_add(rel)))
+}
+
+fn sym_display_name(
+    elf: &ElfReader<'_>,
+    sym: &Sym,
+    source: SymbolSource,
+    demangle: bool,
+) -> Result<String, ElfReadError> {
+    let name = if sym.info.r#type() == c::STT_SECTION {
         elf.sh_string(elf.section_header(sym.shndx)?.name)?
-            .to_string()
     } else {
-        elf.string(sym.name)?.to_string()
-    })
+        match source {
+            SymbolSource::Static => elf.string(sym.name)?,
+            SymbolSource::Dynamic => elf.dyn_string(sym.name)?,
+        }
+    };
+    let name = name.to_string();
+
+    Ok(if demangle { demangle_name(&name) } else { name })
+}
+
+/// Demangles symbol names and the symbol part of section names like `.text.<symbol>`.
+/// Names that aren't mangled are kept as is.
+fn demangle_name(name: &str) -> String {
+    let section_symbol = name.strip_prefix('.').and_then(|name| name.split_once('.'));
+    match section_symbol {
+        Some((section, symbol)) => {
+            format!(".{section}.{}", rustc_demangle::demangle(symbol))
+        }
+        None => rustc_demangle::demangle(name).to_string(),
+    }
+}
+
+/// The flags as letters in the order of `readelf -S`, like `WA` for writable data.
+/// Unknown flags are shown as `x`.
+fn shflags_letters(flags: &ShFlags) -> String {
+    const LETTERS: [(ShFlags, char); 13] = [
+        (ShFlags::SHF_WRITE, 'W'),
+        (ShFlags::SHF_ALLOC, 'A'),
+        (ShFlags::SHF_EXECINSTR, 'X'),
+        (ShFlags::SHF_MERGE, 'M'),
+        (ShFlags::SHF_STRINGS, 'S'),
+        (ShFlags::SHF_INFO_LINK, 'I'),
+        (ShFlags::SHF_LINK_ORDER, 'L'),
+        (ShFlags::SHF_OS_NONCONFORMING, 'O'),
+        (ShFlags::SHF_GROUP, 'G'),
+        (ShFlags::SHF_TLS, 'T'),
+        (ShFlags::SHF_COMPRESSED, 'C'),
+        (ShFlags::SHF_GNU_RETAIN, 'R'),
+        (ShFlags::SHF_EXCLUDE, 'E'),
+    ];
+
+    let mut letters = LETTERS
+        .iter()
+        .filter(|(flag, _)| flags.contains(*flag))
+        .map(|&(_, letter)| letter)
+        .collect::<String>();
+    let known = LETTERS
+        .iter()
+        .fold(ShFlags::empty(), |known, &(flag, _)| known | flag);
+    if flags.bits() & !known.bits() != 0 {
+        letters.push('x');
+    }
+    letters
+}
+
+fn display_option<T: Display>(value: &Option<T>) -> String {
+    value.as_ref().map(ToString::to_string).unwrap_or_default()
+}
+
+fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
 }
 
 fn print_table(mut table: Table) {
     table.with(Style::blank());
     println!("{table}");
 }
+
+#[cfg(test)]
+mod tests {
+    use elven_parser::consts::ShFlags;
+
+    use super::shflags_letters;
+
+    #[test]
+    fn section_flag_letters() {
+        let text = ShFlags::SHF_ALLOC | ShFlags::SHF_EXECINSTR;
+        assert_eq!(shflags_letters(&text), "AX");
+        let bss = ShFlags::SHF_ALLOC | ShFlags::SHF_WRITE;
+        assert_eq!(shflags_letters(&bss), "WA");
+        let strings = ShFlags::SHF_MERGE | ShFlags::SHF_STRINGS;
+        assert_eq!(shflags_letters(&strings), "MS");
+        assert_eq!(shflags_letters(&ShFlags::empty()), "");
+    }
+}
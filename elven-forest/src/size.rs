@@ -1,20 +1,192 @@
-use std::borrow::Cow;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+};
 
 use anyhow::{Context, Result};
-use elven_parser::read::ElfReader;
+use bstr::BStr;
+use elven_parser::{
+    consts as c,
+    read::{ElfReader, Shdr},
+};
 
-pub fn analyze_text_bloat(elf: ElfReader<'_>, csv: bool) -> Result<()> {
-    let text = elf
-        .section_header_by_name(b".text")
-        .context(".text not found")?;
+const DEPTH: usize = 4;
 
+/// Prints how much each symbol in the sections contributes to their size, biggest first.
+/// The sizes are the distance to the next symbol.
+pub fn analyze_bloat(elf: &ElfReader<'_>, sections: &[&Shdr], csv: bool) -> Result<()> {
+    if csv {
+        println!(
+            "section,size,{}",
+            (1..=DEPTH)
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+    }
+
+    for &section in sections {
+        let section_name = elf.sh_string(section.name)?;
+        if !csv {
+            println!("\n{section_name}");
+        }
+
+        for (sym, size) in symbol_sizes(elf, section)? {
+            let components = symbol_components(std::str::from_utf8(sym)?, DEPTH, csv)
+                .with_context(|| sym.to_string())?;
+
+            if csv {
+                println!("{section_name},{size},{components}");
+            } else {
+                println!("{size} {components}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Like [`analyze_bloat`], but sums up the sizes of the symbols of every crate, biggest first.
+/// Symbols that aren't Rust symbols are attributed to `<unknown>`.
+pub fn analyze_crate_bloat(elf: &ElfReader<'_>, sections: &[&Shdr], csv: bool) -> Result<()> {
+    let mut crates = HashMap::<String, (u64, usize)>::new();
+    for &section in sections {
+        for (sym, size) in symbol_sizes(elf, section)? {
+            let name = symbol_crate(std::str::from_utf8(sym)?);
+            let (total, count) = crates.entry(name).or_default();
+            *total += size;
+            *count += 1;
+        }
+    }
+
+    let mut crates = crates.into_iter().collect::<Vec<_>>();
+    crates.sort_by(|(a_name, (a_size, _)), (b_name, (b_size, _))| {
+        b_size.cmp(a_size).then_with(|| a_name.cmp(b_name))
+    });
+
+    if csv {
+        println!("crate,size,symbols");
+    } else {
+        println!("\n{:>10} {:>8} crate", "size", "symbols");
+    }
+    for (name, (size, count)) in crates {
+        if csv {
+            println!("{name},{size},{count}");
+        } else {
+            println!("{size:>10} {count:>8} {name}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints how the sizes of the symbols in `section` changed between `old` and `new`,
+/// biggest change first. Unchanged symbols are left out.
+pub fn analyze_text_bloat_diff(
+    old: &ElfReader<'_>,
+    new: &ElfReader<'_>,
+    section: &str,
+    csv: bool,
+) -> Result<()> {
+    let old_sh = old
+        .section_header_by_name(section.as_bytes())
+        .with_context(|| format!("{section} not found in the old file"))?;
+    let new_sh = new
+        .section_header_by_name(section.as_bytes())
+        .with_context(|| format!("{section} not found in the new file"))?;
+    let deltas = size_deltas(&symbol_sizes(old, old_sh)?, &symbol_sizes(new, new_sh)?);
+
+    if csv {
+        println!("delta,old,new,symbol");
+    }
+    for delta in deltas {
+        let name = rustc_demangle::demangle(std::str::from_utf8(delta.name)?).to_string();
+        let size = |size: Option<u64>| size.map(|size| size.to_string()).unwrap_or_default();
+        if csv {
+            let name = if name.contains(',') {
+                format!("\"{name}\"")
+            } else {
+                name
+            };
+            println!(
+                "{:+},{},{},{name}",
+                delta.delta(),
+                size(delta.old),
+                size(delta.new)
+            );
+        } else {
+            println!("{:+} {name}", delta.delta());
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct SizeDelta<'a> {
+    name: &'a BStr,
+    /// `None` if the symbol was added.
+    old: Option<u64>,
+    /// `None` if the symbol was removed.
+    new: Option<u64>,
+}
+
+impl SizeDelta<'_> {
+    fn delta(&self) -> i128 {
+        i128::from(self.new.unwrap_or(0)) - i128::from(self.old.unwrap_or(0))
+    }
+}
+
+/// Symbols with the same name, like local ones, are added up.
+fn size_deltas<'a>(old: &[(&'a BStr, u64)], new: &[(&'a BStr, u64)]) -> Vec<SizeDelta<'a>> {
+    let mut deltas = BTreeMap::<&BStr, SizeDelta<'a>>::new();
+    for (symbols, is_new) in [(old, false), (new, true)] {
+        for &(name, size) in symbols {
+            let delta = deltas.entry(name).or_insert(SizeDelta {
+                name,
+                old: None,
+                new: None,
+            });
+            let total = if is_new {
+                &mut delta.new
+            } else {
+                &mut delta.old
+            };
+            *total.get_or_insert(0) += size;
+        }
+    }
+
+    let mut deltas = deltas
+        .into_values()
+        .filter(|delta| delta.old != delta.new)
+        .collect::<Vec<_>>();
+    deltas.sort_by_key(|delta| std::cmp::Reverse(delta.delta().abs()));
+    deltas
+}
+
+/// The first path component of a demangled Rust symbol, which is the crate name.
+fn symbol_crate(sym: &str) -> String {
+    let Ok(demangled) = rustc_demangle::try_demangle(sym) else {
+        return "<unknown>".to_owned();
+    };
+    let demangled = format!("{demangled:#}");
+
+    path_components(&demangled)
+        .first()
+        .map_or_else(|| "<unknown>".to_owned(), |&name| name.to_owned())
+}
+
+fn symbol_sizes<'a>(elf: &ElfReader<'a>, section: &Shdr) -> Result<Vec<(&'a BStr, u64)>> {
     let syms = elf.symbols().context("symbols not found")?;
 
-    let text_range = text.addr..(text.addr + text.size);
+    let section_range = section.addr..(section.addr + section.size);
 
+    // Section symbols are at the start of the section and would take the size of whatever
+    // is before the first real symbol.
     let mut symbols_sorted = syms
-        .into_iter()
-        .filter(|sym| text_range.contains(&sym.value))
+        .iter()
+        .filter(|sym| sym.info.r#type() != c::STT_SECTION)
+        .filter(|sym| section_range.contains(&sym.value))
         .collect::<Vec<_>>();
 
     symbols_sorted.sort_by_key(|s| s.value);
@@ -35,30 +207,7 @@ pub fn analyze_text_bloat(elf: ElfReader<'_>, csv: bool) -> Result<()> {
     symbol_sizes.sort_by_key(|&(_, size)| size);
     symbol_sizes.reverse();
 
-    let depth = 4;
-
-    if csv {
-        println!(
-            "size,{}",
-            (1..=depth)
-                .map(|x| x.to_string())
-                .collect::<Vec<_>>()
-                .join(",")
-        );
-    }
-
-    for (sym, size) in symbol_sizes {
-        let components = symbol_components(std::str::from_utf8(sym)?, depth, csv)
-            .with_context(|| sym.to_string())?;
-
-        if csv {
-            println!("{size},{components}");
-        } else {
-            println!("{size} {components}");
-        }
-    }
-
-    Ok(())
+    Ok(symbol_sizes)
 }
 
 fn symbol_components(sym: &str, depth: usize, csv: bool) -> Result<String> {
@@ -68,20 +217,12 @@ fn symbol_components(sym: &str, depth: usize, csv: bool) -> Result<String> {
         return Ok(demangled);
     }
 
-    let mut components = if demangled.starts_with('<') {
-        parse_qpath(&demangled)
-            .context("invalid qpath")
-            .and_then(|qpath| qpath_components(qpath))
-            .unwrap_or_else(|_| demangled.split("::").collect::<Vec<_>>())
-    } else {
-        // normal path
-        demangled.split("::").collect::<Vec<_>>()
-    };
+    let mut components = path_components(&demangled);
 
     if components.len() >= depth {
         components.truncate(depth);
     } else {
-        components.extend(std::iter::repeat("_").take(depth - components.len()));
+        components.extend(std::iter::repeat_n("_", depth - components.len()));
     }
 
     let components = components
@@ -96,7 +237,19 @@ fn symbol_components(sym: &str, depth: usize, csv: bool) -> Result<String> {
         .collect::<Vec<_>>();
 
     // qpath
-    return Ok(components.join(","));
+    Ok(components.join(","))
+}
+
+fn path_components(demangled: &str) -> Vec<&str> {
+    if demangled.starts_with('<') {
+        parse_qpath(demangled)
+            .context("invalid qpath")
+            .and_then(|qpath| qpath_components(qpath))
+            .unwrap_or_else(|_| demangled.split("::").collect::<Vec<_>>())
+    } else {
+        // normal path
+        demangled.split("::").collect::<Vec<_>>()
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -132,13 +285,12 @@ fn qpath_components(qpath: QPath<'_>) -> Result<Vec<&str>> {
 // I have no clue what to do about that.
 
 fn parse_qpath(s: &str) -> Result<QPath<'_>> {
-    let mut chars = s.char_indices().skip(1);
     let mut angle_brackets = 1u64;
 
     let mut result = None;
     let mut as_idx = None;
 
-    while let Some((idx, char)) = chars.next() {
+    for (idx, char) in s.char_indices().skip(1) {
         match char {
             '<' => angle_brackets += 1,
             '>' => {
@@ -148,10 +300,8 @@ fn parse_qpath(s: &str) -> Result<QPath<'_>> {
                     break;
                 }
             }
-            ' ' => {
-                if angle_brackets == 1 && as_idx == None {
-                    as_idx = Some(idx);
-                }
+            ' ' if angle_brackets == 1 && as_idx.is_none() => {
+                as_idx = Some(idx);
             }
             _ => {}
         }
@@ -182,9 +332,102 @@ fn parse_qpath(s: &str) -> Result<QPath<'_>> {
 
 #[cfg(test)]
 mod tests {
-    use crate::size::QPath;
+    use std::num::NonZeroU64;
+
+    use bstr::BStr;
+    use elven_parser::{
+        consts::{self as c, ShFlags},
+        read::ElfReader,
+        write::{ElfWriter, Header, Section, Symbol},
+        Addr,
+    };
 
-    use super::{parse_qpath, symbol_components};
+    use super::{
+        parse_qpath, size_deltas, symbol_components, symbol_crate, symbol_sizes, QPath, SizeDelta,
+    };
+    use crate::test_data::load_test_file;
+
+    /// An ELF file with a `.text` section with the symbols at the offsets, in order.
+    /// The last symbol just marks the end.
+    fn elf_with_symbols(symbols: &[(&str, u64)]) -> Vec<u8> {
+        let mut writer = ElfWriter::new(Header::x86_64(c::ET_EXEC));
+
+        let text_name = writer.add_sh_string(b".text");
+        let end = symbols.last().map_or(0, |&(_, offset)| offset);
+        let text = writer
+            .add_section(Section {
+                flags: ShFlags::SHF_ALLOC | ShFlags::SHF_EXECINSTR,
+                addr_align: NonZeroU64::new(16),
+                ..Section::progbits(text_name, vec![0xcc; end as usize + 1])
+            })
+            .unwrap();
+        writer.set_section_addr(text, Addr(0x1000));
+
+        let mut builder = writer.symbol_table_builder();
+        for &(name, offset) in symbols {
+            builder.add_symbol(Symbol {
+                name: name.as_bytes(),
+                r#type: c::SymbolType(c::STT_FUNC),
+                binding: c::SymbolBinding(c::STB_GLOBAL),
+                visibility: c::SymbolVisibility(c::STV_DEFAULT),
+                section: text,
+                value: Addr(0x1000 + offset),
+                size: 0,
+            });
+        }
+        writer.finish_symbol_table(builder).unwrap();
+
+        writer.write().unwrap()
+    }
+
+    #[test]
+    fn size_diff() {
+        let old = elf_with_symbols(&[("same", 0), ("grows", 16), ("removed", 24), ("end", 32)]);
+        let new = elf_with_symbols(&[("same", 0), ("grows", 16), ("added", 48), ("end", 52)]);
+        let sizes = |data| {
+            let elf = ElfReader::new(data).unwrap();
+            symbol_sizes(&elf, elf.section_header_by_name(b".text").unwrap()).unwrap()
+        };
+
+        let deltas = size_deltas(&sizes(&old), &sizes(&new));
+        assert_eq!(
+            deltas,
+            [
+                SizeDelta {
+                    name: BStr::new("grows"),
+                    old: Some(8),
+                    new: Some(32),
+                },
+                SizeDelta {
+                    name: BStr::new("removed"),
+                    old: Some(8),
+                    new: None,
+                },
+                SizeDelta {
+                    name: BStr::new("added"),
+                    old: None,
+                    new: Some(4),
+                },
+            ]
+        );
+        assert_eq!(
+            deltas.iter().map(SizeDelta::delta).collect::<Vec<_>>(),
+            [24, -8, 4]
+        );
+    }
+
+    #[test]
+    fn text_symbol_sizes() {
+        let mmap = load_test_file("hello_world");
+        let elf = ElfReader::new(&mmap).unwrap();
+
+        let text = elf.section_header_by_name(b".text").unwrap();
+        let sizes = symbol_sizes(&elf, text).unwrap();
+        assert!(sizes
+            .iter()
+            .any(|(name, _)| name.starts_with(b"_ZN11hello_world4main")));
+        assert!(sizes.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+    }
 
     #[test]
     fn parse_qpaths() {
@@ -207,6 +450,17 @@ mod tests {
         );
     }
 
+    #[test]
+    fn crate_of_symbol() {
+        assert_eq!(
+            symbol_crate("_ZN11hello_world4main17h0123456789abcdefE"),
+            "hello_world"
+        );
+        let sym = "_ZN106_$LT$$LT$std..path..Iter$u20$as$u20$core..fmt..Debug$GT$..fmt..DebugHelper$u20$as$u20$core..fmt..Debug$GT$3fmt17h4f87ac80fb33df05E";
+        assert_eq!(symbol_crate(sym), "std");
+        assert_eq!(symbol_crate("_start"), "<unknown>");
+    }
+
     #[test]
     fn path_debug_helper() {
         // <<std::path::Components as core::fmt::Debug>::fmt::DebugHelper as core::fmt::Debug>::fmt::h4f87ac80fb33df05
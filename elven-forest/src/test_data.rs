@@ -0,0 +1,16 @@
+use std::{fs, path::Path};
+
+use memmap2::Mmap;
+
+/// Maps a file created by `test_data/create_test_data.sh`.
+pub fn load_test_file(name: &str) -> Mmap {
+    let workspace_root = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+    let path = workspace_root.join("test_data/out").join(name);
+    let file = fs::File::open(&path).unwrap_or_else(|_| {
+        panic!(
+            "test data {} missing, run test_data/create_test_data.sh",
+            path.display()
+        )
+    });
+    unsafe { Mmap::map(&file) }.unwrap()
+}
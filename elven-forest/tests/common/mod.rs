@@ -0,0 +1,31 @@
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+/// A file created by `test_data/create_test_data.sh`.
+pub fn test_data(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../test_data/out")
+        .join(name)
+}
+
+/// Runs elven-forest on the file, which may fail.
+pub fn elven_forest_output(args: &[&str], file: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_elven-forest"))
+        .args(args)
+        .arg(file)
+        .output()
+        .unwrap()
+}
+
+/// Runs elven-forest on the file and returns its output, it must succeed.
+pub fn elven_forest(args: &[&str], file: &Path) -> String {
+    let output = elven_forest_output(args, file);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
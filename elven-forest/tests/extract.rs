@@ -0,0 +1,36 @@
+mod common;
+
+use elven_parser::read::ElfReader;
+
+use crate::common::{elven_forest, test_data};
+
+#[test]
+fn extract_sections() {
+    let file = test_data("hello_world");
+    let dir = std::env::temp_dir();
+    let text_path = dir.join(format!("elven-forest-text-{}", std::process::id()));
+    let bss_path = dir.join(format!("elven-forest-bss-{}", std::process::id()));
+
+    elven_forest(
+        &[
+            &format!("--extract-section=.text:{}", text_path.display()),
+            &format!("--extract-section=.bss:{}", bss_path.display()),
+        ],
+        &file,
+    );
+
+    let data = std::fs::read(&file).unwrap();
+    let elf = ElfReader::new(&data).unwrap();
+    let text = std::fs::read(&text_path).unwrap();
+    let bss = std::fs::read(&bss_path).unwrap();
+    std::fs::remove_file(&text_path).unwrap();
+    std::fs::remove_file(&bss_path).unwrap();
+
+    let text_sh = elf.section_header_by_name(b".text").unwrap();
+    assert_eq!(text.len() as u64, text_sh.size);
+    assert_eq!(text, elf.section_content(text_sh).unwrap());
+
+    let bss_sh = elf.section_header_by_name(b".bss").unwrap();
+    assert_eq!(bss.len() as u64, bss_sh.size);
+    assert!(bss.iter().all(|&b| b == 0));
+}
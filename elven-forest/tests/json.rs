@@ -0,0 +1,342 @@
+#![cfg(feature = "serde")]
+
+mod common;
+
+use std::{num::NonZeroU64, path::Path};
+
+use elven_parser::{
+    consts::{self as c, ShFlags},
+    read::{ElfReader, SymIdx},
+    write::{ElfWriter, Header, Rel, Section, Symbol},
+    Addr,
+};
+
+use crate::common::{elven_forest, elven_forest_output, test_data};
+
+fn elven_forest_json(args: &[&str], file: &Path) -> serde_json::Value {
+    let args = [&["--json"], args].concat();
+    serde_json::from_str(&elven_forest(&args, file)).unwrap()
+}
+
+#[test]
+fn section_headers_as_json() {
+    let json = elven_forest_json(&["--section-headers"], &test_data("hello_world"));
+    let object = json.as_object().unwrap();
+    assert_eq!(object.keys().collect::<Vec<_>>(), ["sections"]);
+
+    let sections = object["sections"].as_array().unwrap();
+    let text = sections
+        .iter()
+        .find(|section| section["name"] == ".text")
+        .unwrap();
+    assert_eq!(text["type"], "SHT_PROGBITS");
+    assert!(text["offset"].as_str().unwrap().starts_with("0x"));
+    assert!(text["size"].is_u64());
+    assert_eq!(text["flags"], 0b110);
+}
+
+#[test]
+fn symbols_are_demangled() {
+    let names = |args: &[&str]| {
+        let json = elven_forest_json(args, &test_data("hello_world"));
+        json["symbols"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|sym| sym["name"].as_str().unwrap().to_owned())
+            .collect::<Vec<_>>()
+    };
+
+    let mangled = names(&["--symbols"]);
+    assert!(mangled
+        .iter()
+        .any(|name| name.starts_with("_ZN11hello_world4main")));
+
+    let demangled = names(&["--symbols", "--demangle"]);
+    assert!(demangled
+        .iter()
+        .any(|name| name.starts_with("hello_world::main")));
+    assert!(!demangled.iter().any(|name| name.starts_with("_ZN")));
+
+    // The last one wins.
+    assert_eq!(names(&["--symbols", "-C", "--no-demangle"]), mangled);
+}
+
+#[test]
+fn sections_are_mapped_to_segments() {
+    let json = elven_forest_json(&["--program-headers"], &test_data("hello_world"));
+    let program_headers = json["program_headers"].as_array().unwrap();
+    let mapping = json["segment_sections"].as_array().unwrap();
+    assert_eq!(program_headers.len(), mapping.len());
+
+    let sections_of = |ty: &str| {
+        let idx = program_headers
+            .iter()
+            .position(|ph| ph["type"] == ty)
+            .unwrap();
+        mapping[idx]["sections"].as_str().unwrap().to_owned()
+    };
+    assert_eq!(sections_of("PT_INTERP"), ".interp");
+    assert_eq!(sections_of("PT_DYNAMIC"), ".dynamic");
+}
+
+#[test]
+fn version_needs_are_listed() {
+    let json = elven_forest_json(&["--version-info"], &test_data("hello_world"));
+    let needs = json["version_needs"].as_array().unwrap();
+
+    assert!(needs.iter().any(|need| need["library"] == "libc.so.6"
+        && need["version"].as_str().unwrap().starts_with("GLIBC_2.")));
+}
+
+#[test]
+fn version_definitions_are_listed() {
+    let json = elven_forest_json(&["--version-info"], &test_data("exports_lib.so"));
+    let defs = json["version_defs"].as_array().unwrap();
+
+    let defs = defs
+        .iter()
+        .map(|def| {
+            (
+                def["version"].as_str().unwrap(),
+                def["index"].as_u64().unwrap(),
+                def["flags"].as_str().unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        defs,
+        [("exports_lib.so", 1, "BASE"), ("EXPORTS_1.0", 2, "")]
+    );
+}
+
+#[test]
+fn relocation_types_are_named_for_the_machine() {
+    let types = |name: &str| {
+        let json = elven_forest_json(&["--relocs"], &test_data(name));
+        json["relocations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|rel| rel["type"].as_str().unwrap().to_owned())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        types("relocs_aarch64.o"),
+        [
+            "R_AARCH64_CALL26",
+            "R_AARCH64_ADR_PREL_PG_HI21",
+            "R_AARCH64_ADD_ABS_LO12_NC",
+            "R_AARCH64_LDST64_ABS_LO12_NC",
+            "R_AARCH64_JUMP26",
+            "R_AARCH64_ABS64",
+        ]
+    );
+    assert_eq!(
+        types("relocs_riscv.o"),
+        [
+            "R_RISCV_CALL",
+            "R_RISCV_HI20",
+            "R_RISCV_LO12_I",
+            "R_RISCV_PCREL_HI20",
+            "R_RISCV_PCREL_LO12_I",
+            "R_RISCV_64",
+        ]
+    );
+}
+
+/// There is no assembler for `SHT_REL` on x86-64 around, so the object is written by hand.
+#[test]
+fn rel_relocations_have_no_addend() {
+    let mut writer = ElfWriter::new(Header::x86_64(c::ET_REL));
+
+    let text_name = writer.add_sh_string(b".text");
+    let text = writer
+        .add_section(Section {
+            flags: ShFlags::SHF_ALLOC | ShFlags::SHF_EXECINSTR,
+            addr_align: NonZeroU64::new(16),
+            ..Section::progbits(text_name, vec![0xe8, 0, 0, 0, 0])
+        })
+        .unwrap();
+
+    let mut symbols = writer.symbol_table_builder();
+    symbols.add_symbol(Symbol {
+        name: b"callee",
+        r#type: c::SymbolType(c::STT_NOTYPE),
+        binding: c::SymbolBinding(c::STB_GLOBAL),
+        visibility: c::SymbolVisibility(c::STV_DEFAULT),
+        section: c::SectionIdx(c::SHN_UNDEF),
+        value: Addr(0),
+        size: 0,
+    });
+    writer.finish_symbol_table(symbols).unwrap();
+
+    let rel_name = writer.add_sh_string(b".rel.text");
+    let rels = vec![Rel {
+        offset: Addr(1),
+        sym: SymIdx(1),
+        r#type: c::R_X86_64_PC32,
+    }];
+    writer.add_rel_section(rel_name, text, rels).unwrap();
+
+    let path = std::env::temp_dir().join(format!("elven-forest-rel-{}.o", std::process::id()));
+    std::fs::write(&path, writer.write().unwrap()).unwrap();
+    let json = elven_forest_json(&["--relocs"], &path);
+    let text = elven_forest(&["--relocs"], &path);
+    std::fs::remove_file(&path).unwrap();
+
+    let relocations = json["relocations"].as_array().unwrap();
+    assert_eq!(relocations.len(), 1);
+    let rel = &relocations[0];
+    assert_eq!(rel["section"], ".rel.text");
+    assert_eq!(rel["symbol"], "callee");
+    assert_eq!(rel["type"], "R_X86_64_PC32");
+    assert!(rel["addend"].is_null());
+
+    let row = text
+        .lines()
+        .find(|line| line.contains("R_X86_64_PC32"))
+        .unwrap_or_else(|| panic!("{text}"));
+    let cells = row.split_whitespace().collect::<Vec<_>>();
+    assert_eq!(cells, [".rel.text", "callee", "0x1", "R_X86_64_PC32", "-"]);
+}
+
+/// ARM32 objects use `SHT_REL`, but only ELF64 can be read, so they must not print an empty
+/// relocation table.
+#[test]
+fn elf32_relocations_are_rejected() {
+    let output = elven_forest_output(&["--relocs"], &test_data("relocs_arm.o"));
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("ELFCLASS32 is not supported"), "{stderr}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!stdout.contains("Relocations"), "{stdout}");
+}
+
+#[test]
+fn uncovered_sections() {
+    let json = elven_forest_json(&["--uncovered"], &test_data("hello_world"));
+    assert_eq!(json["uncovered"], serde_json::json!([]));
+
+    let mut writer = ElfWriter::new(Header::x86_64(c::ET_EXEC));
+    let text_name = writer.add_sh_string(b".text");
+    let text = writer
+        .add_section(Section {
+            flags: ShFlags::SHF_ALLOC | ShFlags::SHF_EXECINSTR,
+            ..Section::progbits(text_name, vec![0xc3; 4])
+        })
+        .unwrap();
+    writer.set_section_addr(text, Addr(0x401000));
+
+    let path = std::env::temp_dir().join(format!("elven-forest-unmapped-{}", std::process::id()));
+    std::fs::write(&path, writer.write().unwrap()).unwrap();
+    let json = elven_forest_json(&["--uncovered"], &path);
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        json["uncovered"],
+        serde_json::json!([{ "name": ".text", "addr": "0x401000", "size": 4 }])
+    );
+}
+
+#[test]
+fn dynamic_values_are_decoded() {
+    let json = elven_forest_json(&["--dyns"], &test_data("hello_world"));
+    let dynamic = json["dynamic"].as_array().unwrap();
+
+    let value_of = |tag: &str| {
+        let entry = dynamic.iter().find(|entry| entry["tag"] == tag).unwrap();
+        entry["value"].as_str().unwrap().to_owned()
+    };
+    let flags_1 = value_of("DT_FLAGS_1");
+    assert!(
+        flags_1.split(" | ").any(|flag| flag == "DF_1_PIE"),
+        "{flags_1}"
+    );
+    assert!(value_of("DT_FLAGS").starts_with("DF_"));
+    assert!(value_of("DT_STRTAB").starts_with("0x"));
+    assert_eq!(value_of("DT_PLTREL"), "RELA");
+
+    let needed = dynamic
+        .iter()
+        .filter(|entry| entry["tag"] == "DT_NEEDED")
+        .map(|entry| entry["value"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert!(needed.contains(&"libc.so.6"), "{needed:?}");
+}
+
+#[test]
+fn notes_are_listed() {
+    let json = elven_forest_json(&["--notes"], &test_data("hello_world"));
+    let notes = json["notes"].as_array().unwrap();
+
+    let build_id = notes
+        .iter()
+        .find(|note| note["type"] == "NT_GNU_BUILD_ID")
+        .unwrap();
+    assert_eq!(build_id["owner"], "GNU");
+    assert_eq!(build_id["location"], ".note.gnu.build-id");
+    assert_eq!(build_id["size"], 20);
+    let id = build_id["description"]
+        .as_str()
+        .unwrap()
+        .strip_prefix("Build ID: ")
+        .unwrap();
+    assert_eq!(id.len(), 40);
+    assert!(id.chars().all(|c| c.is_ascii_hexdigit()));
+
+    assert!(notes.iter().any(|note| note["type"] == "NT_GNU_ABI_TAG"
+        && note["description"]
+            .as_str()
+            .unwrap()
+            .starts_with("OS: Linux")));
+}
+
+#[test]
+fn symbol_file_offsets() {
+    let path = test_data("hello_world");
+    let main = |args: &[&str]| {
+        let json = elven_forest_json(args, &path);
+        json["symbols"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|sym| sym["name"] == "main")
+            .unwrap()
+            .clone()
+    };
+
+    assert!(main(&["--symbols"]).get("adj_offset").is_none());
+
+    let data = std::fs::read(&path).unwrap();
+    let elf = ElfReader::new(&data).unwrap();
+    let text = elf.section_header_by_name(b".text").unwrap();
+    let value = elf.symbol_by_name(b"main").unwrap().value;
+    let offset = text.offset + (value.u64() - text.addr.u64());
+
+    let main = main(&["--symbols", "--file-offsets"]);
+    assert_eq!(main["adj_offset"], offset.to_string());
+}
+
+#[test]
+fn eh_frame_hdr_points_to_fdes() {
+    let json = elven_forest_json(&["--eh-frame"], &test_data("hello_world"));
+    let fdes = json["eh_frame"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|entry| entry["kind"] == "FDE")
+        .collect::<Vec<_>>();
+    let table = json["eh_frame_hdr"].as_array().unwrap();
+    assert_eq!(table.len(), fdes.len());
+
+    for entry in table {
+        let fde = fdes
+            .iter()
+            .find(|fde| fde["offset"] == entry["fde_offset"])
+            .unwrap();
+        let pc_range = fde["pc_range"].as_str().unwrap();
+        assert!(pc_range.starts_with(entry["initial_pc"].as_str().unwrap()));
+    }
+}
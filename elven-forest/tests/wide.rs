@@ -0,0 +1,38 @@
+mod common;
+
+use bstr::ByteSlice;
+use elven_parser::read::ElfReader;
+
+use crate::common::{elven_forest, test_data};
+
+fn symbols(args: &[&str]) -> String {
+    let args = [&["--symbols", "--demangle"], args].concat();
+    elven_forest(&args, &test_data("hello_world"))
+}
+
+#[test]
+fn long_names_are_truncated() {
+    let data = std::fs::read(test_data("hello_world")).unwrap();
+    let elf = ElfReader::new(&data).unwrap();
+    let longest = elf
+        .symbols()
+        .unwrap()
+        .iter()
+        .map(|sym| rustc_demangle::demangle(elf.string(sym.name).unwrap().to_str().unwrap()))
+        .map(|name| name.to_string())
+        .max_by_key(|name| name.chars().count())
+        .unwrap();
+    assert!(longest.chars().count() > 40, "{longest}");
+    let truncated = format!("{}…", longest.chars().take(39).collect::<String>());
+
+    let normal = symbols(&[]);
+    assert!(normal.contains(&truncated), "{normal}");
+    assert!(!normal.contains(&longest), "{normal}");
+
+    let wide = symbols(&["--wide"]);
+    assert!(wide.contains(&longest), "{wide}");
+
+    let narrow = symbols(&["--max-width=20"]);
+    let truncated = format!("{}…", longest.chars().take(19).collect::<String>());
+    assert!(narrow.contains(&truncated), "{narrow}");
+}
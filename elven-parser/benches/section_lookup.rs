@@ -0,0 +1,85 @@
+//! Looks up the names of all symbols in a synthetic file with thousands of sections, once with
+//! a fresh reader for every lookup and once with a single reader that caches the section
+//! headers and string tables.
+//!
+//! Run with `cargo bench -p elven-parser`.
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use elven_parser::{
+    consts::{self as c, ShFlags},
+    read::ElfReader,
+    write::{ElfWriter, Header, Section, Symbol},
+    Addr,
+};
+
+const SECTIONS: usize = 4000;
+const ROUNDS: u32 = 5;
+
+fn large_elf() -> Vec<u8> {
+    let mut writer = ElfWriter::new(Header::x86_64(c::ET_REL));
+
+    let mut sections = Vec::new();
+    for i in 0..SECTIONS {
+        let name = writer.add_sh_string(format!(".text.function_{i}").as_bytes());
+        let section = writer
+            .add_section(Section {
+                flags: ShFlags::SHF_ALLOC | ShFlags::SHF_EXECINSTR,
+                ..Section::progbits(name, vec![0xc3])
+            })
+            .unwrap();
+        sections.push(section);
+    }
+
+    let mut symbols = writer.symbol_table_builder();
+    let names = (0..SECTIONS)
+        .map(|i| format!("function_{i}"))
+        .collect::<Vec<_>>();
+    for (name, &section) in names.iter().zip(&sections) {
+        symbols.add_symbol(Symbol {
+            name: name.as_bytes(),
+            r#type: c::SymbolType(c::STT_FUNC),
+            binding: c::SymbolBinding(c::STB_GLOBAL),
+            visibility: c::SymbolVisibility(c::STV_DEFAULT),
+            section,
+            value: Addr(0),
+            size: 1,
+        });
+    }
+    writer.finish_symbol_table(symbols).unwrap();
+
+    writer.write().unwrap()
+}
+
+fn time(name: &str, mut f: impl FnMut()) {
+    let mut best = Duration::MAX;
+    for _ in 0..ROUNDS {
+        let start = Instant::now();
+        f();
+        best = best.min(start.elapsed());
+    }
+    println!("{name:30} {best:?}");
+}
+
+fn main() {
+    let data = large_elf();
+    let symbols = ElfReader::new(&data).unwrap().symbols().unwrap();
+    println!("{SECTIONS} sections, {} symbols", symbols.len());
+
+    time("fresh reader for every lookup", || {
+        for sym in symbols {
+            let elf = ElfReader::new(&data).unwrap();
+            black_box(elf.string(sym.name).unwrap());
+        }
+    });
+
+    time("one cached reader", || {
+        let elf = ElfReader::new(&data).unwrap();
+        for sym in symbols {
+            black_box(elf.string(sym.name).unwrap());
+        }
+    });
+}
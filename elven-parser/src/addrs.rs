@@ -26,6 +26,16 @@ impl Addr {
     pub fn u64(self) -> u64 {
         self.value
     }
+
+    /// Adds `rhs`, returning `None` on overflow instead of wrapping around.
+    pub fn checked_add(self, rhs: u64) -> Option<Self> {
+        self.value.checked_add(rhs).map(Addr)
+    }
+
+    /// Subtracts `rhs`, returning `None` if the result would be negative.
+    pub fn checked_sub(self, rhs: u64) -> Option<Self> {
+        self.value.checked_sub(rhs).map(Addr)
+    }
 }
 
 impl Debug for Addr {
@@ -40,6 +50,21 @@ impl Display for Addr {
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for Addr {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Accepts the hex string from serializing, or a plain number.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Addr {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_hex(deserializer).map(Addr)
+    }
+}
+
 impl Add<Self> for Addr {
     type Output = Self;
 
@@ -76,6 +101,16 @@ impl Offset {
     pub fn u64(self) -> u64 {
         self.value
     }
+
+    /// Adds `rhs`, returning `None` on overflow instead of wrapping around.
+    pub fn checked_add(self, rhs: u64) -> Option<Self> {
+        self.value.checked_add(rhs).map(Offset)
+    }
+
+    /// Subtracts `rhs`, returning `None` if the result would be negative.
+    pub fn checked_sub(self, rhs: u64) -> Option<Self> {
+        self.value.checked_sub(rhs).map(Offset)
+    }
 }
 
 impl ToIdxUsize for Offset {
@@ -96,6 +131,21 @@ impl Display for Offset {
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for Offset {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Accepts the hex string from serializing, or a plain number.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Offset {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_hex(deserializer).map(Offset)
+    }
+}
+
 impl Add<Self> for Offset {
     type Output = Self;
 
@@ -153,3 +203,21 @@ impl From<u64> for Offset {
         Offset(value)
     }
 }
+
+#[cfg(feature = "serde")]
+fn deserialize_hex<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum HexOrValue {
+        Value(u64),
+        Hex(String),
+    }
+
+    match <HexOrValue as serde::Deserialize>::deserialize(deserializer)? {
+        HexOrValue::Value(value) => Ok(value),
+        HexOrValue::Hex(hex) => hex
+            .strip_prefix("0x")
+            .and_then(|digits| u64::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| serde::de::Error::custom(format_args!("invalid hex number {hex}"))),
+    }
+}
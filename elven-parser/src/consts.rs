@@ -65,6 +65,12 @@ macro_rules! const_group_with_fmt {
 
         impl Eq for $struct_name {}
 
+        impl std::hash::Hash for $struct_name {
+            fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+                self.0.hash(state)
+            }
+        }
+
         impl PartialOrd<$ty> for $struct_name {
             fn partial_cmp(&self, other: &$ty) -> Option<std::cmp::Ordering> {
                 self.0.partial_cmp(other)
@@ -89,6 +95,63 @@ macro_rules! const_group_with_fmt {
                 wrap.0
             }
         }
+
+        /// Serialized as the name of the constant, or the number if there is none.
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for $struct_name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                match self.0 {
+                    $(
+                        $value => serializer.serialize_str(stringify!($name)),
+                    )*
+                    a => serde::Serialize::serialize(&a, serializer),
+                }
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> serde::Deserialize<'de> for $struct_name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                match <NameOrValue<$ty> as serde::Deserialize>::deserialize(deserializer)? {
+                    NameOrValue::Value(value) => Ok($struct_name(value)),
+                    $(
+                        NameOrValue::Name(name) if name == stringify!($name) => Ok($struct_name($name)),
+                    )*
+                    NameOrValue::Name(name) => Err(serde::de::Error::custom(format_args!(
+                        "unknown {} constant {name}",
+                        $group_name
+                    ))),
+                }
+            }
+        }
+    };
+}
+
+/// The serialized form of a constant, see [`const_group_with_fmt`].
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum NameOrValue<T> {
+    Value(T),
+    Name(String),
+}
+
+/// Flags are serialized as their bits, including the ones without a name.
+macro_rules! serde_flags {
+    ($struct_name:ident($ty:ty)) => {
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for $struct_name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serde::Serialize::serialize(&self.bits(), serializer)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> serde::Deserialize<'de> for $struct_name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                <$ty as serde::Deserialize>::deserialize(deserializer).map(bytemuck::cast)
+            }
+        }
     };
 }
 
@@ -158,13 +221,28 @@ const_group_with_fmt! {
     pub const ET_EXEC = 2;
     pub const ET_DYN = 3;
     pub const ET_CORE = 4;
+    pub const ET_LOPROC = 0xff00; /* Processor-specific range start */
+    pub const ET_HIPROC = 0xffff; /* Processor-specific range end */
 }
 
 const_group_with_fmt! {
     pub struct Machine(u16): "EM"
 
     pub const EM_NONE = 0; /* No machine */
+    pub const EM_M32 = 1; /* AT&T WE 32100 */
+    pub const EM_SPARC = 2; /* SUN SPARC */
+    pub const EM_386 = 3; /* Intel 80386 */
+    pub const EM_MIPS = 8; /* MIPS R3000 big-endian */
+    pub const EM_PPC = 20; /* PowerPC */
+    pub const EM_PPC64 = 21; /* PowerPC 64-bit */
+    pub const EM_S390 = 22; /* IBM S390 */
+    pub const EM_ARM = 40; /* ARM */
+    pub const EM_ALPHA = 41; /* Digital Alpha */
+    pub const EM_IA_64 = 50; /* Intel Merced */
+    pub const EM_MIPS_X = 51; /* Stanford MIPS-X */
     pub const EM_X86_64 = 62; /* AMD x86-64 architecture */
+    pub const EM_AARCH64 = 183; /* ARM AARCH64 */
+    pub const EM_RISCV = 243; /* RISC-V */
 }
 
 pub const EV_NONE: u32 = 0;
@@ -254,6 +332,10 @@ bitflags! {
     }
 }
 
+serde_flags!(ShFlags(u64));
+
+pub const GRP_COMDAT: u32 = 0x1; /* Mark group as COMDAT.  */
+
 pub const SHF_MASKOS: u64 = 0x0ff00000; /* OS-specific.  */
 pub const SHF_MASKPROC: u64 = 0xf0000000; /* Processor-specific */
 
@@ -308,6 +390,8 @@ bitflags! {
     }
 }
 
+serde_flags!(PhFlags(u32));
+
 impl Display for PhFlags {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if self.is_empty() {
@@ -417,6 +501,109 @@ const_group_with_fmt! {
     pub const R_X86_64_NUM = 43;
 }
 
+const_group_with_fmt! {
+    pub struct RAArch64(u32): "R_AARCH64"
+
+    pub const R_AARCH64_NONE = 0; /* No relocation.  */
+    pub const R_AARCH64_ABS64 = 257; /* Direct 64 bit. */
+    pub const R_AARCH64_ABS32 = 258; /* Direct 32 bit.  */
+    pub const R_AARCH64_ABS16 = 259; /* Direct 16-bit.  */
+    pub const R_AARCH64_PREL64 = 260; /* PC-relative 64-bit.	*/
+    pub const R_AARCH64_PREL32 = 261; /* PC-relative 32-bit.	*/
+    pub const R_AARCH64_PREL16 = 262; /* PC-relative 16-bit.	*/
+    pub const R_AARCH64_ADR_PREL_PG_HI21 = 275; /* Page-rel. ADRP imm. from 32:12.  */
+    pub const R_AARCH64_ADD_ABS_LO12_NC = 277; /* Dir. ADD imm. from bits 11:0.  */
+    pub const R_AARCH64_LDST8_ABS_LO12_NC = 278; /* Likewise for LD/ST; no check. */
+    pub const R_AARCH64_JUMP26 = 282; /* PC-rel. B imm. from bits 27:2.  */
+    pub const R_AARCH64_CALL26 = 283; /* Likewise for CALL.  */
+    pub const R_AARCH64_LDST16_ABS_LO12_NC = 284; /* Dir. ADD imm. from bits 11:1.  */
+    pub const R_AARCH64_LDST32_ABS_LO12_NC = 285; /* Likewise for bits 11:2.  */
+    pub const R_AARCH64_LDST64_ABS_LO12_NC = 286; /* Likewise for bits 11:3.  */
+    pub const R_AARCH64_LDST128_ABS_LO12_NC = 299; /* Likewise for bits 11:4.  */
+    pub const R_AARCH64_COPY = 1024; /* Copy symbol at runtime.  */
+    pub const R_AARCH64_GLOB_DAT = 1025; /* Create GOT entry.  */
+    pub const R_AARCH64_JUMP_SLOT = 1026; /* Create PLT entry.  */
+    pub const R_AARCH64_RELATIVE = 1027; /* Adjust by program base.  */
+}
+
+const_group_with_fmt! {
+    pub struct RRiscv(u32): "R_RISCV"
+
+    pub const R_RISCV_NONE = 0; /* No reloc */
+    pub const R_RISCV_32 = 1; /* Direct 32 bit */
+    pub const R_RISCV_64 = 2; /* Direct 64 bit */
+    pub const R_RISCV_RELATIVE = 3; /* Adjust by program base */
+    pub const R_RISCV_COPY = 4; /* Copy symbol at runtime */
+    pub const R_RISCV_JUMP_SLOT = 5; /* Create PLT entry */
+    pub const R_RISCV_BRANCH = 16; /* PC-relative branch */
+    pub const R_RISCV_JAL = 17; /* PC-relative jump */
+    pub const R_RISCV_CALL = 18; /* PC-relative call */
+    pub const R_RISCV_CALL_PLT = 19; /* PC-relative call through the PLT */
+    pub const R_RISCV_GOT_HI20 = 20; /* PC-relative GOT reference */
+    pub const R_RISCV_PCREL_HI20 = 23; /* PC-relative reference, high 20 bits */
+    pub const R_RISCV_PCREL_LO12_I = 24; /* PC-relative reference, low 12 bits, I-type */
+    pub const R_RISCV_PCREL_LO12_S = 25; /* PC-relative reference, low 12 bits, S-type */
+    pub const R_RISCV_HI20 = 26; /* Absolute address, high 20 bits */
+    pub const R_RISCV_LO12_I = 27; /* Absolute address, low 12 bits, I-type */
+    pub const R_RISCV_LO12_S = 28; /* Absolute address, low 12 bits, S-type */
+    pub const R_RISCV_RELAX = 51; /* Instruction can be relaxed */
+}
+
+// ------------------
+// Notes
+// ------------------
+
+const_group_with_fmt! {
+    pub struct NoteType(u32): "NT"
+
+    pub const NT_GNU_ABI_TAG = 1; /* ABI information */
+    pub const NT_GNU_HWCAP = 2; /* Synthetic hwcap information */
+    pub const NT_GNU_BUILD_ID = 3; /* Unique build ID bitstring */
+    pub const NT_GNU_GOLD_VERSION = 4; /* Version note generated by GNU gold */
+    pub const NT_GNU_PROPERTY_TYPE_0 = 5; /* Program property */
+}
+
+// The meaning of the type depends on the note owner, these are the ones of the "CORE" owner.
+const_group_with_fmt! {
+    pub struct CoreNoteType(u32): "NT"
+
+    pub const NT_PRSTATUS = 1; /* Contains copy of prstatus struct */
+    pub const NT_PRFPREG = 2; /* Contains copy of fpregset struct */
+    pub const NT_PRPSINFO = 3; /* Contains copy of prpsinfo struct */
+    pub const NT_SIGINFO = 0x53494749; /* Contains copy of siginfo_t */
+    pub const NT_FILE = 0x46494c45; /* Contains information about mapped files */
+}
+
+// ------------------
+// Symbol versioning
+// ------------------
+
+const_group_with_fmt! {
+    pub struct Versym(u16): "VER_NDX"
+
+    pub const VER_NDX_LOCAL = 0; /* Symbol is local.  */
+    pub const VER_NDX_GLOBAL = 1; /* Symbol is global.  */
+}
+pub const VERSYM_HIDDEN: u16 = 0x8000; /* Symbol is hidden.  */
+pub const VERSYM_VERSION: u16 = 0x7fff; /* Mask for the version index.  */
+
+pub const VER_DEF_CURRENT: u16 = 1; /* Current version */
+pub const VER_NEED_CURRENT: u16 = 1; /* Current version */
+
+pub const VER_FLG_BASE: u16 = 0x1; /* Version definition of file itself */
+pub const VER_FLG_WEAK: u16 = 0x2; /* Weak version identifier */
+
+impl Versym {
+    /// The index of the version, without the hidden bit.
+    pub fn index(self) -> u16 {
+        self.0 & VERSYM_VERSION
+    }
+
+    pub fn is_hidden(self) -> bool {
+        self.0 & VERSYM_HIDDEN != 0
+    }
+}
+
 // ------------------
 // Dynamic
 // ------------------
@@ -506,6 +693,71 @@ const_group_with_fmt! {
     pub const DT_VERNEEDNUM = 0x6fffffff; /* Number of needed versions */
 }
 
+bitflags! {
+    /// The value of `DT_FLAGS`.
+    pub struct DynFlags: u64 {
+        const DF_ORIGIN =       0x00000001;	/* Object may use DF_ORIGIN */
+        const DF_SYMBOLIC =     0x00000002;	/* Symbol resolutions starts here */
+        const DF_TEXTREL =      0x00000004;	/* Object contains text relocations */
+        const DF_BIND_NOW =     0x00000008;	/* No lazy binding for this object */
+        const DF_STATIC_TLS =   0x00000010;	/* Module uses the static TLS model */
+    }
+}
+
+impl Display for DynFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            f.write_str("")
+        } else {
+            write!(f, "{:?}", self)
+        }
+    }
+}
+
+bitflags! {
+    /// The value of `DT_FLAGS_1`.
+    pub struct DynFlags1: u64 {
+        const DF_1_NOW =        0x00000001;	/* Set RTLD_NOW for this object.  */
+        const DF_1_GLOBAL =     0x00000002;	/* Set RTLD_GLOBAL for this object.  */
+        const DF_1_GROUP =      0x00000004;	/* Set RTLD_GROUP for this object.  */
+        const DF_1_NODELETE =   0x00000008;	/* Set RTLD_NODELETE for this object.*/
+        const DF_1_LOADFLTR =   0x00000010;	/* Trigger filtee loading at runtime.*/
+        const DF_1_INITFIRST =  0x00000020;	/* Set RTLD_INITFIRST for this object*/
+        const DF_1_NOOPEN =     0x00000040;	/* Set RTLD_NOOPEN for this object.  */
+        const DF_1_ORIGIN =     0x00000080;	/* $ORIGIN must be handled.  */
+        const DF_1_DIRECT =     0x00000100;	/* Direct binding enabled.  */
+        const DF_1_TRANS =      0x00000200;
+        const DF_1_INTERPOSE =  0x00000400;	/* Object is used to interpose.  */
+        const DF_1_NODEFLIB =   0x00000800;	/* Ignore default lib search path.  */
+        const DF_1_NODUMP =     0x00001000;	/* Object can't be dldump'ed.  */
+        const DF_1_CONFALT =    0x00002000;	/* Configuration alternative created.*/
+        const DF_1_ENDFILTEE =  0x00004000;	/* Filtee terminates filters search. */
+        const DF_1_DISPRELDNE = 0x00008000;	/* Disp reloc applied at build time. */
+        const DF_1_DISPRELPND = 0x00010000;	/* Disp reloc applied at run-time.  */
+        const DF_1_NODIRECT =   0x00020000;	/* Object has no-direct binding. */
+        const DF_1_IGNMULDEF =  0x00040000;
+        const DF_1_NOKSYMS =    0x00080000;
+        const DF_1_NOHDR =      0x00100000;
+        const DF_1_EDITED =     0x00200000;	/* Object is modified after built.  */
+        const DF_1_NORELOC =    0x00400000;
+        const DF_1_SYMINTPOSE = 0x00800000;	/* Object has individual interposers.  */
+        const DF_1_GLOBAUDIT =  0x01000000;	/* Global auditing required.  */
+        const DF_1_SINGLETON =  0x02000000;	/* Singleton symbols are used.  */
+        const DF_1_STUB =       0x04000000;
+        const DF_1_PIE =        0x08000000;
+    }
+}
+
+impl Display for DynFlags1 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            f.write_str("")
+        } else {
+            write!(f, "{:?}", self)
+        }
+    }
+}
+
 pub const DT_ENCODING: u64 = 32; /* Start of encoded range */
 pub const DT_LOOS: u64 = 0x6000000d; /* Start of OS-specific */
 pub const DT_HIOS: u64 = 0x6ffff000; /* End of OS-specific */
@@ -516,6 +768,30 @@ pub const DT_ADDRRNGHI: u64 = 0x6ffffeff;
 pub const DT_VALRNGLO: u64 = 0x6ffffd00;
 pub const DT_VALRNGHI: u64 = 0x6ffffdff;
 
+// ------------------
+// Exception handling frames
+// ------------------
+
+/* Pointer encodings in .eh_frame, the lower four bits are the format. */
+pub const DW_EH_PE_absptr: u8 = 0x00;
+pub const DW_EH_PE_uleb128: u8 = 0x01;
+pub const DW_EH_PE_udata2: u8 = 0x02;
+pub const DW_EH_PE_udata4: u8 = 0x03;
+pub const DW_EH_PE_udata8: u8 = 0x04;
+pub const DW_EH_PE_sleb128: u8 = 0x09;
+pub const DW_EH_PE_sdata2: u8 = 0x0a;
+pub const DW_EH_PE_sdata4: u8 = 0x0b;
+pub const DW_EH_PE_sdata8: u8 = 0x0c;
+
+/* The upper bits are what the value is relative to. */
+pub const DW_EH_PE_pcrel: u8 = 0x10;
+pub const DW_EH_PE_textrel: u8 = 0x20;
+pub const DW_EH_PE_datarel: u8 = 0x30;
+pub const DW_EH_PE_funcrel: u8 = 0x40;
+pub const DW_EH_PE_aligned: u8 = 0x50;
+pub const DW_EH_PE_indirect: u8 = 0x80;
+pub const DW_EH_PE_omit: u8 = 0xff;
+
 impl SectionIdx {
     pub fn usize(self) -> usize {
         self.0 as usize
@@ -0,0 +1,503 @@
+//! The call frame information in `.eh_frame`. This only parses the CIE and FDE records to know
+//! which code they cover, the call frame instructions are left uninterpreted.
+//!
+//! See <https://refspecs.linuxfoundation.org/LSB_5.0.0/LSB-Core-generic/LSB-Core-generic/ehframechpt.html>
+
+use bstr::BStr;
+
+use crate::{
+    consts as c,
+    read::{ElfReadError, Result},
+    Addr, Offset,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub enum EhFrameEntry<'a> {
+    Cie(CieData<'a>),
+    Fde(FdeData<'a>),
+}
+
+/// A Common Information Entry, shared by many FDEs.
+#[derive(Debug, Clone, Copy)]
+pub struct CieData<'a> {
+    /// The offset of the entry in the section.
+    pub offset: Offset,
+    /// The length of the entry, without the length field itself.
+    pub length: u64,
+    pub version: u8,
+    pub augmentation: &'a BStr,
+    pub code_alignment: u64,
+    pub data_alignment: i64,
+    pub return_address_register: u64,
+    pub augmentation_data: &'a [u8],
+    /// The `DW_EH_PE_*` encoding of the PC range in FDEs, from the `R` augmentation.
+    pub fde_pointer_encoding: u8,
+    pub instructions: &'a [u8],
+}
+
+/// A Frame Description Entry, describing the frames of one range of code.
+#[derive(Debug, Clone, Copy)]
+pub struct FdeData<'a> {
+    /// The offset of the entry in the section.
+    pub offset: Offset,
+    /// The length of the entry, without the length field itself.
+    pub length: u64,
+    /// The distance from the CIE pointer field back to the CIE.
+    pub cie_pointer: u64,
+    /// The offset of the CIE in the section.
+    pub cie_offset: Offset,
+    pub pc_begin: Addr,
+    pub pc_range: u64,
+    pub augmentation_data: &'a [u8],
+    pub instructions: &'a [u8],
+}
+
+/// The `.eh_frame_hdr` section, with a table to find the FDE of a PC with a binary search.
+#[derive(Debug, Clone, Copy)]
+pub struct EhFrameHdr<'a> {
+    pub version: u8,
+    /// The `DW_EH_PE_*` encodings of the fields.
+    pub eh_frame_ptr_encoding: u8,
+    pub fde_count_encoding: u8,
+    pub table_encoding: u8,
+    /// The address of `.eh_frame`.
+    pub eh_frame_ptr: Addr,
+    /// The number of entries in the table, zero if there is no table.
+    pub fde_count: u64,
+    table: Cursor<'a>,
+    addr: Addr,
+}
+
+/// An entry of the `.eh_frame_hdr` table, sorted by the PC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EhFrameHdrEntry {
+    pub initial_pc: Addr,
+    /// The address of the FDE in `.eh_frame`.
+    pub fde: Addr,
+}
+
+impl<'a> EhFrameHdr<'a> {
+    /// `addr` is the address of the section, used for PC and data relative pointers.
+    pub(crate) fn parse(data: &'a [u8], addr: Addr) -> Result<Self> {
+        let mut cursor = Cursor {
+            data,
+            pos: 0,
+            entry: 0,
+        };
+        let version = cursor.u8().map_err(hdr_error)?;
+        if version != 1 {
+            return Err(ElfReadError::InvalidEhFrameHdr(0, "unsupported version"));
+        }
+        let eh_frame_ptr_encoding = cursor.u8().map_err(hdr_error)?;
+        let fde_count_encoding = cursor.u8().map_err(hdr_error)?;
+        let table_encoding = cursor.u8().map_err(hdr_error)?;
+
+        let eh_frame_ptr = cursor
+            .pointer_with_base(eh_frame_ptr_encoding, addr, Some(addr))
+            .map_err(hdr_error)?;
+        // The table is only there if both the count and the entries can be read.
+        let fde_count =
+            if fde_count_encoding == c::DW_EH_PE_omit || table_encoding == c::DW_EH_PE_omit {
+                0
+            } else {
+                cursor
+                    .pointer_with_base(fde_count_encoding, addr, Some(addr))
+                    .map_err(hdr_error)?
+            };
+
+        Ok(Self {
+            version,
+            eh_frame_ptr_encoding,
+            fde_count_encoding,
+            table_encoding,
+            eh_frame_ptr: Addr(eh_frame_ptr),
+            fde_count,
+            table: cursor,
+            addr,
+        })
+    }
+
+    /// The entries of the table. Stops after the first error.
+    pub fn entries(&self) -> impl Iterator<Item = Result<EhFrameHdrEntry>> + 'a {
+        let mut cursor = self.table;
+        let (encoding, addr) = (self.table_encoding, self.addr);
+        let mut failed = false;
+
+        (0..self.fde_count).map_while(move |_| {
+            if failed {
+                return None;
+            }
+            let mut entry = || {
+                cursor.entry = cursor.pos;
+                let initial_pc = cursor.pointer_with_base(encoding, addr, Some(addr))?;
+                let fde = cursor.pointer_with_base(encoding, addr, Some(addr))?;
+                Ok(EhFrameHdrEntry {
+                    initial_pc: Addr(initial_pc),
+                    fde: Addr(fde),
+                })
+            };
+            let entry = entry().map_err(hdr_error);
+            failed = entry.is_err();
+            Some(entry)
+        })
+    }
+}
+
+/// The cursor reports errors for `.eh_frame`, this points them at `.eh_frame_hdr` instead.
+fn hdr_error(err: ElfReadError) -> ElfReadError {
+    match err {
+        ElfReadError::InvalidEhFrame(offset, msg) => ElfReadError::InvalidEhFrameHdr(offset, msg),
+        err => err,
+    }
+}
+
+/// Iterates over the entries of an `.eh_frame` section. Stops after the first error or at the
+/// zero terminator.
+#[derive(Debug, Clone)]
+pub struct EhFrameIterator<'a> {
+    data: &'a [u8],
+    addr: Addr,
+    offset: usize,
+    done: bool,
+}
+
+impl<'a> EhFrameIterator<'a> {
+    /// `addr` is the address of the section, used for PC relative pointers.
+    pub fn new(data: &'a [u8], addr: Addr) -> Self {
+        Self {
+            data,
+            addr,
+            offset: 0,
+            done: false,
+        }
+    }
+
+    fn entry_at(&self, offset: usize) -> Result<Option<(EhFrameEntry<'a>, usize)>> {
+        let mut cursor = Cursor {
+            data: self.data,
+            pos: offset,
+            entry: offset,
+        };
+
+        let length = match cursor.u32()? {
+            0 => return Ok(None),
+            u32::MAX => cursor.u64()?,
+            length => u64::from(length),
+        };
+
+        let end = usize::try_from(length)
+            .ok()
+            .and_then(|length| cursor.pos.checked_add(length))
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(|| cursor.error("length out of bounds"))?;
+        cursor.data = &self.data[..end];
+
+        // Unlike in `.debug_frame`, the CIE ID and pointer are 4 bytes even with an extended
+        // length.
+        let id_pos = cursor.pos;
+        let id = u64::from(cursor.u32()?);
+
+        let entry = if id == 0 {
+            EhFrameEntry::Cie(self.cie(cursor, length)?)
+        } else {
+            let cie_offset = usize::try_from(id)
+                .ok()
+                .and_then(|id| id_pos.checked_sub(id))
+                .ok_or_else(|| cursor.error("CIE pointer out of bounds"))?;
+            let cie = match self.entry_at(cie_offset)? {
+                Some((EhFrameEntry::Cie(cie), _)) => cie,
+                _ => return Err(cursor.error("CIE pointer does not point to a CIE")),
+            };
+            EhFrameEntry::Fde(self.fde(cursor, length, id, &cie)?)
+        };
+
+        Ok(Some((entry, end)))
+    }
+
+    fn cie(&self, mut cursor: Cursor<'a>, length: u64) -> Result<CieData<'a>> {
+        let version = cursor.u8()?;
+        let augmentation = cursor.cstr()?;
+        if !augmentation.is_empty() && !augmentation.starts_with(b"z") {
+            return Err(cursor.error("unsupported augmentation"));
+        }
+        let code_alignment = cursor.uleb128()?;
+        let data_alignment = cursor.sleb128()?;
+        let return_address_register = if version == 1 {
+            u64::from(cursor.u8()?)
+        } else {
+            cursor.uleb128()?
+        };
+
+        let mut fde_pointer_encoding = c::DW_EH_PE_absptr;
+        let augmentation_data = if augmentation.starts_with(b"z") {
+            let mut aug = cursor.augmentation()?;
+            let data = &aug.data[aug.pos..];
+            for &kind in &augmentation[1..] {
+                match kind {
+                    b'R' => fde_pointer_encoding = aug.u8()?,
+                    b'P' => {
+                        let encoding = aug.u8()?;
+                        aug.pointer(encoding, self.addr)?;
+                    }
+                    b'L' => {
+                        aug.u8()?;
+                    }
+                    b'S' | b'B' => {}
+                    _ => return Err(cursor.error("unknown augmentation")),
+                }
+            }
+            data
+        } else {
+            &[]
+        };
+
+        Ok(CieData {
+            offset: Offset(cursor.entry as u64),
+            length,
+            version,
+            augmentation: BStr::new(augmentation),
+            code_alignment,
+            data_alignment,
+            return_address_register,
+            augmentation_data,
+            fde_pointer_encoding,
+            instructions: &cursor.data[cursor.pos..],
+        })
+    }
+
+    fn fde(
+        &self,
+        mut cursor: Cursor<'a>,
+        length: u64,
+        cie_pointer: u64,
+        cie: &CieData<'a>,
+    ) -> Result<FdeData<'a>> {
+        let encoding = cie.fde_pointer_encoding;
+        let pc_begin = cursor.pointer(encoding, self.addr)?;
+        // The range is a length, so it is never relative to anything.
+        let pc_range = cursor.pointer(encoding & 0x0f, self.addr)?;
+
+        let augmentation_data = if cie.augmentation.starts_with(b"z") {
+            let aug = cursor.augmentation()?;
+            &aug.data[aug.pos..]
+        } else {
+            &[]
+        };
+
+        Ok(FdeData {
+            offset: Offset(cursor.entry as u64),
+            length,
+            cie_pointer,
+            cie_offset: cie.offset,
+            pc_begin: Addr(pc_begin),
+            pc_range,
+            augmentation_data,
+            instructions: &cursor.data[cursor.pos..],
+        })
+    }
+}
+
+impl<'a> Iterator for EhFrameIterator<'a> {
+    type Item = Result<EhFrameEntry<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.offset >= self.data.len() {
+            return None;
+        }
+
+        match self.entry_at(self.offset) {
+            Ok(Some((entry, next))) => {
+                self.offset = next;
+                Some(Ok(entry))
+            }
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Reads the fields of one entry.
+#[derive(Debug, Clone, Copy)]
+struct Cursor<'a> {
+    /// The section data, up to the end of the current entry.
+    data: &'a [u8],
+    pos: usize,
+    /// The start of the current entry, for errors.
+    entry: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn error(&self, msg: &'static str) -> ElfReadError {
+        ElfReadError::InvalidEhFrame(self.entry, msg)
+    }
+
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let bytes = self
+            .data
+            .get(self.pos..)
+            .and_then(|rest| rest.get(..N))
+            .ok_or_else(|| self.error("unexpected end of entry"))?;
+        self.pos += N;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(u8::from_ne_bytes(self.bytes()?))
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_ne_bytes(self.bytes()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_ne_bytes(self.bytes()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_ne_bytes(self.bytes()?))
+    }
+
+    fn uleb128(&mut self) -> Result<u64> {
+        let mut result = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8()?;
+            if shift < 64 {
+                result |= u64::from(byte & 0x7f) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+        }
+    }
+
+    fn sleb128(&mut self) -> Result<i64> {
+        let mut result = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8()?;
+            if shift < 64 {
+                result |= i64::from(byte & 0x7f) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if shift < 64 && byte & 0x40 != 0 {
+                    result |= -1 << shift;
+                }
+                return Ok(result);
+            }
+        }
+    }
+
+    fn cstr(&mut self) -> Result<&'a [u8]> {
+        let rest = self.data.get(self.pos..).unwrap_or_default();
+        let len = rest
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| self.error("augmentation string is not nul terminated"))?;
+        self.pos += len + 1;
+        Ok(&rest[..len])
+    }
+
+    /// Reads the ULEB128 length of the augmentation data and returns a cursor that ends with
+    /// the data. Skips over the data in `self`.
+    fn augmentation(&mut self) -> Result<Cursor<'a>> {
+        let len = self.uleb128()?;
+        let end = usize::try_from(len)
+            .ok()
+            .and_then(|len| self.pos.checked_add(len))
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(|| self.error("augmentation data out of bounds"))?;
+        let sub = Cursor {
+            data: &self.data[..end],
+            ..*self
+        };
+        self.pos = end;
+        Ok(sub)
+    }
+
+    fn pointer(&mut self, encoding: u8, section_addr: Addr) -> Result<u64> {
+        self.pointer_with_base(encoding, section_addr, None)
+    }
+
+    /// Like [`Cursor::pointer`], with `DW_EH_PE_datarel` pointers relative to `data_base`.
+    fn pointer_with_base(
+        &mut self,
+        encoding: u8,
+        section_addr: Addr,
+        data_base: Option<Addr>,
+    ) -> Result<u64> {
+        let field_addr = section_addr.u64().wrapping_add(self.pos as u64);
+
+        let value = match encoding & 0x0f {
+            c::DW_EH_PE_absptr | c::DW_EH_PE_udata8 | c::DW_EH_PE_sdata8 => self.u64()?,
+            c::DW_EH_PE_uleb128 => self.uleb128()?,
+            c::DW_EH_PE_udata2 => u64::from(self.u16()?),
+            c::DW_EH_PE_udata4 => u64::from(self.u32()?),
+            c::DW_EH_PE_sleb128 => self.sleb128()? as u64,
+            c::DW_EH_PE_sdata2 => i64::from(self.u16()? as i16) as u64,
+            c::DW_EH_PE_sdata4 => i64::from(self.u32()? as i32) as u64,
+            _ => return Err(self.error("unknown pointer format")),
+        };
+
+        let base = match (encoding & 0x70, data_base) {
+            (c::DW_EH_PE_absptr, _) => 0,
+            (c::DW_EH_PE_pcrel, _) => field_addr,
+            (c::DW_EH_PE_datarel, Some(data_base)) => data_base.u64(),
+            _ => return Err(self.error("unsupported pointer application")),
+        };
+
+        Ok(base.wrapping_add(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EhFrameEntry, EhFrameIterator};
+    use crate::Addr;
+
+    /// A CIE and an FDE for 8 bytes of code, both with an extended length.
+    const EXTENDED_EH_FRAME: [u8; 64] = [
+        // CIE: extended length, ID, version, "zR".
+        0xff, 0xff, 0xff, 0xff, 0x14, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, b'z', b'R', 0,
+        // Alignments, return address, augmentation data.
+        0x01, 0x78, 0x10, 0x01, 0x1b,
+        // DW_CFA_def_cfa rsp+8, DW_CFA_offset rip, padding.
+        0x0c, 0x07, 0x08, 0x90, 0x01, 0, 0,
+        // FDE: the extended length and the CIE pointer, which is still only 4 bytes.
+        0xff, 0xff, 0xff, 0xff, 0x10, 0, 0, 0, 0, 0, 0, 0, 0x2c, 0, 0, 0,
+        // PC begin, PC range, augmentation data, padding.
+        0, 0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0,
+        // The zero terminator, which marks the end of the records for the unwinder.
+        0, 0, 0, 0,
+    ];
+
+    #[test]
+    fn extended_length_has_4_byte_cie_pointer() {
+        let entries = EhFrameIterator::new(&EXTENDED_EH_FRAME, Addr(0))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(entries.len(), 2);
+
+        let EhFrameEntry::Cie(cie) = entries[0] else {
+            panic!("expected a CIE: {:?}", entries[0]);
+        };
+        assert_eq!(cie.length, 0x14);
+        assert_eq!(cie.augmentation, "zR");
+
+        let EhFrameEntry::Fde(fde) = entries[1] else {
+            panic!("expected an FDE: {:?}", entries[1]);
+        };
+        assert_eq!(fde.offset.u64(), 32);
+        assert_eq!(fde.cie_pointer, 0x2c);
+        assert_eq!(fde.cie_offset.u64(), 0);
+        assert_eq!(fde.pc_range, 8);
+    }
+}
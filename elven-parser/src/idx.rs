@@ -11,6 +11,11 @@ macro_rules! define_idx {
     ) => {
         #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, bytemuck::Zeroable, bytemuck::Pod)]
         #[repr(transparent)]
+        #[cfg_attr(
+            feature = "serde",
+            derive(serde::Serialize, serde::Deserialize),
+            serde(transparent)
+        )]
         $vis struct $name(pub $ty);
 
         impl crate::idx::ToIdxUsize for $name {
@@ -2,8 +2,12 @@
 
 mod addrs;
 pub mod consts;
+pub mod eh_frame;
 mod idx;
+pub mod patch;
 pub mod read;
+#[cfg(test)]
+mod test_data;
 pub mod write;
 
 pub use crate::addrs::{Addr, Offset};
@@ -0,0 +1,114 @@
+//! Editing an existing ELF file in place, without writing it again with [`ElfWriter`].
+//!
+//! [`ElfWriter`]: crate::write::ElfWriter
+
+use std::mem;
+
+use crate::{
+    consts as c,
+    read::{ElfReadError, ElfReader, Result, Sym},
+    Addr,
+};
+
+/// Patches bytes of an ELF file, for example a memory mapped one.
+/// The layout of the file never changes.
+#[derive(Debug)]
+pub struct ElfPatcher<'a> {
+    data: &'a mut [u8],
+}
+
+impl<'a> ElfPatcher<'a> {
+    pub fn new(data: &'a mut [u8]) -> Result<Self> {
+        ElfReader::new(data)?;
+        Ok(Self { data })
+    }
+
+    /// A reader for the current content, including the patches so far.
+    pub fn reader(&self) -> ElfReader<'_> {
+        ElfReader::new(self.data).expect("the file was checked in ElfPatcher::new")
+    }
+
+    /// Overwrites the content of the section at `offset` in the section with `bytes`.
+    pub fn patch_section_content(
+        &mut self,
+        name: &[u8],
+        offset: usize,
+        bytes: &[u8],
+    ) -> Result<()> {
+        let elf = self.reader();
+        let sh = elf.section_header_by_name(name)?;
+        // Makes sure that the section is actually in the file.
+        let content = elf.section_content(sh)?;
+
+        let end = offset
+            .checked_add(bytes.len())
+            .filter(|&end| end <= content.len())
+            .ok_or_else(|| {
+                ElfReadError::RegionOutOfBounds(
+                    offset.saturating_add(bytes.len()),
+                    content.len(),
+                    format!("section {}", String::from_utf8_lossy(name)),
+                )
+            })?;
+
+        let start = sh.offset.usize();
+        self.data[(start + offset)..(start + end)].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Sets the value of the first symbol in `.symtab` called `name`.
+    pub fn patch_symbol_value(&mut self, name: &[u8], new_value: Addr) -> Result<()> {
+        let elf = self.reader();
+        let symtab = elf.section_header_by_type_first(c::SHT_SYMTAB)?;
+        let sym = elf.symbol_by_name(name)?;
+
+        // The symbols are read directly from the file, so their index follows from the address.
+        let symbols = elf.symbols()?;
+        let idx = (sym as *const Sym as usize - symbols.as_ptr() as usize) / mem::size_of::<Sym>();
+
+        let value =
+            symtab.offset.usize() + idx * mem::size_of::<Sym>() + mem::offset_of!(Sym, value);
+        self.data[value..][..mem::size_of::<Addr>()]
+            .copy_from_slice(bytemuck::bytes_of(&new_value));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use memmap2::MmapOptions;
+
+    use super::ElfPatcher;
+    use crate::{read::ElfReadError, test_data::open_test_file, Addr};
+
+    #[test]
+    fn patch_and_read_back() {
+        let file = open_test_file("hello_world");
+        // A private mapping, the changes don't end up in the file.
+        let mut data = unsafe { MmapOptions::new().map_copy(&file) }.unwrap();
+
+        let mut patcher = ElfPatcher::new(&mut data).unwrap();
+        patcher
+            .patch_section_content(b".text", 4, &[0xcc, 0xcc])
+            .unwrap();
+        patcher.patch_symbol_value(b"main", Addr(0x1234)).unwrap();
+
+        let text_size = {
+            let elf = patcher.reader();
+            elf.section_header_by_name(b".text").unwrap().size as usize
+        };
+        assert!(matches!(
+            patcher.patch_section_content(b".text", text_size - 1, &[0, 0]),
+            Err(ElfReadError::RegionOutOfBounds(..))
+        ));
+        assert!(matches!(
+            patcher.patch_symbol_value(b"does_not_exist", Addr(0)),
+            Err(ElfReadError::NotFoundByName(..))
+        ));
+
+        let elf = patcher.reader();
+        let text = elf.section_header_by_name(b".text").unwrap();
+        assert_eq!(elf.section_content(text).unwrap()[4..6], [0xcc, 0xcc]);
+        assert_eq!(elf.symbol_by_name(b"main").unwrap().value, Addr(0x1234));
+    }
+}
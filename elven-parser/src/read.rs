@@ -4,15 +4,19 @@
 
 use crate::{
     consts::{self as c, DynamicTag, ShType},
+    eh_frame::{EhFrameHdr, EhFrameIterator},
     idx::{define_idx, ElfIndexExt, ToIdxUsize},
     Addr, Offset,
 };
-use bstr::BStr;
+use bstr::{BStr, BString};
 
 use std::{
+    collections::HashMap,
     fmt::{Debug, Display},
     mem,
+    ops::Range,
     string::{self, FromUtf8Error},
+    sync::{Arc, OnceLock},
 };
 
 use bytemuck::{Pod, PodCastError, Zeroable};
@@ -43,10 +47,52 @@ pub enum ElfReadError {
     NotFoundByName(&'static str, std::result::Result<String, Vec<u8>>),
     #[error("Dynamic entry not found: {0}")]
     DynEntryNotFound(DynamicTag),
+    #[error("The address {0} is not inside of any allocated section")]
+    UnmappedAddress(Addr),
+    #[error("The ELF class {0} is not supported, only ELFCLASS64 is")]
+    UnsupportedClass(c::Class),
+    #[error("The data encoding {0} does not match the byte order of the host")]
+    UnsupportedEndian(c::Data),
+    #[error("The .eh_frame entry at offset {0:#x} is invalid: {1}")]
+    InvalidEhFrame(usize, &'static str),
+    #[error("The .eh_frame_hdr at offset {0:#x} is invalid: {1}")]
+    InvalidEhFrameHdr(usize, &'static str),
 }
 
 pub type Result<T> = std::result::Result<T, ElfReadError>;
 
+/// An inconsistency found by [`ElfReader::validate`]. The file can still be read,
+/// but tools producing or consuming it will probably misbehave.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ElfWarning {
+    #[error("The section name string table index {index} is out of bounds for {count} sections")]
+    ShStrIndexOutOfBounds { index: u16, count: usize },
+    #[error("Section {section} ends at {end:#x}, after the end of the file at {file_size:#x}")]
+    SectionOutOfBounds {
+        section: c::SectionIdx,
+        end: u64,
+        file_size: usize,
+    },
+    #[error("Segment {segment} ends at {end:#x}, after the end of the file at {file_size:#x}")]
+    SegmentOutOfBounds {
+        segment: usize,
+        end: u64,
+        file_size: usize,
+    },
+    #[error(
+        "The sections {a_name} and {b_name} overlap at {}..{}",
+        overlap_range.start,
+        overlap_range.end
+    )]
+    OverlappingSections {
+        a_name: BString,
+        b_name: BString,
+        overlap_range: Range<Addr>,
+    },
+    #[error("The symbol table {symtab} links to section {link}, which is not a string table")]
+    InvalidSymbolStringTable { symtab: c::SectionIdx, link: u32 },
+}
+
 define_idx! {
     pub struct ShStringIdx(u32);
 }
@@ -59,14 +105,52 @@ define_idx! {
     pub struct SymIdx(u32);
 }
 
+const HOST_DATA: u8 = if cfg!(target_endian = "little") {
+    c::ELFDATA2LSB
+} else {
+    c::ELFDATA2MSB
+};
+
 /// A raw ELF. Does not come with cute ears for now.
-#[derive(Debug, Clone, Copy)]
+///
+/// The section headers and string tables are looked up once and then cached, so the reader
+/// isn't `Copy`. Cloning it is cheap and keeps the cache.
+#[derive(Debug, Clone)]
 pub struct ElfReader<'a> {
     pub data: &'a [u8],
+    section_headers: OnceLock<&'a [Shdr]>,
+    sh_str_table: OnceLock<&'a [u8]>,
+    str_table: OnceLock<&'a [u8]>,
+}
+
+/// An ELF that owns its data, for keeping it around without a lifetime or sending it to
+/// other threads. [`ElfReader`] itself is `Send` and `Sync` as well, it only borrows bytes.
+#[derive(Debug, Clone)]
+pub struct OwnedElfReader {
+    data: Arc<[u8]>,
+}
+
+impl OwnedElfReader {
+    /// Checks the header like [`ElfReader::new`]. The data must be aligned to 8 bytes,
+    /// which the allocation of an `Arc` always is in practice.
+    pub fn from_arc(data: Arc<[u8]>) -> Result<Self> {
+        let align = 1 << (data.as_ptr() as usize).trailing_zeros().min(3);
+        if align < 8 {
+            return Err(ElfReadError::UnalignedInput(8, align));
+        }
+
+        ElfReader::new(&data)?;
+        Ok(Self { data })
+    }
+
+    pub fn reader(&self) -> ElfReader<'_> {
+        ElfReader::new(&self.data).expect("the file was checked in OwnedElfReader::from_arc")
+    }
 }
 
 #[derive(Debug, Clone, Copy, Zeroable, Pod)]
 #[repr(C)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ElfHeader {
     pub ident: ElfIdent,
     pub r#type: c::Type,
@@ -86,6 +170,7 @@ pub struct ElfHeader {
 
 #[derive(Debug, Clone, Copy, Zeroable, Pod)]
 #[repr(C)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ElfIdent {
     pub magic: [u8; c::SELFMAG],
     pub class: c::Class,
@@ -93,6 +178,7 @@ pub struct ElfIdent {
     pub version: u8,
     pub osabi: c::OsAbi,
     pub abiversion: u8,
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub _pad: [u8; 7],
 }
 
@@ -100,6 +186,7 @@ const _: [u8; c::EI_NIDENT] = [0; mem::size_of::<ElfIdent>()];
 
 #[derive(Debug, Clone, Copy, Zeroable, Pod)]
 #[repr(C)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Phdr {
     pub r#type: c::PhType,
     pub flags: c::PhFlags,
@@ -113,6 +200,7 @@ pub struct Phdr {
 
 #[derive(Debug, Clone, Copy, Zeroable, Pod)]
 #[repr(C)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Shdr {
     pub name: ShStringIdx,
     pub r#type: c::ShType,
@@ -128,6 +216,7 @@ pub struct Shdr {
 
 #[derive(Debug, Clone, Copy, Zeroable, Pod)]
 #[repr(C)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Sym {
     pub name: StringIdx,
     pub info: SymInfo,
@@ -137,11 +226,25 @@ pub struct Sym {
     pub size: u64,
 }
 
+/// The symbol table a symbol comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolSource {
+    /// `.symtab`, removed by stripping.
+    Static,
+    /// `.dynsym`, used by the dynamic linker.
+    Dynamic,
+}
+
 #[derive(Clone, Copy, Zeroable, Pod)]
 #[repr(transparent)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(transparent))]
 pub struct SymInfo(pub u8);
 
 impl SymInfo {
+    pub fn new(r#type: c::SymbolType, binding: c::SymbolBinding) -> Self {
+        Self((binding.0 << 4) | (r#type.0 & 0xf))
+    }
+
     pub fn r#type(self) -> c::SymbolType {
         c::SymbolType(self.0 & 0xf)
     }
@@ -165,6 +268,7 @@ impl Display for SymInfo {
 
 #[derive(Debug, Clone, Copy, Zeroable, Pod)]
 #[repr(C)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Rel {
     pub offset: Addr,
     pub info: RelInfo,
@@ -172,17 +276,71 @@ pub struct Rel {
 
 #[derive(Debug, Clone, Copy, Zeroable, Pod)]
 #[repr(C)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Rela {
     pub offset: Addr,
     pub info: RelInfo,
     pub addend: i64,
 }
 
+/// A `SHT_GROUP` section.
+#[derive(Debug, Clone)]
+pub struct SectionGroup<'a> {
+    /// The index of the group section itself.
+    pub section: c::SectionIdx,
+    pub flags: u32,
+    /// The name of the signature symbol.
+    pub signature: &'a BStr,
+    pub members: Vec<c::SectionIdx>,
+}
+
+impl SectionGroup<'_> {
+    pub fn is_comdat(&self) -> bool {
+        self.flags & c::GRP_COMDAT != 0
+    }
+}
+
+/// Either kind of relocation entry.
+#[derive(Debug, Clone, Copy)]
+pub enum Relocation<'a> {
+    Rel(&'a Rel),
+    Rela(&'a Rela),
+}
+
+impl Relocation<'_> {
+    pub fn offset(&self) -> Addr {
+        match self {
+            Self::Rel(rel) => rel.offset,
+            Self::Rela(rela) => rela.offset,
+        }
+    }
+
+    pub fn info(&self) -> RelInfo {
+        match self {
+            Self::Rel(rel) => rel.info,
+            Self::Rela(rela) => rela.info,
+        }
+    }
+
+    /// The explicit addend. `SHT_REL` relocations store it in the relocated place instead.
+    pub fn addend(&self) -> Option<i64> {
+        match self {
+            Self::Rel(_) => None,
+            Self::Rela(rela) => Some(rela.addend),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Zeroable, Pod)]
 #[repr(transparent)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(transparent))]
 pub struct RelInfo(pub u64);
 
 impl RelInfo {
+    pub fn new(sym: SymIdx, r#type: u32) -> Self {
+        Self((u64::from(sym.0) << 32) | u64::from(r#type))
+    }
+
     pub fn sym(&self) -> SymIdx {
         SymIdx((self.0 >> 32) as u32)
     }
@@ -200,13 +358,353 @@ impl Debug for RelInfo {
 
 #[derive(Debug, Clone, Copy, Zeroable, Pod)]
 #[repr(C)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Dyn {
     pub tag: c::DynamicTag,
     pub val: u64,
 }
 
+#[derive(Debug, Clone, Copy, Zeroable, Pod)]
+#[repr(C)]
+pub struct Nhdr {
+    pub namesz: u32,
+    pub descsz: u32,
+    pub r#type: u32,
+}
+
+/// A stub in the procedure linkage table, jumping to the address in its GOT slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PltEntry<'a> {
+    pub addr: Addr,
+    pub got_slot: Addr,
+    /// The function the stub is for, from the `R_X86_64_JUMP_SLOT` relocation of the slot.
+    pub symbol: Option<&'a BStr>,
+}
+
+/// The content of `.gnu_debuglink`, pointing to the file with the debug info of a
+/// stripped binary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebugLink<'a> {
+    pub filename: &'a BStr,
+    /// The CRC-32 of the whole debug info file.
+    pub crc: u32,
+}
+
+/// An entry of a note section.
+#[derive(Debug, Clone, Copy)]
+pub struct Note<'a> {
+    pub namesz: u32,
+    pub descsz: u32,
+    pub note_type: u32,
+    /// The owner of the note, including the nul terminator.
+    pub name: &'a [u8],
+    pub desc: &'a [u8],
+}
+
+/// Iterates over the entries of a note section. Stops at the first malformed entry.
+#[derive(Debug, Clone)]
+pub struct NoteIterator<'a> {
+    data: &'a [u8],
+    align: usize,
+}
+
+impl<'a> Iterator for NoteIterator<'a> {
+    type Item = Note<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let header = load_ref::<Nhdr>(self.data, "note header").ok()?;
+
+        let name_start = mem::size_of::<Nhdr>();
+        let name_end = name_start + header.namesz as usize;
+        let desc_start = name_end.next_multiple_of(self.align);
+        let desc_end = desc_start + header.descsz as usize;
+
+        let name = self.data.get(name_start..name_end)?;
+        let desc = self.data.get(desc_start..desc_end)?;
+
+        let next = desc_end.next_multiple_of(self.align).min(self.data.len());
+        self.data = &self.data[next..];
+
+        Some(Note {
+            namesz: header.namesz,
+            descsz: header.descsz,
+            note_type: header.r#type,
+            name,
+            desc,
+        })
+    }
+}
+
+/// An entry of a `SHT_GNU_verneed` section, describing a library that versions are needed from.
+#[derive(Debug, Clone, Copy, Zeroable, Pod)]
+#[repr(C)]
+pub struct Verneed {
+    pub version: u16,
+    /// The number of associated [`Vernaux`] entries.
+    pub cnt: u16,
+    /// The file name of the library.
+    pub file: StringIdx,
+    /// The offset of the first [`Vernaux`] entry, relative to this entry.
+    pub aux: u32,
+    /// The offset of the next entry, relative to this entry.
+    pub next: u32,
+}
+
+/// A version that is needed from a library.
+#[derive(Debug, Clone, Copy, Zeroable, Pod)]
+#[repr(C)]
+pub struct Vernaux {
+    pub hash: u32,
+    pub flags: u16,
+    /// The version index that is used in the versym table.
+    pub other: u16,
+    pub name: StringIdx,
+    /// The offset of the next entry, relative to this entry.
+    pub next: u32,
+}
+
+/// An entry of a `SHT_GNU_verdef` section, describing a version defined by this object.
+#[derive(Debug, Clone, Copy, Zeroable, Pod)]
+#[repr(C)]
+pub struct Verdef {
+    pub version: u16,
+    pub flags: u16,
+    /// The version index that is used in the versym table.
+    pub ndx: u16,
+    /// The number of associated [`Verdaux`] entries.
+    pub cnt: u16,
+    pub hash: u32,
+    /// The offset of the first [`Verdaux`] entry, relative to this entry.
+    pub aux: u32,
+    /// The offset of the next entry, relative to this entry.
+    pub next: u32,
+}
+
+/// The name of a version definition. The first one is the name of the version itself,
+/// the others are its parents.
+#[derive(Debug, Clone, Copy, Zeroable, Pod)]
+#[repr(C)]
+pub struct Verdaux {
+    pub name: StringIdx,
+    /// The offset of the next entry, relative to this entry.
+    pub next: u32,
+}
+
+/// A chain of version entries that are linked through relative `next` offsets.
+#[derive(Debug, Clone)]
+struct VersionChain<'a> {
+    data: &'a [u8],
+    offset: usize,
+    remaining: usize,
+}
+
+impl<'a> VersionChain<'a> {
+    fn next_entry<T: Pod>(&mut self, next: impl Fn(&T) -> u32) -> Option<(usize, &'a T)> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let offset = self.offset;
+        let entry = load_ref::<T>(self.data.get(offset..)?, "version entry").ok()?;
+
+        self.remaining -= 1;
+        match next(entry) {
+            0 => self.remaining = 0,
+            next => self.offset += next as usize,
+        }
+
+        Some((offset, entry))
+    }
+}
+
+/// Iterates over the entries of a `SHT_GNU_verneed` section. Stops at the first malformed entry.
+#[derive(Debug, Clone)]
+pub struct VerneedIterator<'a> {
+    chain: VersionChain<'a>,
+}
+
+impl<'a> Iterator for VerneedIterator<'a> {
+    type Item = (&'a Verneed, VernauxIterator<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (offset, need) = self.chain.next_entry(|need: &Verneed| need.next)?;
+        let aux = VernauxIterator {
+            chain: VersionChain {
+                data: self.chain.data,
+                offset: offset + need.aux as usize,
+                remaining: need.cnt.into(),
+            },
+        };
+
+        Some((need, aux))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VernauxIterator<'a> {
+    chain: VersionChain<'a>,
+}
+
+impl<'a> Iterator for VernauxIterator<'a> {
+    type Item = &'a Vernaux;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.chain
+            .next_entry(|aux: &Vernaux| aux.next)
+            .map(|(_, aux)| aux)
+    }
+}
+
+/// Iterates over the entries of a `SHT_GNU_verdef` section. Stops at the first malformed entry.
+#[derive(Debug, Clone)]
+pub struct VerdefIterator<'a> {
+    chain: VersionChain<'a>,
+}
+
+impl<'a> Iterator for VerdefIterator<'a> {
+    type Item = (&'a Verdef, VerdauxIterator<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (offset, def) = self.chain.next_entry(|def: &Verdef| def.next)?;
+        let aux = VerdauxIterator {
+            chain: VersionChain {
+                data: self.chain.data,
+                offset: offset + def.aux as usize,
+                remaining: def.cnt.into(),
+            },
+        };
+
+        Some((def, aux))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VerdauxIterator<'a> {
+    chain: VersionChain<'a>,
+}
+
+impl<'a> Iterator for VerdauxIterator<'a> {
+    type Item = &'a Verdaux;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.chain
+            .next_entry(|aux: &Verdaux| aux.next)
+            .map(|(_, aux)| aux)
+    }
+}
+
+/// The header of a `SHT_GNU_HASH` section.
+#[derive(Debug, Clone, Copy, Zeroable, Pod)]
+#[repr(C)]
+pub struct GnuHashHeader {
+    pub nbuckets: u32,
+    pub symoffset: u32,
+    pub bloom_size: u32,
+    pub bloom_shift: u32,
+}
+
+/// A GNU-style hash table for looking up dynamic symbols by name.
+#[derive(Debug, Clone, Copy)]
+pub struct GnuHashTable<'a> {
+    pub header: &'a GnuHashHeader,
+    pub bloom: &'a [u64],
+    pub buckets: &'a [u32],
+    /// Starts at the symbol with index `symoffset`.
+    pub chains: &'a [u32],
+    symbols: &'a [Sym],
+    strings: &'a [u8],
+}
+
+impl GnuHashTable<'_> {
+    pub fn lookup_symbol(&self, name: &[u8]) -> Option<SymIdx> {
+        let hash = gnu_hash(name);
+        let symoffset = self.header.symoffset as usize;
+
+        if self.bloom.is_empty() || self.buckets.is_empty() {
+            return None;
+        }
+
+        let word = self.bloom[(hash as usize / 64) % self.bloom.len()];
+        // A shift as large as the hash only appears in broken files.
+        let shifted = hash.checked_shr(self.header.bloom_shift)?;
+        let mask = (1 << (hash % 64)) | (1 << (shifted % 64));
+        if word & mask != mask {
+            return None;
+        }
+
+        let mut idx = self.buckets[hash as usize % self.buckets.len()] as usize;
+        if idx < symoffset {
+            return None;
+        }
+
+        loop {
+            let chain_hash = *self.chains.get(idx - symoffset)?;
+
+            if hash | 1 == chain_hash | 1 {
+                let sym = self.symbols.get(idx)?;
+                if string_at(self.strings, sym.name).ok()? == name {
+                    return Some(SymIdx(idx.try_into().ok()?));
+                }
+            }
+
+            // The lowest bit marks the end of the chain.
+            if chain_hash & 1 != 0 {
+                return None;
+            }
+            idx += 1;
+        }
+    }
+}
+
+/// The hash function used by `SHT_GNU_HASH`.
+pub fn gnu_hash(name: &[u8]) -> u32 {
+    name.iter()
+        .fold(5381_u32, |h, &c| h.wrapping_mul(33).wrapping_add(c.into()))
+}
+
+/// A SysV-style hash table (`SHT_HASH`) for looking up dynamic symbols by name.
+#[derive(Debug, Clone, Copy)]
+pub struct SysvHashTable<'a> {
+    pub buckets: &'a [u32],
+    pub chains: &'a [u32],
+    symbols: &'a [Sym],
+    strings: &'a [u8],
+}
+
+impl SysvHashTable<'_> {
+    pub fn lookup_symbol(&self, name: &[u8]) -> Option<SymIdx> {
+        if self.buckets.is_empty() {
+            return None;
+        }
+
+        let hash = sysv_hash(name);
+        let mut idx = self.buckets[hash as usize % self.buckets.len()];
+
+        // Chains are terminated by STN_UNDEF.
+        while idx != 0 {
+            let sym = self.symbols.get(idx as usize)?;
+            if string_at(self.strings, sym.name).ok()? == name {
+                return Some(SymIdx(idx));
+            }
+            idx = *self.chains.get(idx as usize)?;
+        }
+
+        None
+    }
+}
+
+/// The hash function used by `SHT_HASH`.
+pub fn sysv_hash(name: &[u8]) -> u32 {
+    name.iter().fold(0_u32, |h, &c| {
+        let h = (h << 4).wrapping_add(c.into());
+        let g = h & 0xf000_0000;
+        (h ^ (g >> 24)) & !g
+    })
+}
+
 impl<'a> ElfReader<'a> {
-    /// Create a new elf reader. This only checks the elf magic but doens't do any parsing.
+    /// Create a new elf reader. This only checks the elf magic, class and data encoding
+    /// but doens't do any parsing.
     /// The input slice `data` must be aligned to 8 bytes, otherwise the reader may panic later.
     pub fn new(data: &'a [u8]) -> Result<Self> {
         if data.len() < mem::size_of::<ElfHeader>() {
@@ -223,7 +721,21 @@ impl<'a> ElfReader<'a> {
             return Err(ElfReadError::WrongMagic(magic));
         }
 
-        let elf = ElfReader { data };
+        let ident = load_ref::<ElfIdent>(data, "ident")?;
+        if ident.class != c::ELFCLASS64 {
+            return Err(ElfReadError::UnsupportedClass(ident.class));
+        }
+        // We cast the structures directly from the bytes, so they better be in our byte order.
+        if ident.data != HOST_DATA {
+            return Err(ElfReadError::UnsupportedEndian(ident.data));
+        }
+
+        let elf = ElfReader {
+            data,
+            section_headers: OnceLock::new(),
+            sh_str_table: OnceLock::new(),
+            str_table: OnceLock::new(),
+        };
 
         Ok(elf)
     }
@@ -256,6 +768,10 @@ impl<'a> ElfReader<'a> {
     }
 
     pub fn section_headers(&self) -> Result<&'a [Shdr]> {
+        cached(&self.section_headers, || self.load_section_headers())
+    }
+
+    fn load_section_headers(&self) -> Result<&'a [Shdr]> {
         let header = self.header()?;
 
         if header.shnum == 0 {
@@ -296,13 +812,73 @@ impl<'a> ElfReader<'a> {
         ))
     }
 
-    pub fn section_header_by_type(&self, ty: u32) -> Result<&'a Shdr> {
-        self.section_headers()?
-            .iter()
-            .find(|sh| sh.r#type == ty)
+    /// The first section with this type. Only use this for types that appear at most once,
+    /// like `SHT_SYMTAB`, and [`Self::section_headers_by_type`] for everything else.
+    pub fn section_header_by_type_first(&self, ty: u32) -> Result<&'a Shdr> {
+        self.section_headers_by_type(ty)?
+            .next()
             .ok_or(ElfReadError::SectionTypeNotFound(ShType(ty)))
     }
 
+    /// The section headers together with their index, for referring to them later.
+    pub fn section_headers_with_idx(
+        &self,
+    ) -> Result<impl Iterator<Item = (c::SectionIdx, &'a Shdr)>> {
+        // There are at most `u16::MAX` sections, `e_shnum` is a `u16`.
+        Ok(self
+            .section_headers()?
+            .iter()
+            .enumerate()
+            .map(|(idx, sh)| (c::SectionIdx(idx as u16), sh)))
+    }
+
+    pub fn section_headers_by_type(&self, ty: u32) -> Result<impl Iterator<Item = &'a Shdr>> {
+        Ok(self
+            .section_headers()?
+            .iter()
+            .filter(move |sh| sh.r#type == ty))
+    }
+
+    /// The allocated sections whose addresses aren't contained in any `PT_LOAD` segment, so they
+    /// are never mapped. Sections of relocatable files don't have addresses yet, and thread
+    /// local zero-initialized data is only covered by `PT_TLS`.
+    pub fn sections_outside_load_segments(&self) -> Result<Vec<(c::SectionIdx, &'a Shdr)>> {
+        if self.header()?.r#type == c::ET_REL {
+            return Ok(Vec::new());
+        }
+
+        let loads = self
+            .program_headers()?
+            .iter()
+            .filter(|ph| ph.r#type == c::PT_LOAD)
+            .collect::<Vec<_>>();
+
+        Ok(self
+            .section_headers_with_idx()?
+            .filter(|(_, sh)| {
+                sh.flags.contains(c::ShFlags::SHF_ALLOC)
+                    && !(sh.r#type == c::SHT_NOBITS && sh.flags.contains(c::ShFlags::SHF_TLS))
+            })
+            .filter(|(_, sh)| {
+                let end = sh.addr.u64().saturating_add(sh.size);
+                !loads
+                    .iter()
+                    .any(|ph| ph.vaddr <= sh.addr && end <= ph.vaddr.u64().saturating_add(ph.memsz))
+            })
+            .collect())
+    }
+
+    /// The allocated section whose addresses contain `addr`.
+    pub fn section_at_vaddr(&self, addr: Addr) -> Result<Option<&'a Shdr>> {
+        Ok(self.section_headers()?.iter().find(|sh| {
+            sh.flags.contains(c::ShFlags::SHF_ALLOC)
+                && sh.addr != Addr(0)
+                && sh.addr <= addr
+                // Crafted headers can overflow, such a section can't contain anything.
+                && sh.addr.checked_add(sh.size).is_some_and(|end| addr < end)
+        }))
+    }
+
     pub fn section_content(&self, sh: &Shdr) -> Result<&'a [u8]> {
         if sh.r#type.0 == c::SHT_NOBITS {
             return Ok(&[]);
@@ -314,6 +890,10 @@ impl<'a> ElfReader<'a> {
     }
 
     pub fn sh_str_table(&self) -> Result<&'a [u8]> {
+        cached(&self.sh_str_table, || self.load_sh_str_table())
+    }
+
+    fn load_sh_str_table(&self) -> Result<&'a [u8]> {
         let header = self.header()?;
         let shstrndex = header.shstrndex;
 
@@ -336,68 +916,130 @@ impl<'a> ElfReader<'a> {
     }
 
     pub fn str_table(&self) -> Result<&'a [u8]> {
-        let sh = self.section_header_by_name(b".strtab")?;
-        self.section_content(sh)
+        cached(&self.str_table, || {
+            let sh = self.section_header_by_name(b".strtab")?;
+            self.section_content(sh)
+        })
     }
 
     pub fn sh_string(&self, idx: ShStringIdx) -> Result<&'a BStr> {
         let str_table = self.sh_str_table()?;
-        let indexed = str_table.get_elf(idx.., "string offset")?;
-        let end = indexed
-            .iter()
-            .position(|&c| c == b'\0')
-            .ok_or(ElfReadError::NoStringNulTerm(idx.to_idx_usize()))?;
-        Ok(BStr::new(&indexed[..end]))
+        string_at(str_table, idx)
     }
 
     pub fn string(&self, idx: StringIdx) -> Result<&'a BStr> {
         let str_table = self.str_table()?;
-        let indexed = str_table.get_elf(idx.., "string offset")?;
-        let end = indexed
-            .iter()
-            .position(|&c| c == b'\0')
-            .ok_or(ElfReadError::NoStringNulTerm(idx.to_idx_usize()))?;
-        Ok(BStr::new(&indexed[..end]))
+        string_at(str_table, idx)
     }
 
     pub fn dyn_string(&self, idx: StringIdx) -> Result<&'a BStr> {
-        let tab_addr = self.dyn_entry_by_tag(c::DT_STRTAB)?;
+        let tab_addr = Addr(self.dyn_entry_by_tag(c::DT_STRTAB)?.val);
         let tab_sz = self.dyn_entry_by_tag(c::DT_STRSZ)?;
 
+        // `DT_STRTAB` is the address of the table in memory, not its offset in the file.
+        let sh = self
+            .section_at_vaddr(tab_addr)?
+            .ok_or(ElfReadError::UnmappedAddress(tab_addr))?;
+        let offset = sh.offset.u64() + (tab_addr.u64() - sh.addr.u64());
+
         let str_table = self
             .data
-            .get_elf(tab_addr.val.., "dyn string table")?
+            .get_elf(offset.., "dyn string table")?
             .get_elf(..tab_sz.val, "dyn string table size")?;
 
-        let indexed = str_table.get_elf(idx.., "string offset")?;
-        let end = indexed
-            .iter()
-            .position(|&c| c == b'\0')
-            .ok_or(ElfReadError::NoStringNulTerm(idx.to_idx_usize()))?;
-        Ok(BStr::new(&indexed[..end]))
+        string_at(str_table, idx)
     }
 
     pub fn relas(&self) -> Result<impl Iterator<Item = (&'a Shdr, &'a Rela)>> {
         Ok(self
-            .section_headers()?
-            .iter()
-            .filter(|sh| sh.r#type == c::SHT_RELA)
-            .map(|sh| {
+            .relocation_entries(c::SHT_RELA, "relocations")?
+            .map(|(_, sh, rela)| (sh, rela)))
+    }
+
+    /// Like [`ElfReader::relas`], with the index of the `SHT_RELA` section.
+    /// The section the relocations apply to is in its `sh_info`.
+    pub fn relas_with_section_idx(
+        &self,
+    ) -> Result<impl Iterator<Item = (c::SectionIdx, &'a Shdr, &'a Rela)>> {
+        self.relocation_entries(c::SHT_RELA, "relocations")
+    }
+
+    pub fn rels(&self) -> Result<impl Iterator<Item = (&'a Shdr, &'a Rel)>> {
+        Ok(self
+            .relocation_entries(c::SHT_REL, "relocations without addend")?
+            .map(|(_, sh, rel)| (sh, rel)))
+    }
+
+    /// All relocations of both the `SHT_REL` and `SHT_RELA` kind.
+    pub fn relocations(&self) -> Result<impl Iterator<Item = (&'a Shdr, Relocation<'a>)>> {
+        Ok(self
+            .rels()?
+            .map(|(sh, rel)| (sh, Relocation::Rel(rel)))
+            .chain(self.relas()?.map(|(sh, rela)| (sh, Relocation::Rela(rela)))))
+    }
+
+    fn relocation_entries<T: Pod>(
+        &self,
+        ty: u32,
+        kind: &'static str,
+    ) -> Result<impl Iterator<Item = (c::SectionIdx, &'a Shdr, &'a T)>> {
+        Ok(self
+            .section_headers_with_idx()?
+            .filter(|(_, sh)| sh.r#type == ty)
+            .map(|(idx, sh)| {
+                let content = self.section_content(sh)?;
+                let entries = load_slice::<T>(content, content.len() / mem::size_of::<T>(), kind)?;
+                Ok((idx, sh, entries))
+            })
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .flat_map(|(idx, sh, entries)| entries.iter().map(move |entry| (idx, sh, entry))))
+    }
+
+    pub fn section_groups(&self) -> Result<impl Iterator<Item = SectionGroup<'a>>> {
+        Ok(self
+            .section_headers_with_idx()?
+            .filter(|(_, sh)| sh.r#type == c::SHT_GROUP)
+            .map(|(idx, sh)| {
                 let content = self.section_content(sh)?;
-                let relas = load_slice::<Rela>(
+                let words = load_slice::<u32>(
                     content,
-                    content.len() / mem::size_of::<Rela>(),
-                    "relocations",
+                    content.len() / mem::size_of::<u32>(),
+                    "section group",
                 )?;
-                Ok((sh, relas))
+                let (&flags, members) = words
+                    .split_first()
+                    .ok_or(ElfReadError::IndexOutOfBounds("section group flags", 0))?;
+
+                let symtab = self.linked_section(sh)?;
+                let symbols = self.section_content(symtab)?;
+                let symbols =
+                    load_slice::<Sym>(symbols, symbols.len() / mem::size_of::<Sym>(), "symbols")?;
+                let signature = symbols.get_elf(sh.info as usize, "group signature symbol")?;
+                let strtab = self.section_content(self.linked_section(symtab)?)?;
+
+                let members = members
+                    .iter()
+                    .map(|&member| {
+                        u16::try_from(member).map(c::SectionIdx).map_err(|_| {
+                            ElfReadError::IndexOutOfBounds("group member", member as usize)
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                Ok(SectionGroup {
+                    section: idx,
+                    flags,
+                    signature: string_at(strtab, signature.name)?,
+                    members,
+                })
             })
             .collect::<Result<Vec<_>>>()?
-            .into_iter()
-            .flat_map(|(sh, relas)| relas.iter().map(move |rela| (sh, rela))))
+            .into_iter())
     }
 
     pub fn symbols(&self) -> Result<&'a [Sym]> {
-        let sh = self.section_header_by_type(c::SHT_SYMTAB)?;
+        let sh = self.section_header_by_type_first(c::SHT_SYMTAB)?;
 
         let data = self.section_content(sh)?;
 
@@ -422,19 +1064,456 @@ impl<'a> ElfReader<'a> {
         ))
     }
 
-    pub fn dyn_symbols(&self) -> Result<&'a [Sym]> {
-        let addr = self.dyn_entry_by_tag(c::DT_SYMTAB)?;
-        let size = self.dyn_entry_by_tag(c::DT_SYMENT)?;
+    /// The symbol whose `value..value + size` contains `addr`. Many symbols have no size, so
+    /// if none covers the address, the nearest sizeless symbol below it is used instead.
+    pub fn find_symbol_at_address(&self, addr: Addr) -> Result<Option<&'a Sym>> {
+        let mut nearest: Option<&'a Sym> = None;
+
+        for sym in self.symbols()? {
+            if sym.shndx == c::SHN_UNDEF
+                || matches!(sym.info.r#type().0, c::STT_SECTION | c::STT_FILE)
+                || sym.value > addr
+            {
+                continue;
+            }
+
+            if sym.size == 0 {
+                if nearest.is_none_or(|nearest| sym.value > nearest.value) {
+                    nearest = Some(sym);
+                }
+            } else if addr.u64() < sym.value.u64().saturating_add(sym.size) {
+                return Ok(Some(sym));
+            }
+        }
+
+        Ok(nearest)
+    }
+
+    /// The function pointers in the `SHT_INIT_ARRAY` section, run before `main`.
+    /// In position independent executables, these are usually zero and filled in by relocations.
+    pub fn init_array(&self) -> Result<&'a [Addr]> {
+        self.function_array(c::SHT_INIT_ARRAY, "init array")
+    }
 
-        dbg!(addr, size);
+    /// The function pointers in the `SHT_FINI_ARRAY` section, run after `main`.
+    pub fn fini_array(&self) -> Result<&'a [Addr]> {
+        self.function_array(c::SHT_FINI_ARRAY, "fini array")
+    }
 
-        let data = self.dyn_content(addr.val, size.val)?;
+    fn function_array(&self, ty: u32, kind: &'static str) -> Result<&'a [Addr]> {
+        let sh = self.section_header_by_type_first(ty)?;
+        let data = self.section_content(sh)?;
+
+        load_slice(data, data.len() / mem::size_of::<Addr>(), kind)
+    }
+
+    pub fn dyn_symbols(&self) -> Result<&'a [Sym]> {
+        let sh = self.section_header_by_type_first(c::SHT_DYNSYM)?;
+
+        let data = self.section_content(sh)?;
 
         load_slice(data, data.len() / mem::size_of::<Sym>(), "dyn symbols")
     }
 
+    /// The symbols of `.symtab` followed by the ones of `.dynsym`. A missing table just
+    /// contributes no symbols, so this also works for stripped binaries.
+    pub fn iter_all_symbols(&self) -> Result<impl Iterator<Item = (&'a Sym, SymbolSource)>> {
+        let or_empty = |symbols| match symbols {
+            Err(ElfReadError::SectionTypeNotFound(_)) => Ok(&[][..]),
+            symbols => symbols,
+        };
+        let symbols = or_empty(self.symbols())?;
+        let dyn_symbols = or_empty(self.dyn_symbols())?;
+
+        Ok(symbols
+            .iter()
+            .map(|sym| (sym, SymbolSource::Static))
+            .chain(dyn_symbols.iter().map(|sym| (sym, SymbolSource::Dynamic))))
+    }
+
     pub fn dyn_symbol(&self, idx: SymIdx) -> Result<&'a Sym> {
-        dbg!(self.dyn_symbols()?).get_elf(idx, "symbol index")
+        self.dyn_symbols()?.get_elf(idx, "symbol index")
+    }
+
+    pub fn gnu_hash_table(&self) -> Result<GnuHashTable<'a>> {
+        let sh = self.section_header_by_type_first(c::SHT_GNU_HASH)?;
+        let (symbols, strings) = self.hash_table_symbols(sh)?;
+
+        let content = self.section_content(sh)?;
+        let header = load_ref::<GnuHashHeader>(content, "GNU hash header")?;
+
+        let rest = content.get_elf(mem::size_of::<GnuHashHeader>().., "GNU hash bloom filter")?;
+        let bloom = load_slice::<u64>(rest, header.bloom_size as usize, "GNU hash bloom filter")?;
+
+        let rest = rest.get_elf(mem::size_of_val(bloom).., "GNU hash buckets")?;
+        let buckets = load_slice::<u32>(rest, header.nbuckets as usize, "GNU hash buckets")?;
+
+        let rest = rest.get_elf(mem::size_of_val(buckets).., "GNU hash chains")?;
+        let chains =
+            load_slice::<u32>(rest, rest.len() / mem::size_of::<u32>(), "GNU hash chains")?;
+
+        Ok(GnuHashTable {
+            header,
+            bloom,
+            buckets,
+            chains,
+            symbols,
+            strings,
+        })
+    }
+
+    pub fn sysv_hash_table(&self) -> Result<SysvHashTable<'a>> {
+        let sh = self.section_header_by_type_first(c::SHT_HASH)?;
+        let (symbols, strings) = self.hash_table_symbols(sh)?;
+
+        let content = self.section_content(sh)?;
+        let [nbucket, nchain] = *load_ref::<[u32; 2]>(content, "hash header")?;
+
+        let rest = content.get_elf(mem::size_of::<[u32; 2]>().., "hash buckets")?;
+        let buckets = load_slice::<u32>(rest, nbucket as usize, "hash buckets")?;
+
+        let rest = rest.get_elf(mem::size_of_val(buckets).., "hash chains")?;
+        let chains = load_slice::<u32>(rest, nchain as usize, "hash chains")?;
+
+        Ok(SysvHashTable {
+            buckets,
+            chains,
+            symbols,
+            strings,
+        })
+    }
+
+    /// The symbol and string table used by a hash table section.
+    fn hash_table_symbols(&self, sh: &Shdr) -> Result<(&'a [Sym], &'a [u8])> {
+        let symtab = self.linked_section(sh)?;
+        let data = self.section_content(symtab)?;
+        let symbols = load_slice(data, data.len() / mem::size_of::<Sym>(), "hashed symbols")?;
+
+        let strtab = self.linked_section(symtab)?;
+        let strings = self.section_content(strtab)?;
+
+        Ok((symbols, strings))
+    }
+
+    /// The version index of every dynamic symbol.
+    pub fn versym_table(&self) -> Result<&'a [c::Versym]> {
+        let sh = self.section_header_by_type_first(c::SHT_GNU_versym)?;
+        let data = self.section_content(sh)?;
+
+        load_slice(
+            data,
+            data.len() / mem::size_of::<c::Versym>(),
+            "versym table",
+        )
+    }
+
+    /// The versions needed from other libraries. The strings are in the dynamic string table.
+    pub fn verneed_entries(&self) -> Result<VerneedIterator<'a>> {
+        let sh = self.section_header_by_type_first(c::SHT_GNU_verneed)?;
+
+        Ok(VerneedIterator {
+            chain: self.version_chain(sh)?,
+        })
+    }
+
+    /// The versions defined by this object. The strings are in the dynamic string table.
+    pub fn verdef_entries(&self) -> Result<VerdefIterator<'a>> {
+        let sh = self.section_header_by_type_first(c::SHT_GNU_verdef)?;
+
+        Ok(VerdefIterator {
+            chain: self.version_chain(sh)?,
+        })
+    }
+
+    fn version_chain(&self, sh: &Shdr) -> Result<VersionChain<'a>> {
+        Ok(VersionChain {
+            data: self.section_content(sh)?,
+            offset: 0,
+            // sh_info contains the number of entries.
+            remaining: sh.info as usize,
+        })
+    }
+
+    pub fn notes_in_section(&self, sh: &Shdr) -> Result<NoteIterator<'a>> {
+        let data = self.section_content(sh)?;
+        // Notes are 4 byte aligned, except for the ones that aren't.
+        let align = if sh.addralign == 8 { 8 } else { 4 };
+
+        Ok(NoteIterator { data, align })
+    }
+
+    /// The notes of a `PT_NOTE` segment, for files without section headers.
+    pub fn notes_in_segment(&self, ph: &Phdr) -> Result<NoteIterator<'a>> {
+        let data = self
+            .data
+            .get_elf(ph.offset.., "segment offset")?
+            .get_elf(..ph.filesz, "segment size")?;
+        let align = if ph.align == 8 { 8 } else { 4 };
+
+        Ok(NoteIterator { data, align })
+    }
+
+    /// The descriptor of the `NT_GNU_BUILD_ID` note, if there is one.
+    pub fn gnu_build_id(&self) -> Result<Option<&'a [u8]>> {
+        for sh in self.section_headers()? {
+            if sh.r#type != c::SHT_NOTE {
+                continue;
+            }
+
+            let build_id = self
+                .notes_in_section(sh)?
+                .find(|note| note.name == b"GNU\0" && note.note_type == c::NT_GNU_BUILD_ID);
+
+            if let Some(build_id) = build_id {
+                return Ok(Some(build_id.desc));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// The stubs of `.plt`, or `.plt.sec` when the stubs are split from the lazy binding code
+    /// for indirect branch tracking. Only x86-64 is supported, other files have no entries.
+    pub fn plt_entries(&self) -> Result<impl Iterator<Item = PltEntry<'a>>> {
+        const ENTRY_SIZE: usize = 16;
+
+        let mut entries = Vec::new();
+        if self.header()?.machine != c::EM_X86_64 {
+            return Ok(entries.into_iter());
+        }
+
+        let (sh, first_entry) = match self.section_header_by_name(b".plt.sec") {
+            Ok(sh) => (sh, 0),
+            // The first entry of .plt calls the dynamic linker for lazy binding.
+            Err(ElfReadError::NotFoundByName(..)) => match self.section_header_by_name(b".plt") {
+                Ok(sh) => (sh, 1),
+                Err(ElfReadError::NotFoundByName(..)) => return Ok(entries.into_iter()),
+                Err(err) => return Err(err),
+            },
+            Err(err) => return Err(err),
+        };
+        let content = self.section_content(sh)?;
+
+        let slot_symbols = self
+            .relas()?
+            .filter(|(_, rela)| rela.info.r#type() == c::R_X86_64_JUMP_SLOT)
+            .map(|(_, rela)| {
+                let sym = self.dyn_symbol(rela.info.sym())?;
+                Ok((rela.offset, self.dyn_string(sym.name)?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        for (idx, entry) in content
+            .chunks_exact(ENTRY_SIZE)
+            .enumerate()
+            .skip(first_entry)
+        {
+            let addr = sh.addr + (idx * ENTRY_SIZE) as u64;
+
+            // endbr64 and the bnd prefix come before the jump with indirect branch tracking.
+            let mut pos = 0;
+            if entry.starts_with(&[0xf3, 0x0f, 0x1e, 0xfa]) {
+                pos += 4;
+            }
+            if entry[pos] == 0xf2 {
+                pos += 1;
+            }
+            // jmp [rip + rel32]
+            let Some(rel) = entry[pos..].strip_prefix(&[0xff, 0x25]) else {
+                continue;
+            };
+            let rel = i32::from_le_bytes(rel[..4].try_into().unwrap());
+            let next_instruction = addr.u64() + pos as u64 + 6;
+            let got_slot = Addr(next_instruction.wrapping_add_signed(rel.into()));
+
+            let symbol = slot_symbols
+                .iter()
+                .find(|(offset, _)| *offset == got_slot)
+                .map(|&(_, name)| name);
+
+            entries.push(PltEntry {
+                addr,
+                got_slot,
+                symbol,
+            });
+        }
+
+        Ok(entries.into_iter())
+    }
+
+    /// The `.gnu_debuglink` section, if there is one.
+    pub fn gnu_debuglink(&self) -> Result<Option<DebugLink<'a>>> {
+        let sh = match self.section_header_by_name(b".gnu_debuglink") {
+            Ok(sh) => sh,
+            Err(ElfReadError::NotFoundByName(..)) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let data = self.section_content(sh)?;
+
+        let len = data
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or(ElfReadError::NoStringNulTerm(0))?;
+        // The CRC comes after the nul terminator, aligned to 4 bytes.
+        let crc_offset = (len + 1).next_multiple_of(4);
+        let crc = data
+            .get(crc_offset..)
+            .and_then(|rest| rest.get(..4))
+            .ok_or_else(|| {
+                ElfReadError::RegionOutOfBounds(
+                    crc_offset + 4,
+                    data.len(),
+                    ".gnu_debuglink".to_owned(),
+                )
+            })?;
+
+        Ok(Some(DebugLink {
+            filename: BStr::new(&data[..len]),
+            crc: u32::from_ne_bytes(crc.try_into().unwrap()),
+        }))
+    }
+
+    /// The path of the dynamic linker requested by the `PT_INTERP` program header.
+    pub fn interpreter(&self) -> Result<Option<&'a BStr>> {
+        let Some(ph) = self
+            .program_headers()?
+            .iter()
+            .find(|ph| ph.r#type == c::PT_INTERP)
+        else {
+            return Ok(None);
+        };
+
+        let path = self
+            .data
+            .get_elf(ph.offset.., "interpreter offset")?
+            .get_elf(..ph.filesz, "interpreter size")?;
+
+        Ok(Some(BStr::new(path.strip_suffix(b"\0").unwrap_or(path))))
+    }
+
+    /// The permissions of the stack. For executables and shared objects, this comes from the
+    /// `PT_GNU_STACK` program header. Object files use a `.note.GNU-stack` section instead,
+    /// where `SHF_EXECINSTR` requests an executable stack.
+    pub fn gnu_stack_flags(&self) -> Result<Option<c::PhFlags>> {
+        let ph = self
+            .program_headers()?
+            .iter()
+            .find(|ph| ph.r#type == c::PT_GNU_STACK);
+        if let Some(ph) = ph {
+            return Ok(Some(ph.flags));
+        }
+
+        match self.section_header_by_name(b".note.GNU-stack") {
+            Ok(sh) => {
+                let mut flags = c::PhFlags::PF_R | c::PhFlags::PF_W;
+                if sh.flags.contains(c::ShFlags::SHF_EXECINSTR) {
+                    flags |= c::PhFlags::PF_X;
+                }
+                Ok(Some(flags))
+            }
+            Err(ElfReadError::NotFoundByName(_, _)) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Checks that the headers are consistent with each other and the size of the file.
+    /// Returns all problems instead of stopping at the first, errors only if the headers
+    /// can't be read at all.
+    pub fn validate(&self) -> Result<Vec<ElfWarning>> {
+        let header = self.header()?;
+        let sections = self.section_headers()?;
+        let file_size = self.data.len();
+        let mut warnings = Vec::new();
+
+        if !sections.is_empty() && usize::from(header.shstrndex.0) >= sections.len() {
+            warnings.push(ElfWarning::ShStrIndexOutOfBounds {
+                index: header.shstrndex.0,
+                count: sections.len(),
+            });
+        }
+
+        for (idx, sh) in sections.iter().enumerate() {
+            let section = c::SectionIdx(idx as u16);
+
+            let end = sh.offset.u64().saturating_add(sh.size);
+            if sh.r#type != c::SHT_NOBITS && end > file_size as u64 {
+                warnings.push(ElfWarning::SectionOutOfBounds {
+                    section,
+                    end,
+                    file_size,
+                });
+            }
+
+            if matches!(sh.r#type.0, c::SHT_SYMTAB | c::SHT_DYNSYM) {
+                let is_strtab = usize::try_from(sh.link)
+                    .ok()
+                    .and_then(|link| sections.get(link))
+                    .is_some_and(|strtab| strtab.r#type == c::SHT_STRTAB);
+                if !is_strtab {
+                    warnings.push(ElfWarning::InvalidSymbolStringTable {
+                        symtab: section,
+                        link: sh.link,
+                    });
+                }
+            }
+        }
+
+        for (segment, ph) in self.program_headers()?.iter().enumerate() {
+            let end = ph.offset.u64().saturating_add(ph.filesz);
+            if ph.r#type == c::PT_LOAD && end > file_size as u64 {
+                warnings.push(ElfWarning::SegmentOutOfBounds {
+                    segment,
+                    end,
+                    file_size,
+                });
+            }
+        }
+
+        // Sections of relocatable files don't have addresses yet, neither do sections at 0.
+        // Thread local zero-initialized data doesn't take up address space of its own either.
+        if header.r#type != c::ET_REL {
+            let mut allocated = sections
+                .iter()
+                .filter(|sh| {
+                    sh.flags.contains(c::ShFlags::SHF_ALLOC) && sh.size > 0 && sh.addr.u64() != 0
+                })
+                .filter(|sh| {
+                    !(sh.r#type == c::SHT_NOBITS && sh.flags.contains(c::ShFlags::SHF_TLS))
+                })
+                .collect::<Vec<_>>();
+            allocated.sort_by_key(|sh| sh.addr);
+
+            // The names are just for the message, the string table may be broken as well.
+            let name = |sh: &Shdr| {
+                self.sh_string(sh.name)
+                    .map(|name| name.to_owned())
+                    .unwrap_or_default()
+            };
+
+            for pair in allocated.windows(2) {
+                let [first, second] = pair else {
+                    unreachable!()
+                };
+                let first_end = first.addr.u64().saturating_add(first.size);
+                if first_end > second.addr.u64() {
+                    let second_end = second.addr.u64().saturating_add(second.size);
+                    warnings.push(ElfWarning::OverlappingSections {
+                        a_name: name(first),
+                        b_name: name(second),
+                        overlap_range: second.addr..Addr(first_end.min(second_end)),
+                    });
+                }
+            }
+        }
+
+        Ok(warnings)
+    }
+
+    /// The section referenced by the `sh_link` field of a section.
+    pub fn linked_section(&self, sh: &Shdr) -> Result<&'a Shdr> {
+        let idx = u16::try_from(sh.link)
+            .map_err(|_| ElfReadError::IndexOutOfBounds("section link", sh.link as usize))?;
+        self.section_header(c::SectionIdx(idx))
     }
 
     pub fn dyn_entries(&self) -> Result<&'a [Dyn]> {
@@ -451,6 +1530,39 @@ impl<'a> ElfReader<'a> {
             .ok_or(ElfReadError::DynEntryNotFound(DynamicTag(tag)))
     }
 
+    /// The names of all `DT_NEEDED` libraries, in the order they appear.
+    pub fn dyn_needed_libraries(&self) -> Result<impl Iterator<Item = Result<&'a BStr>>> {
+        let elf = self.clone();
+        Ok(self
+            .dyn_entries()?
+            .iter()
+            .filter(|dy| dy.tag == c::DT_NEEDED)
+            .map(move |dy| {
+                let idx = u32::try_from(dy.val).map_err(|_| {
+                    ElfReadError::IndexOutOfBounds("dyn string table", dy.val as usize)
+                })?;
+                elf.dyn_string(StringIdx(idx))
+            }))
+    }
+
+    /// The CIE and FDE records of the `.eh_frame` section.
+    pub fn eh_frame_entries(&self) -> Result<EhFrameIterator<'a>> {
+        let sh = self.section_header_by_name(b".eh_frame")?;
+        let data = self.section_content(sh)?;
+        Ok(EhFrameIterator::new(data, sh.addr))
+    }
+
+    /// The `.eh_frame_hdr` section with the binary search table of the FDEs, if there is one.
+    pub fn eh_frame_hdr(&self) -> Result<Option<EhFrameHdr<'a>>> {
+        let sh = match self.section_header_by_name(b".eh_frame_hdr") {
+            Ok(sh) => sh,
+            Err(ElfReadError::NotFoundByName(..)) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let data = self.section_content(sh)?;
+        EhFrameHdr::parse(data, sh.addr).map(Some)
+    }
+
     pub fn dyn_content(&self, addr: u64, size: u64) -> Result<&'a [u8]> {
         self.data
             .get_elf(addr.., "dyn content offset")?
@@ -458,15 +1570,53 @@ impl<'a> ElfReader<'a> {
     }
 }
 
-fn load_ref<'a, T: Pod>(data: &'a [u8], kind: impl Into<String>) -> Result<&'a T> {
+/// Finds the first COMDAT group with every signature in `files`, as the index of the file and
+/// the index of the group section. Later groups with the same signature are duplicates.
+pub fn collect_comdat_groups(
+    files: &[ElfReader<'_>],
+) -> Result<HashMap<String, (usize, c::SectionIdx)>> {
+    let mut groups = HashMap::new();
+
+    for (file_idx, elf) in files.iter().enumerate() {
+        for group in elf.section_groups()? {
+            if group.is_comdat() {
+                groups
+                    .entry(group.signature.to_string())
+                    .or_insert((file_idx, group.section));
+            }
+        }
+    }
+
+    Ok(groups)
+}
+
+/// Returns the cached value or computes it. Errors aren't cached, they are computed again.
+fn cached<T: Copy>(cell: &OnceLock<T>, compute: impl FnOnce() -> Result<T>) -> Result<T> {
+    if let Some(&value) = cell.get() {
+        return Ok(value);
+    }
+    let value = compute()?;
+    Ok(*cell.get_or_init(|| value))
+}
+
+fn string_at<I: ToIdxUsize>(str_table: &[u8], idx: I) -> Result<&BStr> {
+    let indexed = str_table.get_elf(idx.., "string offset")?;
+    let end = indexed
+        .iter()
+        .position(|&c| c == b'\0')
+        .ok_or(ElfReadError::NoStringNulTerm(idx.to_idx_usize()))?;
+    Ok(BStr::new(&indexed[..end]))
+}
+
+fn load_ref<T: Pod>(data: &[u8], kind: impl Into<String>) -> Result<&T> {
     load_slice(data, 1, kind).map(|slice| &slice[0])
 }
 
-pub(crate) fn load_slice<'a, T: Pod>(
-    data: &'a [u8],
+pub(crate) fn load_slice<T: Pod>(
+    data: &[u8],
     amount_of_elems: usize,
     kind: impl Into<String>,
-) -> Result<&'a [T]> {
+) -> Result<&[T]> {
     let size = mem::size_of::<T>() * amount_of_elems;
     let align = mem::align_of::<T>();
 
@@ -497,28 +1647,16 @@ pub(crate) fn load_slice<'a, T: Pod>(
 
 #[cfg(test)]
 mod tests {
-    use std::{fs, path::Path};
+    use std::num::NonZeroU64;
 
-    use memmap2::Mmap;
+    use bstr::ByteSlice;
 
     use super::*;
-
-    fn load_test_file(file_name: impl AsRef<Path>) -> Mmap {
-        let name = file_name.as_ref();
-        let this_file_path = Path::new(env!("CARGO_MANIFEST_DIR")).join(file!());
-        let double_this_crate = this_file_path.parent().unwrap().parent().unwrap();
-        let workspace_root = double_this_crate.parent().unwrap().parent().unwrap();
-
-        let file_path = workspace_root.join("test_data").join("out").join(name);
-
-        let file = fs::File::open(&file_path).expect(&format!(
-            "Failed to open test file {} at path {}. Consider running `test_data/create_test_data.sh` to create the test data files",
-            name.display(),
-            file_path.display()
-        ));
-
-        unsafe { Mmap::map(&file).unwrap() }
-    }
+    use crate::{
+        eh_frame::EhFrameEntry,
+        test_data::load_test_file,
+        write::{ElfWriter, Header, Section},
+    };
 
     #[test]
     fn rust_hello_world_bin() -> super::Result<()> {
@@ -540,6 +1678,259 @@ mod tests {
             println!("{name:20} {:5} {:?}", section.size, section.r#type);
         }
 
+        let build_id = elf.gnu_build_id()?.expect("no build id");
+        assert_eq!(build_id.len(), 20);
+
+        assert_eq!(
+            elf.gnu_stack_flags()?,
+            Some(c::PhFlags::PF_R | c::PhFlags::PF_W)
+        );
+
+        let interpreter = elf.interpreter()?.expect("no interpreter");
+        assert!(interpreter.starts_with(b"/"), "{interpreter}");
+        assert!(interpreter.contains_str("ld-linux"), "{interpreter}");
+
+        let main = elf.symbol_by_name(b"main")?;
+        assert_ne!(main.size, 0);
+        for addr in [main.value, main.value + (main.size - 1)] {
+            let found = elf.find_symbol_at_address(addr)?.expect("no symbol");
+            assert_eq!(elf.string(found.name)?, "main");
+        }
+
+        assert_eq!(elf.init_array()?.len(), 1);
+        assert_eq!(elf.fini_array()?.len(), 1);
+
+        let eh_frame = elf.eh_frame_entries()?.collect::<super::Result<Vec<_>>>()?;
+        let EhFrameEntry::Cie(cie) = eh_frame[0] else {
+            panic!("first entry is not a CIE: {:?}", eh_frame[0]);
+        };
+        assert_eq!(cie.augmentation, "zR");
+        assert_eq!(cie.code_alignment, 1);
+        assert_eq!(cie.data_alignment, -8);
+        assert_eq!(cie.return_address_register, 16);
+        assert!(
+            eh_frame.iter().any(|entry| matches!(
+                entry,
+                EhFrameEntry::Fde(fde) if fde.pc_begin == header.entry && fde.pc_range > 0
+            )),
+            "no FDE for the entrypoint"
+        );
+
+        let eh_frame_sh = elf.section_header_by_name(b".eh_frame")?;
+        let hdr = elf.eh_frame_hdr()?.expect("no .eh_frame_hdr");
+        assert_eq!(hdr.version, 1);
+        assert_eq!(hdr.eh_frame_ptr, eh_frame_sh.addr);
+        let table = hdr.entries().collect::<super::Result<Vec<_>>>()?;
+        assert_eq!(table.len() as u64, hdr.fde_count);
+        assert!(table.windows(2).all(|w| w[0].initial_pc < w[1].initial_pc));
+        for entry in &table {
+            let offset = Offset(entry.fde.u64() - eh_frame_sh.addr.u64());
+            assert!(
+                eh_frame.iter().any(|e| matches!(
+                    e,
+                    EhFrameEntry::Fde(fde) if fde.offset == offset && fde.pc_begin == entry.initial_pc
+                )),
+                "no FDE for {entry:?}"
+            );
+        }
+
+        let needed = elf
+            .dyn_needed_libraries()?
+            .collect::<super::Result<Vec<_>>>()?;
+        assert!(needed.contains(&BStr::new("libc.so.6")), "{needed:?}");
+
+        assert!(elf.sections_outside_load_segments()?.is_empty());
+
+        let text = elf.section_at_vaddr(main.value)?.expect("no section");
+        assert_eq!(elf.sh_string(text.name)?, ".text");
+        assert!(elf.section_at_vaddr(Addr(0))?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn dyn_strings_are_read_from_dynstr() -> super::Result<()> {
+        let file = load_test_file("hello_world");
+        let elf = ElfReader::new(&file)?;
+
+        // `DT_STRTAB` is an address, which has to be mapped to the `.dynstr` section.
+        let dynstr = elf.section_header_by_name(b".dynstr")?;
+        let strtab = elf.dyn_entry_by_tag(c::DT_STRTAB)?;
+        assert_eq!(strtab.val, dynstr.addr.u64());
+        let content = elf.section_content(dynstr)?;
+
+        let needed = elf
+            .dyn_entries()?
+            .iter()
+            .filter(|dy| dy.tag == c::DT_NEEDED)
+            .collect::<Vec<_>>();
+        assert!(!needed.is_empty());
+        for dy in needed {
+            let idx = StringIdx(dy.val as u32);
+            let name = elf.dyn_string(idx)?;
+            assert_eq!(name, string_at(content, idx)?);
+            assert!(
+                name.iter().all(|b| b.is_ascii_graphic()),
+                "garbage library name: {name:?}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn section_at_vaddr_with_overflowing_size() -> super::Result<()> {
+        let mut writer = ElfWriter::new(Header::x86_64(c::ET_EXEC));
+        let name = writer.add_sh_string(b".bss");
+        let bss = writer
+            .add_section(Section {
+                name,
+                ..Section::nobits(u64::MAX)
+            })
+            .unwrap();
+        writer.set_section_addr(bss, Addr(0x1000));
+        let data = writer.write().unwrap();
+
+        let elf = ElfReader::new(&data)?;
+        assert!(elf.section_at_vaddr(Addr(0x2000))?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn symbol_at_address_with_overflowing_size() -> super::Result<()> {
+        let mut writer = ElfWriter::new(Header::x86_64(c::ET_EXEC));
+        let name = writer.add_sh_string(b".bss");
+        let bss = writer
+            .add_section(Section {
+                name,
+                ..Section::nobits(0x1000)
+            })
+            .unwrap();
+        let mut symbols = writer.symbol_table_builder();
+        symbols.add_symbol(crate::write::Symbol {
+            name: b"huge",
+            r#type: c::SymbolType(c::STT_OBJECT),
+            binding: c::SymbolBinding(c::STB_GLOBAL),
+            visibility: c::SymbolVisibility(c::STV_DEFAULT),
+            section: bss,
+            value: Addr(0x1000),
+            size: u64::MAX,
+        });
+        writer.finish_symbol_table(symbols).unwrap();
+        let data = writer.write().unwrap();
+
+        let elf = ElfReader::new(&data)?;
+        let sym = elf
+            .find_symbol_at_address(Addr(0x2000))?
+            .expect("no symbol");
+        assert_eq!(elf.string(sym.name)?, "huge");
+
+        Ok(())
+    }
+
+    fn object_writer() -> ElfWriter {
+        ElfWriter::new(Header::x86_64(c::ET_REL))
+    }
+
+    /// An object file with a COMDAT group of one `.text` section, with `padding` empty
+    /// sections before the group to give it a different index in every file.
+    fn comdat_object(signature: &[u8], padding: usize) -> Vec<u8> {
+        let mut writer = object_writer();
+
+        for i in 0..padding {
+            let name = writer.add_sh_string(format!(".pad{i}").as_bytes());
+            writer
+                .add_section(Section {
+                    name,
+                    ..Section::nobits(0)
+                })
+                .unwrap();
+        }
+
+        let text_name = writer.add_sh_string(b".text.comdat");
+        let text = writer
+            .add_section(Section {
+                flags: c::ShFlags::SHF_ALLOC | c::ShFlags::SHF_EXECINSTR | c::ShFlags::SHF_GROUP,
+                ..Section::progbits(text_name, vec![0xc3])
+            })
+            .unwrap();
+
+        let mut symbols = writer.symbol_table_builder();
+        symbols.add_symbol(crate::write::Symbol {
+            name: signature,
+            r#type: c::SymbolType(c::STT_SECTION),
+            binding: c::SymbolBinding(c::STB_LOCAL),
+            visibility: c::SymbolVisibility(c::STV_DEFAULT),
+            section: text,
+            value: Addr(0),
+            size: 0,
+        });
+        let symtab = writer.finish_symbol_table(symbols).unwrap();
+
+        let group_name = writer.add_sh_string(b".group");
+        let content = [c::GRP_COMDAT, u32::from(text.0)];
+        writer
+            .add_section(Section {
+                name: group_name,
+                r#type: ShType(c::SHT_GROUP),
+                flags: c::ShFlags::empty(),
+                fixed_entsize: NonZeroU64::new(4),
+                addr_align: NonZeroU64::new(4),
+                content: bytemuck::cast_slice(&content).to_vec(),
+                nobits_size: 0,
+                link: symtab.0.into(),
+                info: 1,
+            })
+            .unwrap();
+
+        writer.write().unwrap()
+    }
+
+    #[test]
+    fn comdat_groups_keep_the_first() -> super::Result<()> {
+        let first = comdat_object(b"inline_fn", 0);
+        let second = comdat_object(b"inline_fn", 2);
+        let other = comdat_object(b"other_fn", 1);
+        let without_groups = object_writer().write().unwrap();
+
+        let files =
+            [&without_groups, &first, &second, &other].map(|data| ElfReader::new(data).unwrap());
+        let groups = collect_comdat_groups(&files)?;
+
+        assert_eq!(groups.len(), 2);
+        let first_group = files[1].section_groups()?.next().unwrap();
+        assert_eq!(groups["inline_fn"], (1, first_group.section));
+        let other_group = files[3].section_groups()?.next().unwrap();
+        assert_eq!(groups["other_fn"], (3, other_group.section));
+        assert_ne!(first_group.section, other_group.section);
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_to_other_threads() -> super::Result<()> {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<ElfReader<'_>>();
+        assert_send_sync::<OwnedElfReader>();
+
+        let file = load_test_file("hello_world");
+        let borrowed = ElfReader::new(&file)?;
+        let entry = std::thread::scope(|scope| {
+            scope
+                .spawn(|| borrowed.header().map(|header| header.entry))
+                .join()
+                .unwrap()
+        })?;
+
+        let owned = OwnedElfReader::from_arc(Arc::from(&file[..]))?;
+        let sent = owned.clone();
+        let owned_entry = std::thread::spawn(move || sent.reader().header().map(|h| h.entry))
+            .join()
+            .unwrap()?;
+        assert_eq!(entry, owned_entry);
+        assert_eq!(owned.reader().header()?.entry, entry);
+
         Ok(())
     }
 
@@ -549,6 +1940,12 @@ mod tests {
         let elf = ElfReader::new(&file)?;
         let header = elf.header()?;
 
+        assert_eq!(elf.interpreter()?, None);
+        assert_eq!(
+            elf.gnu_stack_flags()?,
+            Some(c::PhFlags::PF_R | c::PhFlags::PF_W)
+        );
+
         assert_eq!(header.ident.class, c::ELFCLASS64);
         assert_eq!(header.ident.data, c::ELFDATA2LSB);
         assert_eq!(header.ident.osabi, c::ELFOSABI_SYSV);
@@ -565,9 +1962,26 @@ mod tests {
             println!("{name:20} {:5} {:?}", sh.size, sh.r#type);
         }
 
+        let rela_sections = elf
+            .section_headers_by_type(c::SHT_RELA)?
+            .map(|sh| elf.sh_string(sh.name))
+            .collect::<super::Result<Vec<_>>>()?;
+        assert_eq!(rela_sections, [".rela.text", ".rela.eh_frame"]);
+
+        let (text_idx, _) = elf
+            .section_headers_with_idx()?
+            .find(|(_, sh)| elf.sh_string(sh.name).is_ok_and(|name| name == ".text"))
+            .expect("no .text");
+        for (idx, sh, _) in elf.relas_with_section_idx()? {
+            assert!(std::ptr::eq(elf.section_header(idx)?, sh));
+        }
+        assert!(elf
+            .relas_with_section_idx()?
+            .any(|(_, sh, _)| sh.info == u32::from(text_idx.0)));
+
         println!("Relocations:\n");
 
-        println!("{:20} {:10} {}", "Section", "Symbol", "Relocation");
+        println!("{:20} {:10} Relocation", "Section", "Symbol");
 
         let mut has_puts = false;
         for (sh, rela) in elf.relas()? {
@@ -583,6 +1997,293 @@ mod tests {
 
         assert!(has_puts, "puts symbol not found");
 
+        assert_eq!(elf.rels()?.count(), 0);
+        assert_eq!(elf.relocations()?.count(), elf.relas()?.count());
+
+        Ok(())
+    }
+
+    #[test]
+    fn riscv_object_relocations() -> super::Result<()> {
+        let file = load_test_file("relocs_riscv.o");
+        let elf = ElfReader::new(&file)?;
+
+        assert_eq!(elf.header()?.machine, c::EM_RISCV);
+
+        let types = elf
+            .relas()?
+            .map(|(_, rela)| c::RRiscv(rela.info.r#type()).to_string())
+            .collect::<Vec<_>>();
+
+        for expected in [
+            "R_RISCV_CALL",
+            "R_RISCV_HI20",
+            "R_RISCV_LO12_I",
+            "R_RISCV_PCREL_HI20",
+            "R_RISCV_PCREL_LO12_I",
+            "R_RISCV_64",
+        ] {
+            assert!(
+                types.iter().any(|ty| ty == expected),
+                "{expected} in {types:?}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn elf32_is_rejected() {
+        let mut data = [0_u8; mem::size_of::<ElfHeader>()];
+        data[..c::SELFMAG].copy_from_slice(c::ELFMAG);
+        data[c::EI_CLASS] = c::ELFCLASS32;
+        data[c::EI_DATA] = c::ELFDATA2LSB;
+
+        let err = ElfReader::new(&data).unwrap_err();
+        assert!(
+            matches!(err, ElfReadError::UnsupportedClass(class) if class == c::ELFCLASS32),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn foreign_endian_is_rejected() {
+        let foreign = if HOST_DATA == c::ELFDATA2LSB {
+            c::ELFDATA2MSB
+        } else {
+            c::ELFDATA2LSB
+        };
+
+        let mut data = [0_u8; mem::size_of::<ElfHeader>()];
+        data[..c::SELFMAG].copy_from_slice(c::ELFMAG);
+        data[c::EI_CLASS] = c::ELFCLASS64;
+        data[c::EI_DATA] = foreign;
+
+        let err = ElfReader::new(&data).unwrap_err();
+        assert!(
+            matches!(err, ElfReadError::UnsupportedEndian(data) if data == foreign),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn c_shared_library_hash_tables() -> super::Result<()> {
+        let file = load_test_file("exports_lib.so");
+        let elf = ElfReader::new(&file)?;
+
+        let gnu_hash = elf.gnu_hash_table()?;
+        let sysv_hash = elf.sysv_hash_table()?;
+
+        for name in [b"exported_function".as_slice(), b"exported_data"] {
+            let idx = gnu_hash.lookup_symbol(name).expect("symbol not found");
+            assert_eq!(sysv_hash.lookup_symbol(name), Some(idx));
+
+            let sym = elf.dyn_symbol(idx)?;
+            assert_eq!(elf.dyn_string(sym.name)?, name);
+        }
+
+        // Hidden by the version script, so it is only in .symtab.
+        elf.symbol_by_name(b"hidden_function")?;
+        assert_eq!(gnu_hash.lookup_symbol(b"hidden_function"), None);
+        assert_eq!(sysv_hash.lookup_symbol(b"hidden_function"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn gnu_hash_with_too_large_bloom_shift() {
+        let header = super::GnuHashHeader {
+            nbuckets: 1,
+            symoffset: 1,
+            bloom_size: 1,
+            bloom_shift: 40,
+        };
+        let table = super::GnuHashTable {
+            header: &header,
+            bloom: &[u64::MAX],
+            buckets: &[1],
+            chains: &[0],
+            symbols: &[],
+            strings: &[],
+        };
+        assert_eq!(table.lookup_symbol(b"anything"), None);
+    }
+
+    #[test]
+    fn symbol_versions() -> super::Result<()> {
+        let file = load_test_file("exports_lib.so");
+        let elf = ElfReader::new(&file)?;
+
+        let versyms = elf.versym_table()?;
+        assert_eq!(versyms.len(), elf.dyn_symbols()?.len());
+
+        let defs = elf
+            .verdef_entries()?
+            .map(|(def, mut aux)| {
+                let name = aux.next().expect("no verdaux").name;
+                Ok((def.ndx, def.flags, elf.dyn_string(name)?.to_string()))
+            })
+            .collect::<super::Result<Vec<_>>>()?;
+        assert_eq!(
+            defs,
+            [
+                (1, c::VER_FLG_BASE, "exports_lib.so".to_owned()),
+                (2, 0, "EXPORTS_1.0".to_owned())
+            ]
+        );
+
+        let (sym_idx, _) = elf
+            .dyn_symbols()?
+            .iter()
+            .enumerate()
+            .find(|(_, sym)| {
+                elf.dyn_string(sym.name)
+                    .is_ok_and(|n| n == "exported_function")
+            })
+            .expect("exported_function not found");
+        assert_eq!(versyms[sym_idx].index(), 2);
+
+        let file = load_test_file("hello_world");
+        let elf = ElfReader::new(&file)?;
+
+        let mut glibc_versions = Vec::new();
+        for (need, aux) in elf.verneed_entries()? {
+            if elf.dyn_string(need.file)? == "libc.so.6" {
+                for aux in aux {
+                    glibc_versions.push(elf.dyn_string(aux.name)?);
+                }
+            }
+        }
+        assert!(
+            glibc_versions.iter().any(|v| v.starts_with(b"GLIBC_2.")),
+            "{glibc_versions:?}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serialize_header() -> super::Result<()> {
+        let file = load_test_file("hello_world");
+        let elf = ElfReader::new(&file)?;
+        let header = serde_json::to_value(elf.header()?).unwrap();
+
+        assert_eq!(header["type"], "ET_DYN");
+        assert_eq!(header["machine"], "EM_X86_64");
+        assert_eq!(header["ident"]["class"], "ELFCLASS64");
+        assert_eq!(header["entry"], elf.header()?.entry.to_string());
+        assert!(header["ident"].get("_pad").is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn gnu_debuglink() -> super::Result<()> {
+        let file = load_test_file("hello_world");
+        assert_eq!(ElfReader::new(&file)?.gnu_debuglink()?, None);
+
+        let mut writer = ElfWriter::new(Header::x86_64(c::ET_EXEC));
+        let name = writer.add_sh_string(b".gnu_debuglink");
+        let mut content = b"app.debug\0\0\0".to_vec();
+        content.extend(0xdeadbeef_u32.to_le_bytes());
+        writer
+            .add_section(Section {
+                flags: c::ShFlags::empty(),
+                addr_align: NonZeroU64::new(4),
+                ..Section::progbits(name, content)
+            })
+            .unwrap();
+        let data = writer.write().unwrap();
+
+        let elf = ElfReader::new(&data)?;
+        assert_eq!(
+            elf.gnu_debuglink()?,
+            Some(DebugLink {
+                filename: BStr::new("app.debug"),
+                crc: 0xdeadbeef,
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn all_symbols() -> super::Result<()> {
+        let file = load_test_file("hello_world");
+        let elf = ElfReader::new(&file)?;
+        let symbols = elf.iter_all_symbols()?.collect::<Vec<_>>();
+
+        let count = |source| symbols.iter().filter(|(_, s)| *s == source).count();
+        assert_eq!(count(SymbolSource::Static), elf.symbols()?.len());
+        assert_eq!(count(SymbolSource::Dynamic), elf.dyn_symbols()?.len());
+        assert!(symbols
+            .iter()
+            .any(|&(sym, source)| source == SymbolSource::Dynamic
+                && elf
+                    .dyn_string(sym.name)
+                    .is_ok_and(|name| name == "__libc_start_main")));
+
+        let file = load_test_file("hello_world_obj.o");
+        let elf = ElfReader::new(&file)?;
+        assert!(elf
+            .iter_all_symbols()?
+            .all(|(_, source)| source == SymbolSource::Static));
+
+        Ok(())
+    }
+
+    #[test]
+    fn plt_entries() -> super::Result<()> {
+        let file = load_test_file("hello_world");
+        let elf = ElfReader::new(&file)?;
+        let plt = elf.section_header_by_name(b".plt")?;
+        let got_plt = elf.section_header_by_name(b".got.plt")?;
+
+        let entries = elf.plt_entries()?.collect::<Vec<_>>();
+        assert!(!entries.is_empty());
+        for entry in &entries {
+            assert!(entry.addr >= plt.addr && entry.addr < plt.addr + plt.size);
+            assert!(entry.got_slot >= got_plt.addr && entry.got_slot < got_plt.addr + got_plt.size);
+        }
+        assert!(entries
+            .iter()
+            .any(|entry| entry.symbol == Some(BStr::new("__cxa_finalize"))));
+
+        let file = load_test_file("hello_world_obj.o");
+        assert_eq!(ElfReader::new(&file)?.plt_entries()?.count(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn validate() -> super::Result<()> {
+        for name in ["hello_world", "hello_world_obj.o"] {
+            let file = load_test_file(name);
+            let elf = ElfReader::new(&file)?;
+            assert_eq!(elf.validate()?, [], "{name}");
+        }
+
+        let mut writer = ElfWriter::new(Header::x86_64(c::ET_EXEC));
+        for name in [b".text".as_slice(), b".rodata"] {
+            let name = writer.add_sh_string(name);
+            let idx = writer
+                .add_section(Section::progbits(name, vec![0; 16]))
+                .unwrap();
+            writer.set_section_addr(idx, Addr(0x1000 + 8 * u64::from(idx.0)));
+        }
+        let data = writer.write().unwrap();
+
+        let elf = ElfReader::new(&data)?;
+        assert_eq!(
+            elf.validate()?,
+            [ElfWarning::OverlappingSections {
+                a_name: ".text".into(),
+                b_name: ".rodata".into(),
+                overlap_range: Addr(0x1018)..Addr(0x1020),
+            }]
+        );
+
         Ok(())
     }
 }
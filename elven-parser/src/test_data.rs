@@ -0,0 +1,19 @@
+use std::{fs, path::Path};
+
+use memmap2::Mmap;
+
+/// Opens a file created by `test_data/create_test_data.sh`.
+pub(crate) fn open_test_file(name: impl AsRef<Path>) -> fs::File {
+    let workspace_root = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+    let path = workspace_root.join("test_data/out").join(name);
+    fs::File::open(&path).unwrap_or_else(|_| {
+        panic!(
+            "test data {} missing, run test_data/create_test_data.sh",
+            path.display()
+        )
+    })
+}
+
+pub(crate) fn load_test_file(name: impl AsRef<Path>) -> Mmap {
+    unsafe { Mmap::map(&open_test_file(name)) }.unwrap()
+}
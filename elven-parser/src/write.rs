@@ -1,21 +1,47 @@
-use bytemuck::Pod;
+use bstr::{BStr, BString, ByteSlice};
+use bytemuck::{Pod, Zeroable};
 
 use crate::consts::{
-    Machine, PhFlags, PhType, SectionIdx, ShFlags, ShType, Type, SHT_NULL, SHT_STRTAB,
+    Class, Data, DynamicTag, Machine, OsAbi, PhFlags, PhType, SectionIdx, ShFlags, ShType,
+    SymbolBinding, SymbolType, SymbolVisibility, Type, DT_NEEDED, DT_NULL, DT_RELA, DT_RELAENT,
+    DT_RELASZ, DT_RPATH, DT_SONAME, DT_STRSZ, DT_STRTAB, DT_SYMENT, DT_SYMTAB, ELFCLASS64,
+    ELFDATA2LSB, ELFMAG, ELFOSABI_SYSV, EM_X86_64, NT_GNU_BUILD_ID, PT_LOAD, PT_PHDR,
+    SHN_LORESERVE, SHN_UNDEF, SHT_DYNAMIC, SHT_DYNSYM, SHT_GROUP, SHT_NOBITS, SHT_NOTE, SHT_NULL,
+    SHT_PROGBITS, SHT_REL, SHT_RELA, SHT_STRTAB, SHT_SYMTAB, STB_LOCAL,
+};
+use crate::patch::ElfPatcher;
+use crate::read::{
+    self, ElfHeader, ElfIdent, ElfReadError, Phdr, RelInfo, ShStringIdx, Shdr, StringIdx, SymIdx,
+    SymInfo,
 };
-use crate::read::{self, ElfHeader, ElfIdent, Phdr, ShStringIdx, Shdr};
 use crate::{Addr, Offset};
-use std::io::Write;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
 use std::mem::size_of;
 use std::num::NonZeroU64;
+use std::path::Path;
 use std::{io, mem};
 
 #[derive(Debug, thiserror::Error)]
 pub enum WriteElfError {
     #[error("Too many {0}")]
     TooMany(&'static str),
+    #[error("A {0} section must be added first")]
+    MissingSection(&'static str),
+    #[error("A section with the name {0:?} was already added")]
+    DuplicateSectionName(BString),
+    #[error("The {0} section can't be removed")]
+    CannotRemoveSection(&'static str),
+    #[error("Program header {0} refers to a removed section")]
+    DanglingProgramHeader(usize),
+    #[error("Section {0} is still referenced by another section")]
+    SectionInUse(SectionIdx),
+    #[error("PT_PHDR needs a program header with the extent of the headers to be in memory")]
+    UnloadedProgramHeaders,
     #[error("Writer IO error")]
     Io(#[from] io::Error),
+    #[error("Failed to patch the output")]
+    Patch(#[from] ElfReadError),
 }
 
 pub type Result<T> = std::result::Result<T, WriteElfError>;
@@ -24,23 +50,109 @@ pub type Result<T> = std::result::Result<T, WriteElfError>;
 pub struct ElfWriter {
     header: read::ElfHeader,
     sections: Vec<Section>,
+    /// The virtual addresses of the sections, by index. Zero for sections that are not loaded.
+    section_addrs: Vec<Addr>,
     programs_headers: Vec<ProgramHeader>,
+    program_header_extents: Vec<(usize, ProgramHeaderExtent)>,
+    /// Whether adding a section with the name of an existing one is an error.
+    strict_dedup: bool,
+    /// Whether a `PT_PHDR` program header is written before all others.
+    phdr_program_header: bool,
+    /// The page size that `PT_LOAD` segments are laid out for, if any.
+    page_size: Option<u64>,
+    /// How the ID of the reserved `.note.gnu.build-id` is computed.
+    build_id: Option<BuildIdAlgo>,
+}
+
+/// How the ID of the `NT_GNU_BUILD_ID` note is computed, like `--build-id=<style>` of ld.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildIdAlgo {
+    /// The SHA-1 hash of the output, 20 bytes.
+    Sha1,
+    /// The MD5 hash of the output, 16 bytes.
+    Md5,
+    /// 16 random bytes, different for every write.
+    Uuid,
+}
+
+pub const BUILD_ID_SECTION_NAME: &[u8] = b".note.gnu.build-id";
+const BUILD_ID_NOTE_NAME: &[u8; 4] = b"GNU\0";
+/// The offset of the ID in the note, after `n_namesz`, `n_descsz`, `n_type` and the name.
+const BUILD_ID_OFFSET: usize = 12 + BUILD_ID_NOTE_NAME.len();
+
+impl BuildIdAlgo {
+    pub fn id_len(self) -> usize {
+        match self {
+            Self::Sha1 => 20,
+            Self::Md5 | Self::Uuid => 16,
+        }
+    }
+
+    /// The size of the whole note.
+    pub fn note_size(self) -> u64 {
+        (BUILD_ID_OFFSET + self.id_len()) as u64
+    }
+
+    fn compute(self, output: &[u8]) -> io::Result<Vec<u8>> {
+        Ok(match self {
+            Self::Sha1 => sha1_smol::Sha1::from(output).digest().bytes().to_vec(),
+            Self::Md5 => md5::compute(output).0.to_vec(),
+            Self::Uuid => {
+                let mut id = vec![0; self.id_len()];
+                File::open("/dev/urandom")?.read_exact(&mut id)?;
+                id
+            }
+        })
+    }
+}
+
+/// What the `filesz` and `memsz` of a program header are computed from, since the file layout
+/// is only known while writing.
+#[derive(Debug, Clone, Copy)]
+pub enum ProgramHeaderExtent {
+    /// From the start of `start` to the end of `end`. `SHT_NOBITS` sections only count
+    /// towards `memsz`.
+    Sections { start: SectionIdx, end: SectionIdx },
+    /// The ELF header and the program headers.
+    Headers,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
 pub struct Header {
     pub ident: ElfIdent,
     pub r#type: Type,
     pub machine: Machine,
 }
 
+impl Header {
+    /// The header of a little endian 64 bit file for x86-64.
+    pub fn x86_64(r#type: u16) -> Self {
+        Self {
+            ident: ElfIdent {
+                magic: *ELFMAG,
+                class: Class(ELFCLASS64),
+                data: Data(ELFDATA2LSB),
+                version: 1,
+                osabi: OsAbi(ELFOSABI_SYSV),
+                abiversion: 0,
+                _pad: [0; 7],
+            },
+            r#type: Type(r#type),
+            machine: Machine(EM_X86_64),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
 pub struct SectionRelativeAbsoluteAddr {
     pub section: SectionIdx,
     pub rel_offset: Offset,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
 pub struct Section {
     pub name: read::ShStringIdx,
     pub r#type: ShType,
@@ -48,9 +160,185 @@ pub struct Section {
     pub fixed_entsize: Option<NonZeroU64>,
     pub addr_align: Option<NonZeroU64>,
     pub content: Vec<u8>,
+    /// The size of a `SHT_NOBITS` section, which has no content in the file.
+    pub nobits_size: u64,
+    pub link: u32,
+    pub info: u32,
+}
+
+/// Collects the entries of the `.dynamic` section, the strings they refer to and the
+/// relocations for the dynamic linker. Written with [`ElfWriter::finish_dynamic`].
+#[derive(Debug, Clone)]
+pub struct DynamicSectionBuilder {
+    strings: Vec<u8>,
+    entries: Vec<read::Dyn>,
+    relocations: Vec<read::Rela>,
+}
+
+/// The sections added by [`ElfWriter::finish_dynamic`], in the order of their addresses.
+#[derive(Debug, Clone, Copy)]
+pub struct DynamicSections {
+    pub dynsym: SectionIdx,
+    pub dynstr: SectionIdx,
+    pub rela: Option<SectionIdx>,
+    pub dynamic: SectionIdx,
+    pub dynamic_addr: Addr,
+}
+
+impl DynamicSectionBuilder {
+    pub fn add_needed(&mut self, lib: &[u8]) {
+        let name = self.add_string(lib);
+        self.add_entry(DynamicTag(DT_NEEDED), name);
+    }
+
+    pub fn set_soname(&mut self, name: &[u8]) {
+        let name = self.add_string(name);
+        self.set_entry(DynamicTag(DT_SONAME), name);
+    }
+
+    pub fn set_rpath(&mut self, path: &[u8]) {
+        let path = self.add_string(path);
+        self.set_entry(DynamicTag(DT_RPATH), path);
+    }
+
+    pub fn add_entry(&mut self, tag: DynamicTag, val: u64) {
+        self.entries.push(read::Dyn { tag, val });
+    }
+
+    /// Adds a relocation to `.rela.dyn`. `.dynsym` only contains the null symbol,
+    /// so the relocation can't refer to a symbol.
+    pub fn add_relocation(&mut self, offset: Addr, r#type: u32, addend: i64) {
+        self.relocations.push(read::Rela {
+            offset,
+            info: RelInfo::new(SymIdx(0), r#type),
+            addend,
+        });
+    }
+
+    fn set_entry(&mut self, tag: DynamicTag, val: u64) {
+        match self.entries.iter_mut().find(|entry| entry.tag == tag) {
+            Some(entry) => entry.val = val,
+            None => self.add_entry(tag, val),
+        }
+    }
+
+    /// Adds a string to `.dynstr`, returning its offset.
+    fn add_string(&mut self, content: &[u8]) -> u64 {
+        let idx = self.strings.len();
+        self.strings.extend(content);
+        self.strings.push(0);
+        idx as u64
+    }
+}
+
+/// Collects the symbols of `.symtab` and their names in `.strtab`.
+/// Written with [`ElfWriter::finish_symbol_table`].
+#[derive(Debug, Clone)]
+pub struct SymbolTableBuilder {
+    strings: Vec<u8>,
+    locals: Vec<read::Sym>,
+    globals: Vec<read::Sym>,
+}
+
+/// A symbol for [`SymbolTableBuilder::add_symbol`].
+#[derive(Debug, Clone, Copy)]
+pub struct Symbol<'a> {
+    pub name: &'a [u8],
+    pub r#type: SymbolType,
+    pub binding: SymbolBinding,
+    pub visibility: SymbolVisibility,
+    pub section: SectionIdx,
+    pub value: Addr,
+    pub size: u64,
+}
+
+impl SymbolTableBuilder {
+    /// Adds a symbol. Local symbols always come before all others in the table,
+    /// regardless of the order they are added in.
+    pub fn add_symbol(&mut self, sym: Symbol<'_>) {
+        let name = self.strings.len();
+        self.strings.extend(sym.name);
+        self.strings.push(0);
+
+        let entry = read::Sym {
+            name: StringIdx(name as u32),
+            info: SymInfo::new(sym.r#type, sym.binding),
+            other: sym.visibility,
+            shndx: sym.section,
+            value: sym.value,
+            size: sym.size,
+        };
+        if sym.binding == STB_LOCAL {
+            self.locals.push(entry);
+        } else {
+            self.globals.push(entry);
+        }
+    }
+}
+
+/// A relocation with an explicit addend, for [`ElfWriter::add_rela_section`].
+#[derive(Debug, Clone, Copy)]
+pub struct Rela {
+    pub offset: Addr,
+    pub sym: SymIdx,
+    pub r#type: u32,
+    pub addend: i64,
+}
+
+/// A relocation without an addend, for [`ElfWriter::add_rel_section`].
+#[derive(Debug, Clone, Copy)]
+pub struct Rel {
+    pub offset: Addr,
+    pub sym: SymIdx,
+    pub r#type: u32,
+}
+
+impl Section {
+    /// A zero-initialized section like `.bss` that takes up memory but no space in the file.
+    pub fn nobits(size: u64) -> Self {
+        Self {
+            name: read::ShStringIdx(0),
+            r#type: ShType(SHT_NOBITS),
+            flags: ShFlags::SHF_ALLOC | ShFlags::SHF_WRITE,
+            fixed_entsize: None,
+            addr_align: None,
+            content: Vec::new(),
+            nobits_size: size,
+            link: 0,
+            info: 0,
+        }
+    }
+
+    /// An allocated section with content, like `.rodata`.
+    pub fn progbits(name: read::ShStringIdx, content: Vec<u8>) -> Self {
+        Self {
+            name,
+            r#type: ShType(SHT_PROGBITS),
+            flags: ShFlags::SHF_ALLOC,
+            fixed_entsize: None,
+            addr_align: None,
+            content,
+            nobits_size: 0,
+            link: 0,
+            info: 0,
+        }
+    }
+
+    fn is_nobits(&self) -> bool {
+        self.r#type == SHT_NOBITS
+    }
+
+    fn size(&self) -> u64 {
+        if self.is_nobits() {
+            self.nobits_size
+        } else {
+            self.content.len() as u64
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
 pub struct ProgramHeader {
     pub r#type: PhType,
     pub flags: PhFlags,
@@ -92,6 +380,9 @@ impl ElfWriter {
             content: Vec::new(),
             fixed_entsize: None,
             addr_align: None,
+            nobits_size: 0,
+            link: 0,
+            info: 0,
         };
 
         let shstrtab = Section {
@@ -103,15 +394,49 @@ impl ElfWriter {
             content: b"\0.shstrtab\0".to_vec(),
             fixed_entsize: None,
             addr_align: None,
+            nobits_size: 0,
+            link: 0,
+            info: 0,
         };
 
         Self {
             header,
             sections: vec![null_section, shstrtab],
+            section_addrs: vec![Addr(0); 2],
             programs_headers: Vec::new(),
+            program_header_extents: Vec::new(),
+            strict_dedup: false,
+            phdr_program_header: false,
+            page_size: None,
+            build_id: None,
         }
     }
 
+    /// Makes [`Self::add_section`] reject sections with the name of an existing section,
+    /// which some tools don't accept. Otherwise, duplicates only cause a warning.
+    pub fn set_strict_dedup(&mut self, strict: bool) {
+        self.strict_dedup = strict;
+    }
+
+    /// Writes a `PT_PHDR` program header describing the program headers themselves before all
+    /// other program headers. The program headers must be loaded by a program header with the
+    /// [`ProgramHeaderExtent::Headers`] extent, which determines the address.
+    pub fn set_phdr_program_header(&mut self, enabled: bool) {
+        self.phdr_program_header = enabled;
+    }
+
+    /// Places the section at the start of every `PT_LOAD` program header at a file offset that
+    /// is congruent to its address modulo `page_size`, so the segments can be mapped with
+    /// different protections. Without a page size, sections are only aligned by their
+    /// `addr_align`.
+    pub fn set_page_size(&mut self, page_size: u64) {
+        assert!(
+            page_size.is_power_of_two(),
+            "page size must be a power of two"
+        );
+        self.page_size = Some(page_size);
+    }
+
     pub fn set_entry(&mut self, entry: Addr) {
         self.header.entry = entry;
     }
@@ -124,17 +449,508 @@ impl ElfWriter {
         ShStringIdx(idx as u32)
     }
 
+    fn sh_string(&self, idx: ShStringIdx) -> &BStr {
+        let content = &self.sections[SH_STRTAB].content;
+        let rest = content.get(idx.0 as usize..).unwrap_or_default();
+        let end = rest.find_byte(0).unwrap_or(rest.len());
+        rest[..end].as_bstr()
+    }
+
     pub fn add_section(&mut self, section: Section) -> Result<SectionIdx> {
+        let name = self.sh_string(section.name);
+        let is_duplicate = self
+            .sections
+            .iter()
+            .skip(1)
+            .any(|existing| self.sh_string(existing.name) == name);
+        if is_duplicate {
+            if self.strict_dedup {
+                return Err(WriteElfError::DuplicateSectionName(name.to_owned()));
+            }
+            tracing::warn!(%name, "Adding a second section with the same name");
+        }
+
         let len = self.sections.len();
         self.sections.push(section);
+        self.section_addrs.push(Addr(0));
         Ok(SectionIdx(
             len.try_into()
                 .map_err(|_| WriteElfError::TooMany("sections"))?,
         ))
     }
 
-    pub fn add_program_header(&mut self, ph: ProgramHeader) {
+    /// Removes a section, shifting the indices of all sections after it down by one.
+    /// References to those sections are updated, including the ones from symbols and section
+    /// groups, but references to the removed section itself are an error.
+    /// The null section and `.shstrtab` always stay in place.
+    pub fn remove_section(&mut self, idx: SectionIdx) -> Result<()> {
+        match idx.usize() {
+            0 => return Err(WriteElfError::CannotRemoveSection("null")),
+            SH_STRTAB => return Err(WriteElfError::CannotRemoveSection(".shstrtab")),
+            _ => {}
+        }
+
+        for (ph_idx, ph) in self.programs_headers.iter().enumerate() {
+            let extent_refs = match self.program_header_extent(ph_idx) {
+                Some(ProgramHeaderExtent::Sections { start, end }) => start == idx || end == idx,
+                _ => false,
+            };
+            if ph.offset.section == idx || extent_refs {
+                return Err(WriteElfError::DanglingProgramHeader(ph_idx));
+            }
+        }
+
+        let referenced_by_section = self.sections.iter().any(|sh| {
+            sh.link == u32::from(idx.0)
+                || (sh.flags.contains(ShFlags::SHF_INFO_LINK) && sh.info == u32::from(idx.0))
+        });
+        let mut referenced_by_content = false;
+        self.update_section_refs_in_content(|other| {
+            referenced_by_content |= *other == u32::from(idx.0);
+        });
+        if referenced_by_section || referenced_by_content {
+            return Err(WriteElfError::SectionInUse(idx));
+        }
+
+        self.sections.remove(idx.usize());
+        self.section_addrs.remove(idx.usize());
+
+        let shift = |other: &mut SectionIdx| {
+            if other.0 > idx.0 {
+                other.0 -= 1;
+            }
+        };
+        let shift_u32 = |other: &mut u32| {
+            if *other > u32::from(idx.0) {
+                *other -= 1;
+            }
+        };
+
+        for sh in &mut self.sections {
+            shift_u32(&mut sh.link);
+            if sh.flags.contains(ShFlags::SHF_INFO_LINK) {
+                shift_u32(&mut sh.info);
+            }
+        }
+        self.update_section_refs_in_content(shift_u32);
+        for ph in &mut self.programs_headers {
+            shift(&mut ph.offset.section);
+        }
+        for (_, extent) in &mut self.program_header_extents {
+            if let ProgramHeaderExtent::Sections { start, end } = extent {
+                shift(start);
+                shift(end);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Calls `f` with the section indices in the content of symbol tables and section groups.
+    fn update_section_refs_in_content(&mut self, mut f: impl FnMut(&mut u32)) {
+        for section in &mut self.sections {
+            match section.r#type.0 {
+                SHT_SYMTAB | SHT_DYNSYM => {
+                    for entry in section.content.chunks_exact_mut(size_of::<read::Sym>()) {
+                        let mut sym = bytemuck::pod_read_unaligned::<read::Sym>(entry);
+                        // Special indices like SHN_ABS don't refer to a section.
+                        if sym.shndx.0 == SHN_UNDEF || sym.shndx.0 >= SHN_LORESERVE {
+                            continue;
+                        }
+                        let mut shndx = u32::from(sym.shndx.0);
+                        f(&mut shndx);
+                        sym.shndx.0 = shndx as u16;
+                        entry.copy_from_slice(bytemuck::bytes_of(&sym));
+                    }
+                }
+                SHT_GROUP => {
+                    // The first word contains the flags, the members follow.
+                    for entry in section.content.chunks_exact_mut(4).skip(1) {
+                        let mut member = u32::from_ne_bytes(entry.try_into().unwrap());
+                        f(&mut member);
+                        entry.copy_from_slice(&member.to_ne_bytes());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Replaces the content of a section, keeping its header.
+    pub fn replace_section_content(&mut self, idx: SectionIdx, content: Vec<u8>) {
+        self.sections[idx.usize()].content = content;
+    }
+
+    /// Sets the address the section is loaded at. Only meaningful for `SHF_ALLOC` sections.
+    pub fn set_section_addr(&mut self, idx: SectionIdx, addr: Addr) {
+        self.section_addrs[idx.usize()] = addr;
+    }
+
+    /// Adds a `SHT_RELA` section for `target_section`. The relocations refer to the
+    /// symbol table, which must have been added before.
+    pub fn add_rela_section(
+        &mut self,
+        name: ShStringIdx,
+        target_section: SectionIdx,
+        relas: Vec<Rela>,
+    ) -> Result<SectionIdx> {
+        let relas = relas
+            .into_iter()
+            .map(|rela| read::Rela {
+                offset: rela.offset,
+                info: RelInfo::new(rela.sym, rela.r#type),
+                addend: rela.addend,
+            })
+            .collect::<Vec<_>>();
+
+        self.add_relocation_section::<read::Rela>(name, ShType(SHT_RELA), target_section, &relas)
+    }
+
+    /// Adds a `SHT_REL` section for `target_section`. The relocations refer to the
+    /// symbol table, which must have been added before.
+    pub fn add_rel_section(
+        &mut self,
+        name: ShStringIdx,
+        target_section: SectionIdx,
+        rels: Vec<Rel>,
+    ) -> Result<SectionIdx> {
+        let rels = rels
+            .into_iter()
+            .map(|rel| read::Rel {
+                offset: rel.offset,
+                info: RelInfo::new(rel.sym, rel.r#type),
+            })
+            .collect::<Vec<_>>();
+
+        self.add_relocation_section::<read::Rel>(name, ShType(SHT_REL), target_section, &rels)
+    }
+
+    fn add_relocation_section<T: Pod>(
+        &mut self,
+        name: ShStringIdx,
+        r#type: ShType,
+        target_section: SectionIdx,
+        entries: &[T],
+    ) -> Result<SectionIdx> {
+        let symtab = self
+            .sections
+            .iter()
+            .position(|sh| sh.r#type == SHT_SYMTAB)
+            .or_else(|| self.sections.iter().position(|sh| sh.r#type == SHT_DYNSYM))
+            .ok_or(WriteElfError::MissingSection("symbol table"))?;
+
+        self.add_section(Section {
+            name,
+            r#type,
+            flags: ShFlags::SHF_INFO_LINK,
+            fixed_entsize: NonZeroU64::new(mem::size_of::<T>() as u64),
+            addr_align: NonZeroU64::new(8),
+            content: bytemuck::cast_slice(entries).to_vec(),
+            nobits_size: 0,
+            link: symtab as u32,
+            info: target_section.0.into(),
+        })
+    }
+
+    pub fn symbol_table_builder(&self) -> SymbolTableBuilder {
+        SymbolTableBuilder {
+            // The null string.
+            strings: vec![0],
+            locals: Vec::new(),
+            globals: Vec::new(),
+        }
+    }
+
+    /// Adds the `.strtab` and `.symtab` sections, returning the index of `.symtab`.
+    pub fn finish_symbol_table(&mut self, builder: SymbolTableBuilder) -> Result<SectionIdx> {
+        let SymbolTableBuilder {
+            strings,
+            locals,
+            globals,
+        } = builder;
+
+        let strtab_name = self.add_sh_string(b".strtab");
+        let strtab = self.add_section(Section {
+            name: strtab_name,
+            r#type: ShType(SHT_STRTAB),
+            flags: ShFlags::empty(),
+            fixed_entsize: None,
+            addr_align: None,
+            content: strings,
+            nobits_size: 0,
+            link: 0,
+            info: 0,
+        })?;
+
+        // The null symbol is local, too.
+        let first_global = locals.len() + 1;
+        let symbols = std::iter::once(read::Sym::zeroed())
+            .chain(locals)
+            .chain(globals)
+            .collect::<Vec<_>>();
+
+        let symtab_name = self.add_sh_string(b".symtab");
+        self.add_section(Section {
+            name: symtab_name,
+            r#type: ShType(SHT_SYMTAB),
+            flags: ShFlags::empty(),
+            fixed_entsize: NonZeroU64::new(mem::size_of::<read::Sym>() as u64),
+            addr_align: NonZeroU64::new(8),
+            content: bytemuck::cast_slice(&symbols).to_vec(),
+            nobits_size: 0,
+            link: strtab.0.into(),
+            info: first_global
+                .try_into()
+                .map_err(|_| WriteElfError::TooMany("symbols"))?,
+        })
+    }
+
+    pub fn dynamic_section_builder(&self) -> DynamicSectionBuilder {
+        DynamicSectionBuilder {
+            // The null string.
+            strings: vec![0],
+            entries: Vec::new(),
+            relocations: Vec::new(),
+        }
+    }
+
+    /// Adds the `.dynsym`, `.dynstr`, `.rela.dyn` and `.dynamic` sections. They are loaded
+    /// one after another starting at `addr`, so all of them have to be in the same segment.
+    /// `.rela.dyn` is left out if there are no relocations.
+    pub fn finish_dynamic(
+        &mut self,
+        builder: DynamicSectionBuilder,
+        addr: Addr,
+    ) -> Result<DynamicSections> {
+        let DynamicSectionBuilder {
+            strings,
+            mut entries,
+            relocations,
+        } = builder;
+
+        // The dynamic linker needs a symbol table to process relocations, even if nothing
+        // refers to it.
+        let dynsym_name = self.add_sh_string(b".dynsym");
+        let dynsym_size = mem::size_of::<read::Sym>() as u64;
+        let dynsym = self.add_section(Section {
+            name: dynsym_name,
+            r#type: ShType(SHT_DYNSYM),
+            flags: ShFlags::SHF_ALLOC,
+            fixed_entsize: NonZeroU64::new(dynsym_size),
+            addr_align: NonZeroU64::new(8),
+            content: vec![0; dynsym_size as usize],
+            nobits_size: 0,
+            link: 0,
+            // Only the null symbol, which is local.
+            info: 1,
+        })?;
+        self.set_section_addr(dynsym, addr);
+
+        let strsz = strings.len() as u64;
+        let dynstr_addr = addr + dynsym_size;
+        let dynstr_name = self.add_sh_string(b".dynstr");
+        let dynstr = self.add_section(Section {
+            name: dynstr_name,
+            r#type: ShType(SHT_STRTAB),
+            flags: ShFlags::SHF_ALLOC,
+            fixed_entsize: None,
+            addr_align: None,
+            content: strings,
+            nobits_size: 0,
+            link: 0,
+            info: 0,
+        })?;
+        self.set_section_addr(dynstr, dynstr_addr);
+        self.sections[dynsym.usize()].link = dynstr.0.into();
+
+        entries.extend([
+            read::Dyn {
+                tag: DynamicTag(DT_SYMTAB),
+                val: addr.u64(),
+            },
+            read::Dyn {
+                tag: DynamicTag(DT_SYMENT),
+                val: dynsym_size,
+            },
+            read::Dyn {
+                tag: DynamicTag(DT_STRTAB),
+                val: dynstr_addr.u64(),
+            },
+            read::Dyn {
+                tag: DynamicTag(DT_STRSZ),
+                val: strsz,
+            },
+        ]);
+
+        let mut dynamic_addr = Addr(align_up(dynstr_addr.u64() + strsz, 8));
+
+        let rela = if relocations.is_empty() {
+            None
+        } else {
+            let rela_addr = dynamic_addr;
+            let relaent = mem::size_of::<read::Rela>() as u64;
+            let relasz = relocations.len() as u64 * relaent;
+
+            let rela_name = self.add_sh_string(b".rela.dyn");
+            let rela = self.add_section(Section {
+                name: rela_name,
+                r#type: ShType(SHT_RELA),
+                flags: ShFlags::SHF_ALLOC,
+                fixed_entsize: NonZeroU64::new(relaent),
+                addr_align: NonZeroU64::new(8),
+                content: bytemuck::cast_slice(&relocations).to_vec(),
+                nobits_size: 0,
+                link: dynsym.0.into(),
+                info: 0,
+            })?;
+            self.set_section_addr(rela, rela_addr);
+
+            entries.extend([
+                read::Dyn {
+                    tag: DynamicTag(DT_RELA),
+                    val: rela_addr.u64(),
+                },
+                read::Dyn {
+                    tag: DynamicTag(DT_RELASZ),
+                    val: relasz,
+                },
+                read::Dyn {
+                    tag: DynamicTag(DT_RELAENT),
+                    val: relaent,
+                },
+            ]);
+            dynamic_addr = rela_addr + relasz;
+            Some(rela)
+        };
+
+        entries.push(read::Dyn {
+            tag: DynamicTag(DT_NULL),
+            val: 0,
+        });
+
+        let dynamic_name = self.add_sh_string(b".dynamic");
+        let dynamic = self.add_section(Section {
+            name: dynamic_name,
+            r#type: ShType(SHT_DYNAMIC),
+            flags: ShFlags::SHF_ALLOC | ShFlags::SHF_WRITE,
+            fixed_entsize: NonZeroU64::new(mem::size_of::<read::Dyn>() as u64),
+            addr_align: NonZeroU64::new(8),
+            content: bytemuck::cast_slice(&entries).to_vec(),
+            nobits_size: 0,
+            link: dynstr.0.into(),
+            info: 0,
+        })?;
+
+        self.set_section_addr(dynamic, dynamic_addr);
+
+        Ok(DynamicSections {
+            dynsym,
+            dynstr,
+            rela,
+            dynamic,
+            dynamic_addr,
+        })
+    }
+
+    /// Adds an allocated `.note.gnu.build-id` section with a zeroed ID, which is computed
+    /// by [`Self::finalize_build_id`] once the whole file is written. The ID is a hash of the
+    /// output, so it can't be known before.
+    pub fn reserve_build_id(&mut self, algo: BuildIdAlgo) -> Result<SectionIdx> {
+        let mut content = Vec::new();
+        content.extend((BUILD_ID_NOTE_NAME.len() as u32).to_le_bytes());
+        content.extend((algo.id_len() as u32).to_le_bytes());
+        content.extend(NT_GNU_BUILD_ID.to_le_bytes());
+        content.extend(BUILD_ID_NOTE_NAME);
+        // The ID lengths are multiples of 4, so the note needs no padding.
+        content.resize(BUILD_ID_OFFSET + algo.id_len(), 0);
+
+        let name = self.add_sh_string(BUILD_ID_SECTION_NAME);
+        let idx = self.add_section(Section {
+            name,
+            r#type: ShType(SHT_NOTE),
+            flags: ShFlags::SHF_ALLOC,
+            fixed_entsize: None,
+            addr_align: NonZeroU64::new(4),
+            content,
+            nobits_size: 0,
+            link: 0,
+            info: 0,
+        })?;
+        self.build_id = Some(algo);
+        Ok(idx)
+    }
+
+    /// Computes the ID reserved with [`Self::reserve_build_id`] over `output`, the whole file
+    /// written by this writer, and patches it in. The ID itself is zeroed while hashing.
+    /// Does nothing if no ID was reserved.
+    pub fn finalize_build_id(&self, output: &mut [u8]) -> Result<()> {
+        let Some(algo) = self.build_id else {
+            return Ok(());
+        };
+
+        let patch = |output: &mut [u8], id: &[u8]| {
+            ElfPatcher::new(output)?.patch_section_content(
+                BUILD_ID_SECTION_NAME,
+                BUILD_ID_OFFSET,
+                id,
+            )
+        };
+        patch(output, &vec![0; algo.id_len()])?;
+        let id = algo.compute(output)?;
+        patch(output, &id)?;
+        Ok(())
+    }
+
+    /// Adds a program header and returns its index.
+    pub fn add_program_header(&mut self, ph: ProgramHeader) -> usize {
         self.programs_headers.push(ph);
+        self.programs_headers.len() - 1
+    }
+
+    /// Computes `filesz` and `memsz` of the program header from `extent` when writing,
+    /// overriding the values in the header.
+    pub fn set_program_header_extent(&mut self, ph_idx: usize, extent: ProgramHeaderExtent) {
+        self.program_header_extents.push((ph_idx, extent));
+    }
+
+    /// The `PT_PHDR` program header, at the address where the headers are loaded.
+    fn phdr_program_header(&self, layout: &Layout) -> Result<Phdr> {
+        let load = self
+            .programs_headers
+            .iter()
+            .enumerate()
+            .find(|&(i, _)| {
+                matches!(
+                    self.program_header_extent(i),
+                    Some(ProgramHeaderExtent::Headers)
+                )
+            })
+            .map(|(_, load)| load)
+            .ok_or(WriteElfError::UnloadedProgramHeaders)?;
+
+        let load_offset =
+            layout.section_content_offsets[load.offset.section.usize()] + load.offset.rel_offset;
+        let vaddr = load.vaddr + (layout.ph_offset().u64() - load_offset.u64());
+        let size = layout.phs_byte_size() as u64;
+
+        Ok(Phdr {
+            r#type: PhType(PT_PHDR),
+            flags: PhFlags::PF_R,
+            offset: layout.ph_offset(),
+            vaddr,
+            paddr: vaddr,
+            filesz: size,
+            memsz: size,
+            align: mem::align_of::<Phdr>() as u64,
+        })
+    }
+
+    fn program_header_extent(&self, ph_idx: usize) -> Option<ProgramHeaderExtent> {
+        self.program_header_extents
+            .iter()
+            .rev()
+            .find(|(idx, _)| *idx == ph_idx)
+            .map(|&(_, extent)| extent)
     }
 }
 
@@ -146,6 +962,9 @@ struct Layout {
     sh_amount: usize,
     // Section contents
     section_content_offsets: Vec<Offset>,
+    /// The order of the section contents in the file, which is the order of the sections
+    /// except for `SHF_LINK_ORDER` sections.
+    section_content_order: Vec<usize>,
     // happy void
     section_content_end_offset: Offset,
 }
@@ -176,8 +995,9 @@ impl ElfWriter {
     fn layout(&self) -> Layout {
         let mut layout = Layout {
             sh_amount: self.sections.len(),
-            ph_amount: self.programs_headers.len(),
-            section_content_offsets: Vec::new(),
+            ph_amount: self.programs_headers.len() + usize::from(self.phdr_program_header),
+            section_content_offsets: vec![Offset(0); self.sections.len()],
+            section_content_order: self.section_content_order(),
             section_content_end_offset: Offset(0),
         };
 
@@ -186,34 +1006,108 @@ impl ElfWriter {
 
         let mut current_offset = layout.section_contents_offset();
 
-        for section in self.sections.iter() {
-            if section.content.len() == 0 {
-                layout.section_content_offsets.push(Offset(0));
+        let segment_addrs = self.segment_start_addrs();
+
+        for &i in &layout.section_content_order {
+            let section = &self.sections[i];
+            if section.size() == 0 {
                 continue;
             }
 
-            let offset = align_up(
+            let mut offset = align_up(
                 current_offset,
                 section.addr_align.map(NonZeroU64::get).unwrap_or(1),
             );
+            if let (Some(page_size), Some(addr)) = (self.page_size, segment_addrs[i]) {
+                offset = congruent_offset(offset, addr, page_size);
+            }
+
+            // NOBITS sections point to where their content would be but don't take up any space.
+            if section.is_nobits() {
+                layout.section_content_offsets[i] = offset;
+                continue;
+            }
 
             current_offset = offset;
 
-            layout.section_content_offsets.push(offset);
+            layout.section_content_offsets[i] = offset;
 
             current_offset += section.content.len();
         }
 
         debug_assert_eq!(self.sections.len(), layout.section_content_offsets.len());
 
-        layout.section_content_end_offset = *layout.section_content_offsets.last().unwrap()
-            + self.sections.last().unwrap().content.len();
+        layout.section_content_end_offset = current_offset;
 
         layout
     }
 
+    /// The address of every section that starts a `PT_LOAD` program header, by index.
+    fn segment_start_addrs(&self) -> Vec<Option<Addr>> {
+        let mut addrs = vec![None; self.sections.len()];
+
+        for (i, ph) in self.programs_headers.iter().enumerate() {
+            let start = match self.program_header_extent(i) {
+                Some(ProgramHeaderExtent::Sections { start, .. }) => start,
+                Some(ProgramHeaderExtent::Headers) => continue,
+                None => ph.offset.section,
+            };
+            if ph.r#type != PT_LOAD || start.usize() == 0 {
+                continue;
+            }
+            // The address of the section itself, the program header may start inside of it.
+            let rel_offset = if start == ph.offset.section {
+                ph.offset.rel_offset.u64()
+            } else {
+                0
+            };
+            addrs[start.usize()] = Some(Addr(ph.vaddr.u64().wrapping_sub(rel_offset)));
+        }
+
+        addrs
+    }
+
+    /// The sections in the order their contents are written. `SHF_LINK_ORDER` sections swap
+    /// places among each other to be in the same order as the sections in their `sh_link`,
+    /// all other sections stay where they are.
+    fn section_content_order(&self) -> Vec<usize> {
+        let mut order = (0..self.sections.len()).collect::<Vec<_>>();
+
+        let slots = order
+            .iter()
+            .copied()
+            .filter(|&i| self.sections[i].flags.contains(ShFlags::SHF_LINK_ORDER))
+            .collect::<Vec<_>>();
+        let mut link_ordered = slots.clone();
+        link_ordered.sort_by_key(|&i| self.sections[i].link);
+
+        for (slot, i) in slots.into_iter().zip(link_ordered) {
+            order[slot] = i;
+        }
+
+        order
+    }
+
+    /// Writes the file into a buffer. Prefer [`Self::write_to`] for large files.
     pub fn write(&self) -> Result<Vec<u8>> {
         let mut output = Vec::new();
+        self.write_to(&mut output)?;
+        Ok(output)
+    }
+
+    /// Creates the file at `path` and writes into it.
+    pub fn write_to_path(&self, path: &Path) -> Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        self.write_to(&mut file)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    pub fn write_to(&self, sink: &mut impl Write) -> Result<()> {
+        let mut output = CountingWriter {
+            inner: sink,
+            position: 0,
+        };
 
         let mut header = self.header;
 
@@ -223,13 +1117,18 @@ impl ElfWriter {
             .try_into()
             .map_err(|_| WriteElfError::TooMany("sections"))?;
 
-        header.phnum = self
-            .programs_headers
-            .len()
+        let layout = self.layout();
+
+        header.phnum = layout
+            .ph_amount
             .try_into()
             .map_err(|_| WriteElfError::TooMany("program headers"))?;
 
-        let layout = self.layout();
+        let phdr = if self.phdr_program_header {
+            Some(self.phdr_program_header(&layout)?)
+        } else {
+            None
+        };
 
         // ld orderes it ph/sh apparently so we will do the same
 
@@ -241,31 +1140,63 @@ impl ElfWriter {
             header.shoff = layout.sh_offset();
         }
 
-        write_pod(&header, &mut output);
+        write_pod(&header, &mut output)?;
+
+        if let Some(phdr) = &phdr {
+            write_pod(phdr, &mut output)?;
+        }
 
         // We know have a few clues about section offsets, so write the program headers.
-        for program_header in self.programs_headers.iter() {
+        for (i, program_header) in self.programs_headers.iter().enumerate() {
             let rel_offset = program_header.offset;
             let section_content_offset =
                 layout.section_content_offsets[rel_offset.section.0 as usize];
 
             let offset = section_content_offset + rel_offset.rel_offset;
 
+            let (filesz, memsz) = match self.program_header_extent(i) {
+                Some(ProgramHeaderExtent::Sections { start, end }) => {
+                    let start_offset = layout.section_content_offsets[start.usize()];
+                    let mut filesz = 0;
+                    let mut memsz = 0;
+                    for idx in start.usize()..=end.usize() {
+                        let section = &self.sections[idx];
+                        if section.size() == 0 {
+                            continue;
+                        }
+                        // `SHF_LINK_ORDER` sections may have been moved, so the last section
+                        // doesn't necessarily end last.
+                        let section_end =
+                            layout.section_content_offsets[idx] + section.size() - start_offset;
+                        memsz = memsz.max(section_end.u64());
+                        if !section.is_nobits() {
+                            filesz = filesz.max(section_end.u64());
+                        }
+                    }
+                    (filesz, memsz)
+                }
+                Some(ProgramHeaderExtent::Headers) => {
+                    let size = layout.sh_offset().u64();
+                    (size, size)
+                }
+                None => (program_header.filesz, program_header.memsz),
+            };
+
             let ph = Phdr {
                 r#type: program_header.r#type,
                 flags: program_header.flags,
                 offset,
                 vaddr: program_header.vaddr,
                 paddr: program_header.paddr,
-                filesz: program_header.filesz,
-                memsz: program_header.memsz,
+                filesz,
+                memsz,
                 align: program_header.align,
             };
 
-            write_pod(&ph, &mut output);
+            write_pod(&ph, &mut output)?;
         }
 
-        assert_eq!(output.len(), layout.sh_offset().usize());
+        assert_eq!(output.position, layout.sh_offset().usize());
 
         let null_sh = Shdr {
             name: ShStringIdx(0),
@@ -279,7 +1210,7 @@ impl ElfWriter {
             addralign: 0,
             entsize: 0,
         };
-        write_pod(&null_sh, &mut output);
+        write_pod(&null_sh, &mut output)?;
 
         for (i, section) in self.sections.iter().enumerate().skip(1) {
             let offset = layout.section_content_offsets[i];
@@ -287,57 +1218,90 @@ impl ElfWriter {
                 name: section.name,
                 r#type: section.r#type,
                 flags: section.flags,
-                addr: Addr(0),
+                addr: self.section_addrs[i],
                 offset,
-                size: section.content.len() as u64,
-                link: 0,
-                info: 0,
+                size: section.size(),
+                link: section.link,
+                info: section.info,
                 addralign: 0,
                 entsize: section.fixed_entsize.map(NonZeroU64::get).unwrap_or(0),
             };
 
-            write_pod(&header, &mut output);
+            write_pod(&header, &mut output)?;
         }
 
-        assert_eq!(output.len(), layout.section_contents_offset().usize());
-
-        for (i, section) in self.sections.iter().enumerate() {
-            let section_size = section.content.len() as u64;
-            if section_size != 0 {
-                let current_offest = output.len();
-                let supposed_offset = layout.section_content_offsets[i];
-                let pre_padding = supposed_offset - current_offest;
-                for _ in 0..pre_padding.u64() {
-                    output.write_all(&[0u8])?;
-                }
+        assert_eq!(output.position, layout.section_contents_offset().usize());
 
-                output.write_all(&section.content)?;
+        for &i in &layout.section_content_order {
+            let section = &self.sections[i];
+            if section.content.is_empty() {
+                continue;
             }
+
+            let supposed_offset = layout.section_content_offsets[i];
+            let pre_padding = supposed_offset - output.position;
+            io::copy(&mut io::repeat(0).take(pre_padding.u64()), &mut output)?;
+
+            output.write_all(&section.content)?;
         }
 
-        assert_eq!(output.len(), layout.section_content_end_offset.usize());
+        assert_eq!(output.position, layout.section_content_end_offset.usize());
 
         if cfg!(debug_assertions) {
-            for offset in &layout.section_content_offsets {
+            let offsets = self
+                .sections
+                .iter()
+                .zip(&layout.section_content_offsets)
+                .filter(|(section, _)| !section.is_nobits());
+            for (_, offset) in offsets {
                 assert!(
-                    offset.usize() < output.len(),
+                    offset.usize() < output.position,
                     "section offset is out of bounds: {offset:?}"
                 );
             }
         }
 
-        Ok(output)
+        Ok(())
     }
 }
 
-fn write_pod<T: Pod>(data: &T, output: &mut Vec<u8>) {
+/// Keeps track of how much has been written to check the layout.
+struct CountingWriter<'a, W> {
+    inner: &'a mut W,
+    position: usize,
+}
+
+impl<W: Write> Write for CountingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.position += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn write_pod<T: Pod>(data: &T, output: &mut impl Write) -> io::Result<()> {
     let data = std::slice::from_ref(data);
-    write_pod_slice(data, output);
+    write_pod_slice(data, output)
 }
 
-fn write_pod_slice<T: Pod>(data: &[T], output: &mut Vec<u8>) {
-    let data = bytemuck::cast_slice::<T, u8>(data);
-    output.extend(data);
+fn write_pod_slice<T: Pod>(data: &[T], output: &mut impl Write) -> io::Result<()> {
+    output.write_all(bytemuck::cast_slice::<T, u8>(data))
+}
+
+/// The first offset at or after `offset` that is the same as `addr` modulo `page_size`.
+fn congruent_offset(offset: Offset, addr: Addr, page_size: u64) -> Offset {
+    let mask = page_size - 1;
+    let page = offset.u64() & !mask;
+    let congruent = page + (addr.u64() & mask);
+    if congruent < offset.u64() {
+        Offset(congruent + page_size)
+    } else {
+        Offset(congruent)
+    }
 }
 
 /// Align a number `n` to `align`, increasing `n` if needed. `align` must be a power of two.
@@ -362,7 +1326,17 @@ fn align_up<T: Into<u64> + From<u64>>(n: T, align: u64) -> T {
 
 #[cfg(test)]
 mod tests {
-    use super::align_up;
+    use std::{mem, num::NonZeroU64};
+
+    use super::{
+        align_up, BuildIdAlgo, ElfWriter, Header, ProgramHeader, ProgramHeaderExtent, Rela,
+        Section, SectionRelativeAbsoluteAddr, Symbol, WriteElfError,
+    };
+    use crate::{
+        consts::{self as c, PhFlags, SectionIdx, ShFlags, ShType},
+        read::{self, ElfHeader, ElfReader, Phdr, Sym, SymIdx},
+        Addr, Offset,
+    };
 
     #[test]
     fn align_up_correct() {
@@ -376,4 +1350,617 @@ mod tests {
         assert_eq!(align_up(8_u64, 8), 8);
         assert_eq!(align_up(0_u64, 1), 0);
     }
+
+    fn test_writer() -> ElfWriter {
+        ElfWriter::new(Header::x86_64(c::ET_EXEC))
+    }
+
+    #[test]
+    fn nobits_section_takes_no_file_space() {
+        let mut writer = test_writer();
+
+        let data_name = writer.add_sh_string(b".data");
+        writer
+            .add_section(Section {
+                flags: ShFlags::SHF_ALLOC | ShFlags::SHF_WRITE,
+                addr_align: NonZeroU64::new(8),
+                ..Section::progbits(data_name, vec![1; 5])
+            })
+            .unwrap();
+        let bss_name = writer.add_sh_string(b".bss");
+        writer
+            .add_section(Section {
+                name: bss_name,
+                ..Section::nobits(0x1000)
+            })
+            .unwrap();
+
+        let output = writer.write().unwrap();
+        let elf = ElfReader::new(&output).unwrap();
+
+        let data = elf.section_header_by_name(b".data").unwrap();
+        let bss = elf.section_header_by_name(b".bss").unwrap();
+        assert_eq!(bss.r#type, c::SHT_NOBITS);
+        assert_eq!(bss.size, 0x1000);
+        assert_eq!(bss.offset, data.offset + data.size);
+        assert_eq!(output.len(), (data.offset + data.size).usize());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn deserialize_description() {
+        let section: Section = serde_json::from_str(
+            r#"{
+                "name": 1,
+                "type": "SHT_PROGBITS",
+                "flags": 6,
+                "fixed_entsize": null,
+                "addr_align": 16,
+                "content": [144, 195],
+                "nobits_size": 0,
+                "link": 0,
+                "info": 0
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(section.r#type, c::SHT_PROGBITS);
+        assert_eq!(section.flags, ShFlags::SHF_ALLOC | ShFlags::SHF_EXECINSTR);
+        assert_eq!(section.addr_align, NonZeroU64::new(16));
+
+        let ph: ProgramHeader = serde_json::from_str(
+            r#"{
+                "type": 1,
+                "flags": 5,
+                "offset": { "section": "SHN_UNDEF", "rel_offset": "0x10" },
+                "vaddr": "0x400000",
+                "paddr": 4194304,
+                "filesz": 0,
+                "memsz": 0,
+                "align": 4096
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(ph.r#type, c::PT_LOAD);
+        assert_eq!(ph.offset.rel_offset, Offset(0x10));
+        assert_eq!(ph.vaddr, Addr(0x400000));
+        assert_eq!(ph.paddr, Addr(0x400000));
+
+        assert!(serde_json::from_str::<c::ShType>(r#""SHT_NONSENSE""#).is_err());
+    }
+
+    #[test]
+    fn strict_dedup_rejects_duplicate_names() {
+        let mut writer = test_writer();
+
+        let first = writer.add_sh_string(b".text");
+        let second = writer.add_sh_string(b".text");
+        writer
+            .add_section(Section {
+                name: first,
+                ..Section::nobits(0)
+            })
+            .unwrap();
+        // Just a warning by default.
+        writer
+            .add_section(Section {
+                name: second,
+                ..Section::nobits(0)
+            })
+            .unwrap();
+
+        writer.set_strict_dedup(true);
+        assert!(matches!(
+            writer.add_section(Section {
+                name: second,
+                ..Section::nobits(0)
+            }),
+            Err(WriteElfError::DuplicateSectionName(name)) if name == ".text"
+        ));
+    }
+
+    #[test]
+    fn program_header_extents() {
+        let mut writer = test_writer();
+
+        let data_name = writer.add_sh_string(b".data");
+        let data = writer
+            .add_section(Section {
+                flags: ShFlags::SHF_ALLOC | ShFlags::SHF_WRITE,
+                addr_align: NonZeroU64::new(8),
+                ..Section::progbits(data_name, vec![1; 5])
+            })
+            .unwrap();
+        let bss_name = writer.add_sh_string(b".bss");
+        let bss = writer
+            .add_section(Section {
+                name: bss_name,
+                ..Section::nobits(0x1000)
+            })
+            .unwrap();
+
+        let load = |section| ProgramHeader {
+            r#type: c::PhType(c::PT_LOAD),
+            flags: PhFlags::PF_R,
+            offset: SectionRelativeAbsoluteAddr {
+                section,
+                rel_offset: Offset(0),
+            },
+            vaddr: Addr(0),
+            paddr: Addr(0),
+            filesz: 0,
+            memsz: 0,
+            align: 0x1000,
+        };
+        let headers = writer.add_program_header(load(SectionIdx(0)));
+        writer.set_program_header_extent(headers, ProgramHeaderExtent::Headers);
+        let data_bss = writer.add_program_header(load(data));
+        writer.set_program_header_extent(
+            data_bss,
+            ProgramHeaderExtent::Sections {
+                start: data,
+                end: bss,
+            },
+        );
+
+        let output = writer.write().unwrap();
+        let elf = ElfReader::new(&output).unwrap();
+        let phs = elf.program_headers().unwrap();
+
+        let header_size = mem::size_of::<ElfHeader>() + 2 * mem::size_of::<Phdr>();
+        assert_eq!(phs[0].filesz, header_size as u64);
+        assert_eq!(phs[0].memsz, header_size as u64);
+        assert_eq!(phs[1].filesz, 5);
+        assert_eq!(phs[1].memsz, 5 + 0x1000);
+    }
+
+    #[test]
+    fn link_order_sections_follow_their_links() {
+        let mut writer = test_writer();
+
+        let mut add = |name: &[u8], flags, link, byte| {
+            let name = writer.add_sh_string(name);
+            writer
+                .add_section(Section {
+                    flags: ShFlags::SHF_ALLOC | flags,
+                    link,
+                    ..Section::progbits(name, vec![byte; 4])
+                })
+                .unwrap()
+        };
+        let text_a = add(b".text.a", ShFlags::SHF_EXECINSTR, 0, 1);
+        let text_b = add(b".text.b", ShFlags::SHF_EXECINSTR, 0, 2);
+        let order_b = add(b".order.b", ShFlags::SHF_LINK_ORDER, text_b.0.into(), 3);
+        let data = add(b".data", ShFlags::SHF_WRITE, 0, 4);
+        let order_a = add(b".order.a", ShFlags::SHF_LINK_ORDER, text_a.0.into(), 5);
+
+        let output = writer.write().unwrap();
+        let elf = ElfReader::new(&output).unwrap();
+        let offset = |idx| elf.section_header(idx).unwrap().offset;
+
+        // The link order sections swap their places, the other sections stay.
+        assert!(offset(text_a) < offset(text_b));
+        assert!(offset(text_b) < offset(order_a));
+        assert!(offset(order_a) < offset(data));
+        assert!(offset(data) < offset(order_b));
+
+        // The indices and contents stay the same.
+        let order_a = elf.section_header(order_a).unwrap();
+        assert_eq!(elf.sh_string(order_a.name).unwrap(), ".order.a");
+        assert_eq!(elf.section_content(order_a).unwrap(), [5; 4]);
+        let order_b = elf.section_header(order_b).unwrap();
+        assert_eq!(elf.section_content(order_b).unwrap(), [3; 4]);
+    }
+
+    #[test]
+    fn segments_start_on_their_page_offset() {
+        let mut writer = test_writer();
+        writer.set_page_size(0x1000);
+
+        let mut add = |name: &[u8], addr: u64, offset_in_section: u64| {
+            let name = writer.add_sh_string(name);
+            let idx = writer
+                .add_section(Section::progbits(name, vec![0; 0x20]))
+                .unwrap();
+            writer.add_program_header(ProgramHeader {
+                r#type: c::PhType(c::PT_LOAD),
+                flags: PhFlags::PF_R,
+                offset: SectionRelativeAbsoluteAddr {
+                    section: idx,
+                    rel_offset: Offset(offset_in_section),
+                },
+                vaddr: Addr(addr + offset_in_section),
+                paddr: Addr(addr + offset_in_section),
+                filesz: 0x20 - offset_in_section,
+                memsz: 0x20 - offset_in_section,
+                align: 0x1000,
+            });
+            idx
+        };
+        let text = add(b".text", 0x401000, 0);
+        let rodata = add(b".rodata", 0x402010, 0);
+        let data = add(b".data", 0x403000, 0x8);
+
+        let output = writer.write().unwrap();
+        let elf = ElfReader::new(&output).unwrap();
+        let offset = |idx| elf.section_header(idx).unwrap().offset.u64();
+
+        assert_eq!(offset(text) % 0x1000, 0);
+        assert_eq!(offset(rodata) % 0x1000, 0x10);
+        assert_eq!(offset(data) % 0x1000, 0);
+        assert!(offset(text) < offset(rodata) && offset(rodata) < offset(data));
+
+        for ph in elf.program_headers().unwrap() {
+            assert_eq!(ph.offset.u64() % 0x1000, ph.vaddr.u64() % 0x1000);
+        }
+    }
+
+    #[test]
+    fn build_id_is_patched_in() {
+        let mut writer = test_writer();
+        writer.reserve_build_id(BuildIdAlgo::Sha1).unwrap();
+        let mut output = writer.write().unwrap();
+        let zeroed = output.clone();
+        writer.finalize_build_id(&mut output).unwrap();
+
+        let elf = ElfReader::new(&output).unwrap();
+        let id = elf.gnu_build_id().unwrap().expect("no build ID");
+        assert_eq!(id, sha1_smol::Sha1::from(&zeroed).digest().bytes());
+
+        // Finalizing again hashes the zeroed ID, not the previous one.
+        let mut again = output.clone();
+        writer.finalize_build_id(&mut again).unwrap();
+        assert_eq!(again, output);
+
+        let mut writer = test_writer();
+        writer.reserve_build_id(BuildIdAlgo::Md5).unwrap();
+        let mut output = writer.write().unwrap();
+        writer.finalize_build_id(&mut output).unwrap();
+        let elf = ElfReader::new(&output).unwrap();
+        assert_eq!(elf.gnu_build_id().unwrap().unwrap().len(), 16);
+    }
+
+    #[test]
+    fn phdr_program_header() {
+        let mut writer = test_writer();
+        writer.set_phdr_program_header(true);
+        assert!(matches!(
+            writer.write(),
+            Err(WriteElfError::UnloadedProgramHeaders)
+        ));
+
+        let headers = writer.add_program_header(ProgramHeader {
+            r#type: c::PhType(c::PT_LOAD),
+            flags: PhFlags::PF_R,
+            offset: SectionRelativeAbsoluteAddr {
+                section: SectionIdx(0),
+                rel_offset: Offset(0),
+            },
+            vaddr: Addr(0x400000),
+            paddr: Addr(0x400000),
+            filesz: 0,
+            memsz: 0,
+            align: 0x1000,
+        });
+        writer.set_program_header_extent(headers, ProgramHeaderExtent::Headers);
+
+        let output = writer.write().unwrap();
+        let elf = ElfReader::new(&output).unwrap();
+        let phs = elf.program_headers().unwrap();
+        assert_eq!(phs.len(), 2);
+
+        let phdr = &phs[0];
+        let phs_size = 2 * mem::size_of::<Phdr>() as u64;
+        assert_eq!(phdr.r#type, c::PT_PHDR);
+        assert_eq!(phdr.offset, elf.header().unwrap().phoff);
+        assert_eq!(
+            phdr.vaddr,
+            Addr(0x400000 + mem::size_of::<ElfHeader>() as u64)
+        );
+        assert_eq!(phdr.filesz, phs_size);
+        assert_eq!(phdr.memsz, phs_size);
+        assert_eq!(phs[1].r#type, c::PT_LOAD);
+        assert_eq!(phs[1].filesz, mem::size_of::<ElfHeader>() as u64 + phs_size);
+    }
+
+    #[test]
+    fn remove_and_replace_sections() {
+        let mut writer = test_writer();
+
+        let mut add = |name: &[u8]| {
+            let name = writer.add_sh_string(name);
+            writer
+                .add_section(Section::progbits(name, vec![1; 4]))
+                .unwrap()
+        };
+        let debug = add(b".debug_info");
+        let data = add(b".data");
+        let symtab = writer
+            .finish_symbol_table(writer.symbol_table_builder())
+            .unwrap();
+        let ph = writer.add_program_header(ProgramHeader {
+            r#type: c::PhType(c::PT_LOAD),
+            flags: PhFlags::PF_R,
+            offset: SectionRelativeAbsoluteAddr {
+                section: data,
+                rel_offset: Offset(0),
+            },
+            vaddr: Addr(0),
+            paddr: Addr(0),
+            filesz: 0,
+            memsz: 0,
+            align: 0x1000,
+        });
+
+        assert!(matches!(
+            writer.remove_section(SectionIdx(1)),
+            Err(WriteElfError::CannotRemoveSection(".shstrtab"))
+        ));
+        assert!(matches!(
+            writer.remove_section(data),
+            Err(WriteElfError::DanglingProgramHeader(idx)) if idx == ph
+        ));
+        assert!(matches!(
+            writer.remove_section(SectionIdx(symtab.0 - 1)),
+            Err(WriteElfError::SectionInUse(_))
+        ));
+
+        writer.remove_section(debug).unwrap();
+        writer.replace_section_content(SectionIdx(data.0 - 1), vec![2; 8]);
+
+        let output = writer.write().unwrap();
+        let elf = ElfReader::new(&output).unwrap();
+
+        // null, .shstrtab, .data, .strtab, .symtab
+        assert_eq!(elf.section_headers().unwrap().len(), 5);
+        assert!(elf.section_header_by_name(b".debug_info").is_err());
+
+        let data = elf.section_header_by_name(b".data").unwrap();
+        assert_eq!(elf.section_content(data).unwrap(), [2; 8]);
+        assert_eq!(elf.program_headers().unwrap()[0].offset, data.offset);
+
+        let symtab = elf.section_header_by_name(b".symtab").unwrap();
+        let strtab = elf.section_header(SectionIdx(symtab.link as u16)).unwrap();
+        assert_eq!(elf.sh_string(strtab.name).unwrap(), ".strtab");
+    }
+
+    #[test]
+    fn removing_a_section_updates_symbols_and_groups() {
+        let mut writer = test_writer();
+
+        let mut add = |name: &[u8], r#type, content| {
+            let name = writer.add_sh_string(name);
+            writer
+                .add_section(Section {
+                    r#type: ShType(r#type),
+                    ..Section::progbits(name, content)
+                })
+                .unwrap()
+        };
+        let unused = add(b".unused", c::SHT_PROGBITS, vec![0; 4]);
+        let text = add(b".text", c::SHT_PROGBITS, vec![0; 4]);
+        let mut group = 1_u32.to_ne_bytes().to_vec();
+        group.extend(u32::from(text.0).to_ne_bytes());
+        add(b".group", c::SHT_GROUP, group);
+
+        let mut symtab = writer.symbol_table_builder();
+        symtab.add_symbol(Symbol {
+            name: b"_start",
+            r#type: c::SymbolType(c::STT_FUNC),
+            binding: c::SymbolBinding(c::STB_GLOBAL),
+            visibility: c::SymbolVisibility(c::STV_DEFAULT),
+            section: text,
+            value: Addr(0),
+            size: 4,
+        });
+        writer.finish_symbol_table(symtab).unwrap();
+
+        assert!(matches!(
+            writer.remove_section(text),
+            Err(WriteElfError::SectionInUse(idx)) if idx == text
+        ));
+        writer.remove_section(unused).unwrap();
+
+        let output = writer.write().unwrap();
+        let elf = ElfReader::new(&output).unwrap();
+
+        let start = elf.symbol_by_name(b"_start").unwrap();
+        let section = elf.section_header(start.shndx).unwrap();
+        assert_eq!(elf.sh_string(section.name).unwrap(), ".text");
+
+        let group = elf.section_header_by_name(b".group").unwrap();
+        let members = elf.section_content(group).unwrap();
+        let member = u32::from_ne_bytes(members[4..8].try_into().unwrap());
+        assert_eq!(member, u32::from(start.shndx.0));
+    }
+
+    #[test]
+    fn section_addresses() {
+        let mut writer = test_writer();
+
+        let text_name = writer.add_sh_string(b".text");
+        let text = writer
+            .add_section(Section {
+                flags: ShFlags::SHF_ALLOC | ShFlags::SHF_EXECINSTR,
+                ..Section::progbits(text_name, vec![0x90; 16])
+            })
+            .unwrap();
+        writer.set_section_addr(text, Addr(0x401000));
+
+        let output = writer.write().unwrap();
+        let elf = ElfReader::new(&output).unwrap();
+
+        let text = elf.section_header_by_name(b".text").unwrap();
+        assert_eq!(text.addr, Addr(0x401000));
+        let shstrtab = elf.section_header_by_name(b".shstrtab").unwrap();
+        assert_eq!(shstrtab.addr, Addr(0));
+    }
+
+    #[test]
+    fn rela_section_round_trip() {
+        let mut writer = test_writer();
+
+        let text_name = writer.add_sh_string(b".text");
+        let text = writer
+            .add_section(Section {
+                flags: ShFlags::SHF_ALLOC | ShFlags::SHF_EXECINSTR,
+                ..Section::progbits(text_name, vec![0x90; 16])
+            })
+            .unwrap();
+
+        let rela_name = writer.add_sh_string(b".rela.text");
+        let relas = vec![Rela {
+            offset: Addr(4),
+            sym: SymIdx(1),
+            r#type: c::R_X86_64_PC32,
+            addend: -4,
+        }];
+        assert!(matches!(
+            writer.add_rela_section(rela_name, text, relas.clone()),
+            Err(WriteElfError::MissingSection(_))
+        ));
+
+        let symtab_name = writer.add_sh_string(b".symtab");
+        let symtab = writer
+            .add_section(Section {
+                name: symtab_name,
+                r#type: ShType(c::SHT_SYMTAB),
+                flags: ShFlags::empty(),
+                fixed_entsize: NonZeroU64::new(mem::size_of::<Sym>() as u64),
+                addr_align: NonZeroU64::new(8),
+                content: vec![0; 2 * mem::size_of::<Sym>()],
+                nobits_size: 0,
+                link: 0,
+                info: 0,
+            })
+            .unwrap();
+        writer.add_rela_section(rela_name, text, relas).unwrap();
+
+        let output = writer.write().unwrap();
+        let elf = ElfReader::new(&output).unwrap();
+
+        let relas = elf.relas().unwrap().collect::<Vec<_>>();
+        assert_eq!(relas.len(), 1);
+        let (sh, rela) = relas[0];
+        assert_eq!(elf.sh_string(sh.name).unwrap(), ".rela.text");
+        assert_eq!(sh.r#type, c::SHT_RELA);
+        assert_eq!(sh.link, u32::from(symtab.0));
+        assert_eq!(sh.info, u32::from(text.0));
+        assert_eq!(sh.entsize, mem::size_of::<read::Rela>() as u64);
+        assert_eq!(rela.offset, Addr(4));
+        assert_eq!(rela.info.sym(), SymIdx(1));
+        assert_eq!(rela.info.r#type(), c::R_X86_64_PC32);
+        assert_eq!(rela.addend, -4);
+    }
+
+    #[test]
+    fn symbol_table_puts_locals_first() {
+        let mut writer = test_writer();
+
+        let text_name = writer.add_sh_string(b".text");
+        let text = writer
+            .add_section(Section {
+                flags: ShFlags::SHF_ALLOC | ShFlags::SHF_EXECINSTR,
+                ..Section::progbits(text_name, vec![0xc3; 2])
+            })
+            .unwrap();
+
+        let mut symbols = writer.symbol_table_builder();
+        let symbol = |name, binding| Symbol {
+            name,
+            r#type: c::SymbolType(c::STT_FUNC),
+            binding: c::SymbolBinding(binding),
+            visibility: c::SymbolVisibility(c::STV_DEFAULT),
+            section: text,
+            value: Addr(1),
+            size: 1,
+        };
+        symbols.add_symbol(symbol(b"_start", c::STB_GLOBAL));
+        symbols.add_symbol(symbol(b"helper", c::STB_LOCAL));
+        writer.finish_symbol_table(symbols).unwrap();
+
+        let output = writer.write().unwrap();
+        let elf = ElfReader::new(&output).unwrap();
+
+        let names = elf
+            .symbols()
+            .unwrap()
+            .iter()
+            .map(|sym| elf.string(sym.name).unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["", "helper", "_start"]);
+
+        let start = &elf.symbols().unwrap()[2];
+        assert_eq!(start.info.binding(), c::STB_GLOBAL);
+        assert_eq!(start.info.r#type(), c::STT_FUNC);
+        assert_eq!(start.shndx, text);
+        assert_eq!(start.value, Addr(1));
+
+        let symtab = elf.section_header_by_type_first(c::SHT_SYMTAB).unwrap();
+        assert_eq!(symtab.info, 2);
+        let strtab = elf.linked_section(symtab).unwrap();
+        assert_eq!(elf.sh_string(strtab.name).unwrap(), ".strtab");
+    }
+
+    #[test]
+    fn dynamic_section_round_trip() {
+        let mut writer = test_writer();
+
+        let mut dynamic = writer.dynamic_section_builder();
+        dynamic.add_needed(b"libc.so.6");
+        dynamic.set_soname(b"libold.so");
+        dynamic.set_soname(b"libtest.so");
+        dynamic.set_rpath(b"$ORIGIN");
+        dynamic.add_entry(c::DynamicTag(c::DT_FLAGS), 0x8);
+        dynamic.add_relocation(Addr(0x4000), c::R_X86_64_RELATIVE, 0x1000);
+        writer.finish_dynamic(dynamic, Addr(0x3000)).unwrap();
+
+        let output = writer.write().unwrap();
+        let elf = ElfReader::new(&output).unwrap();
+
+        let string_of = |tag| {
+            let entry = elf.dyn_entry_by_tag(tag).unwrap();
+            elf.dyn_string(read::StringIdx(entry.val as u32)).unwrap()
+        };
+        assert_eq!(string_of(c::DT_NEEDED), "libc.so.6");
+        assert_eq!(string_of(c::DT_SONAME), "libtest.so");
+        assert_eq!(string_of(c::DT_RPATH), "$ORIGIN");
+        assert_eq!(elf.dyn_entry_by_tag(c::DT_FLAGS).unwrap().val, 0x8);
+
+        let entries = elf.dyn_entries().unwrap();
+        assert_eq!(entries.len(), 12);
+        assert_eq!(entries.last().unwrap().tag, c::DT_NULL);
+
+        let dynsym = elf.section_header_by_type_first(c::SHT_DYNSYM).unwrap();
+        assert_eq!(dynsym.addr, Addr(0x3000));
+        assert_eq!(
+            elf.dyn_entry_by_tag(c::DT_SYMTAB).unwrap().val,
+            dynsym.addr.u64()
+        );
+
+        let dynamic = elf.section_header_by_name(b".dynamic").unwrap();
+        let dynstr = elf.linked_section(dynamic).unwrap();
+        assert_eq!(elf.sh_string(dynstr.name).unwrap(), ".dynstr");
+        assert_eq!(dynstr.addr, dynsym.addr + dynsym.size);
+        assert_eq!(
+            elf.dyn_entry_by_tag(c::DT_STRTAB).unwrap().val,
+            dynstr.addr.u64()
+        );
+
+        let rela = elf.section_header_by_name(b".rela.dyn").unwrap();
+        assert_eq!(
+            rela.addr,
+            Addr(align_up(dynstr.addr.u64() + dynstr.size, 8))
+        );
+        assert_eq!(
+            elf.dyn_entry_by_tag(c::DT_RELA).unwrap().val,
+            rela.addr.u64()
+        );
+        assert_eq!(elf.dyn_entry_by_tag(c::DT_RELASZ).unwrap().val, rela.size);
+        let (_, relocation) = elf.relas().unwrap().next().unwrap();
+        assert_eq!(relocation.offset, Addr(0x4000));
+        assert_eq!(relocation.info.r#type(), c::R_X86_64_RELATIVE);
+        assert_eq!(relocation.addend, 0x1000);
+
+        assert_eq!(dynamic.addr, rela.addr + rela.size);
+    }
 }
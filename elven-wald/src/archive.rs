@@ -0,0 +1,108 @@
+//! Reading of `ar` archives, which are used for static libraries.
+//!
+//! Only the GNU/SysV variant is supported, where long member names are stored in a `//` member.
+
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use bstr::{BStr, ByteSlice};
+
+pub const MAGIC: &[u8] = b"!<arch>\n";
+
+const HEADER_SIZE: usize = 60;
+const HEADER_END: &[u8] = b"`\n";
+
+#[derive(Debug, Clone, Copy)]
+pub struct Member<'a> {
+    pub name: &'a BStr,
+    pub data: &'a [u8],
+}
+
+/// A copy of a member. Members are only aligned to two bytes in the archive,
+/// but ELF files must be aligned to eight.
+pub struct OwnedMember {
+    pub path: PathBuf,
+    buf: Vec<u64>,
+    len: usize,
+}
+
+impl OwnedMember {
+    pub fn new(path: PathBuf, data: &[u8]) -> Self {
+        let mut buf = vec![0_u64; data.len().div_ceil(8)];
+        bytemuck::cast_slice_mut(&mut buf)[..data.len()].copy_from_slice(data);
+        Self {
+            path,
+            buf,
+            len: data.len(),
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &bytemuck::cast_slice(&self.buf)[..self.len]
+    }
+}
+
+pub fn is_archive(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// All object file members of an archive. The symbol index is skipped, symbols are
+/// looked up in the members directly.
+pub fn members(data: &[u8]) -> Result<Vec<Member<'_>>> {
+    let mut rest = data
+        .strip_prefix(MAGIC)
+        .context("not an archive, missing magic")?;
+    let mut long_names: &[u8] = &[];
+    let mut members = Vec::new();
+
+    while !rest.is_empty() {
+        let header = rest
+            .get(..HEADER_SIZE)
+            .context("truncated archive member header")?;
+        if &header[58..] != HEADER_END {
+            bail!("invalid archive member header");
+        }
+
+        let size = parse_decimal(&header[48..58]).context("invalid archive member size")?;
+        let content = rest
+            .get(HEADER_SIZE..)
+            .and_then(|content| content.get(..size))
+            .context("archive member content is out of bounds")?;
+
+        match header[..16].trim_end() {
+            b"/" | b"/SYM64/" => {}
+            b"//" => long_names = content,
+            name => members.push(Member {
+                name: member_name(name, long_names)?,
+                data: content,
+            }),
+        }
+
+        // Members are aligned to two bytes.
+        let next = (HEADER_SIZE + size).next_multiple_of(2).min(rest.len());
+        rest = &rest[next..];
+    }
+
+    Ok(members)
+}
+
+fn member_name<'a>(name: &'a [u8], long_names: &'a [u8]) -> Result<&'a BStr> {
+    let name = match name.strip_prefix(b"/") {
+        Some(offset) => {
+            let offset = parse_decimal(offset).context("invalid long member name offset")?;
+            let long_name = long_names
+                .get(offset..)
+                .context("long member name offset is out of bounds")?;
+            let end = long_name.find_byte(b'\n').unwrap_or(long_name.len());
+            &long_name[..end]
+        }
+        None => name,
+    };
+
+    // GNU terminates names with a slash so that they can contain spaces.
+    Ok(BStr::new(name.strip_suffix(b"/").unwrap_or(name)))
+}
+
+fn parse_decimal(field: &[u8]) -> Result<usize> {
+    Ok(field.to_str()?.trim().parse()?)
+}
@@ -0,0 +1,405 @@
+//! The call frame information for unwinding. The `.eh_frame` sections of all files are merged
+//! into one, and `.eh_frame_hdr` gets a table to find the FDE of a PC with a binary search.
+//! The unwinder finds the table through the `PT_GNU_EH_FRAME` segment.
+//!
+//! Identical CIEs are only written once. CIEs with relocations, like the pointer to a
+//! personality routine, are always kept, since their bytes don't say what they point to.
+//! FDEs for code that isn't linked, for example because of `--gc-sections`, are dropped.
+
+use std::{collections::HashMap, ops::Range};
+
+use anyhow::{Context, Result};
+use elven_parser::{
+    consts as c,
+    eh_frame::{EhFrameEntry, EhFrameIterator},
+    read::Rela,
+    Addr,
+};
+
+use crate::{storage::SegmentPart, utils::AlignExt, LinkCtxt, SectionId, DEFAULT_PAGE_ALIGN};
+
+/// The encodings of `.eh_frame_hdr`, the same as ld uses.
+const EH_FRAME_PTR_ENCODING: u8 = c::DW_EH_PE_pcrel | c::DW_EH_PE_sdata4;
+const FDE_COUNT_ENCODING: u8 = c::DW_EH_PE_udata4;
+const TABLE_ENCODING: u8 = c::DW_EH_PE_datarel | c::DW_EH_PE_sdata4;
+/// The `0xffffffff` marker and the 8 byte length of records with an extended length.
+const EXTENDED_HEADER_SIZE: usize = 12;
+
+#[derive(Debug)]
+pub struct EhFrame {
+    /// The address of `.eh_frame`.
+    pub addr: Addr,
+    pub content: Vec<u8>,
+    /// The address of `.eh_frame_hdr`, on the page after `.eh_frame`.
+    pub hdr_addr: Addr,
+    pub hdr_content: Vec<u8>,
+}
+
+impl Default for EhFrame {
+    fn default() -> Self {
+        Self {
+            addr: Addr(0),
+            content: Vec::new(),
+            hdr_addr: Addr(0),
+            hdr_content: Vec::new(),
+        }
+    }
+}
+
+impl EhFrame {
+    pub fn is_empty(&self) -> bool {
+        self.content.is_empty()
+    }
+}
+
+/// The `.eh_frame` section of an input file.
+pub struct EhFramePart<'a> {
+    pub id: SectionId,
+    pub data: &'a [u8],
+    /// The relocations of the section, and whether the section they point into is linked.
+    pub relocations: Vec<(Rela, bool)>,
+}
+
+impl EhFramePart<'_> {
+    /// Whether the section of each relocation in the range is linked.
+    fn relocations_in<'r>(&'r self, range: &'r Range<usize>) -> impl Iterator<Item = bool> + 'r {
+        self.relocations
+            .iter()
+            .filter(|(rela, _)| range.contains(&rela.offset.usize()))
+            .map(|&(_, live)| live)
+    }
+}
+
+/// Where a CIE or FDE of an input section ended up in the merged section.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Placement {
+    /// The index of the input section in the parts.
+    pub part: usize,
+    pub input: Range<usize>,
+    pub output: usize,
+}
+
+/// The merged `.eh_frame`, before the relocations of the parts are applied.
+#[derive(Debug)]
+pub struct MergedEhFrame {
+    pub content: Vec<u8>,
+    pub placements: Vec<Placement>,
+}
+
+/// Concatenates the CIEs and FDEs of all parts, without duplicate CIEs and without FDEs that
+/// refer to code that isn't linked, followed by the zero terminator. The CIE pointers of the
+/// FDEs are updated for the new layout, everything else is left for the relocations.
+pub fn merge_eh_frames(parts: &[EhFramePart<'_>]) -> Result<MergedEhFrame> {
+    let mut content = Vec::new();
+    let mut placements = Vec::new();
+    // The output offsets of the CIEs without relocations, by their content.
+    let mut cies = HashMap::<&[u8], usize>::new();
+
+    for (part_idx, part) in parts.iter().enumerate() {
+        // The output offsets of the CIEs of this part, by their input offset.
+        let mut cie_offsets = HashMap::new();
+
+        // Pointers are only decoded for the parser, which doesn't matter for the layout.
+        for entry in EhFrameIterator::new(part.data, Addr(0)) {
+            let (offset, length, cie_offset) = match entry? {
+                EhFrameEntry::Cie(cie) => (cie.offset, cie.length, None),
+                EhFrameEntry::Fde(fde) => (fde.offset, fde.length, Some(fde.cie_offset)),
+            };
+            let (range, header) = record_range(part.data, offset.usize(), length)?;
+            let record = &part.data[range.clone()];
+            let output = content.len();
+
+            match cie_offset {
+                None => {
+                    if part.relocations_in(&range).next().is_none() {
+                        if let Some(&existing) = cies.get(record) {
+                            cie_offsets.insert(offset, existing);
+                            continue;
+                        }
+                        cies.insert(record, output);
+                    }
+                    cie_offsets.insert(offset, output);
+                    content.extend(record);
+                }
+                Some(cie_offset) => {
+                    if part.relocations_in(&range).any(|live| !live) {
+                        continue;
+                    }
+                    let cie_output = *cie_offsets
+                        .get(&cie_offset)
+                        .context("FDE before its CIE in .eh_frame")?;
+
+                    content.extend(record);
+                    // The CIE pointer is the distance from itself back to the CIE. It is 4 bytes,
+                    // even in records with an extended length.
+                    let pointer_pos = output + header;
+                    let pointer = u32::try_from(pointer_pos - cie_output)
+                        .context(".eh_frame is too large")?;
+                    content[pointer_pos..][..4].copy_from_slice(&pointer.to_le_bytes());
+                }
+            }
+
+            placements.push(Placement {
+                part: part_idx,
+                input: range,
+                output,
+            });
+        }
+    }
+
+    // Unwinders read records until the zero terminator.
+    if !content.is_empty() {
+        content.extend([0; 4]);
+    }
+
+    Ok(MergedEhFrame {
+        content,
+        placements,
+    })
+}
+
+/// The bytes of a record including the length, and the size of the length field, which is
+/// followed by the 4 byte CIE ID or pointer.
+fn record_range(data: &[u8], offset: usize, length: u64) -> Result<(Range<usize>, usize)> {
+    let extended = data.get(offset..offset + 4) == Some(&[0xff; 4][..]);
+    let header = if extended { EXTENDED_HEADER_SIZE } else { 4 };
+    let end = usize::try_from(length)
+        .ok()
+        .and_then(|length| (offset + header).checked_add(length))
+        .context(".eh_frame record is too large")?;
+    Ok((offset..end, header))
+}
+
+impl<'a> LinkCtxt<'a> {
+    /// Merges the `.eh_frame` sections of all files onto its own pages after the allocated
+    /// sections, followed by `.eh_frame_hdr`.
+    pub(crate) fn build_eh_frame(&self) -> Result<EhFrame> {
+        let mut parts = Vec::new();
+
+        for file in &self.elves {
+            let elf = &file.elf;
+            for (idx, sh) in elf.section_headers_with_idx()? {
+                let id = SectionId {
+                    file: file.id,
+                    section: idx,
+                };
+                if self.discarded.contains(&id)
+                    || sh.r#type.0 == c::SHT_NOBITS
+                    || elf.sh_string(sh.name)? != ".eh_frame"
+                {
+                    continue;
+                }
+
+                let mut relocations = Vec::new();
+                for (rela_sh, rela) in elf.relas()? {
+                    if rela_sh.info != u32::from(idx.0) {
+                        continue;
+                    }
+                    let live = match self.relocation_target_section(file.id, rela.info.sym())? {
+                        Some(target) => self.storage.section_va(target).is_some(),
+                        None => true,
+                    };
+                    relocations.push((*rela, live));
+                }
+
+                parts.push(EhFramePart {
+                    id,
+                    data: elf.section_content(sh)?,
+                    relocations,
+                });
+            }
+        }
+
+        if parts.is_empty() {
+            return Ok(EhFrame::default());
+        }
+
+        let MergedEhFrame {
+            mut content,
+            placements,
+        } = merge_eh_frames(&parts)?;
+
+        let addr = self
+            .allocated_end()
+            .checked_align_up(DEFAULT_PAGE_ALIGN)
+            .context("no address space left for .eh_frame")?;
+
+        for placement in &placements {
+            let part = &parts[placement.part];
+            let segment_part = SegmentPart {
+                pad_from_prev: 0,
+                base: addr,
+                file: part.id.file,
+                section: part.id.section,
+                size: content.len() as u64,
+            };
+            for (rela, _) in &part.relocations {
+                let offset = rela.offset.usize();
+                if !placement.input.contains(&offset) {
+                    continue;
+                }
+                let rela = Rela {
+                    offset: Addr((placement.output + offset - placement.input.start) as u64),
+                    ..*rela
+                };
+                self.apply_relocation(&segment_part, &rela, &mut content)
+                    .with_context(|| {
+                        format!("applying relocations to .eh_frame of {:?}", part.id.file)
+                    })?;
+            }
+        }
+
+        let hdr_addr = (addr + content.len() as u64)
+            .checked_align_up(DEFAULT_PAGE_ALIGN)
+            .context("no address space left for .eh_frame_hdr")?;
+        let hdr_content = hdr_content(&content, addr, hdr_addr)?;
+
+        Ok(EhFrame {
+            addr,
+            content,
+            hdr_addr,
+            hdr_content,
+        })
+    }
+}
+
+/// The content of `.eh_frame_hdr` for the final `.eh_frame` at `addr`, with the table sorted
+/// by the start of the code of the FDEs.
+fn hdr_content(eh_frame: &[u8], addr: Addr, hdr_addr: Addr) -> Result<Vec<u8>> {
+    let relative = |target: Addr, base: Addr| {
+        let offset = i64::try_from(target.u64())? - i64::try_from(base.u64())?;
+        i32::try_from(offset).context(".eh_frame_hdr is too far away from the code")
+    };
+
+    let mut table = Vec::new();
+    for entry in EhFrameIterator::new(eh_frame, addr) {
+        if let EhFrameEntry::Fde(fde) = entry? {
+            table.push((fde.pc_begin, addr + fde.offset.u64()));
+        }
+    }
+    table.sort();
+
+    let mut content = vec![1, EH_FRAME_PTR_ENCODING, FDE_COUNT_ENCODING, TABLE_ENCODING];
+    // The pointer is relative to itself.
+    content.extend(relative(addr, hdr_addr + 4)?.to_le_bytes());
+    content.extend(u32::try_from(table.len())?.to_le_bytes());
+    for (initial_pc, fde) in table {
+        content.extend(relative(initial_pc, hdr_addr)?.to_le_bytes());
+        content.extend(relative(fde, hdr_addr)?.to_le_bytes());
+    }
+
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use elven_parser::{
+        consts::{self as c, SectionIdx},
+        eh_frame::{EhFrameEntry, EhFrameIterator},
+        read::{RelInfo, Rela, SymIdx},
+        Addr,
+    };
+
+    use super::{merge_eh_frames, EhFramePart};
+    use crate::{FileId, SectionId};
+
+    /// A CIE like the one gas writes, followed by an FDE for 8 bytes of code.
+    const EH_FRAME: [u8; 44] = [
+        // CIE: length, ID, version, "zR", alignments, return address, augmentation data.
+        0x14, 0, 0, 0, 0, 0, 0, 0, 1, b'z', b'R', 0, 0x01, 0x78, 0x10, 0x01, 0x1b,
+        // DW_CFA_def_cfa rsp+8, DW_CFA_offset rip, padding.
+        0x0c, 0x07, 0x08, 0x90, 0x01, 0, 0,
+        // FDE: length, CIE pointer, PC begin, PC range, augmentation data, padding.
+        0x10, 0, 0, 0, 0x1c, 0, 0, 0, 0, 0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0,
+    ];
+
+    /// The same records with an extended length, which doesn't change the size of the CIE
+    /// pointer.
+    const EXTENDED_EH_FRAME: [u8; 60] = [
+        // CIE: extended length, ID, version, "zR".
+        0xff, 0xff, 0xff, 0xff, 0x14, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, b'z', b'R', 0,
+        // Alignments, return address, augmentation data.
+        0x01, 0x78, 0x10, 0x01, 0x1b,
+        // DW_CFA_def_cfa rsp+8, DW_CFA_offset rip, padding.
+        0x0c, 0x07, 0x08, 0x90, 0x01, 0, 0,
+        // FDE: the extended length and the CIE pointer, which is still only 4 bytes.
+        0xff, 0xff, 0xff, 0xff, 0x10, 0, 0, 0, 0, 0, 0, 0, 0x2c, 0, 0, 0,
+        // PC begin, PC range, augmentation data, padding.
+        0, 1, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0,
+    ];
+
+    fn part(file: usize, relocations: Vec<(Rela, bool)>) -> EhFramePart<'static> {
+        EhFramePart {
+            id: SectionId {
+                file: FileId(file),
+                section: SectionIdx(1),
+            },
+            data: &EH_FRAME,
+            relocations,
+        }
+    }
+
+    #[test]
+    fn identical_cies_are_merged() {
+        let merged = merge_eh_frames(&[part(0, Vec::new()), part(1, Vec::new())]).unwrap();
+        assert_eq!(merged.content.len(), EH_FRAME.len() + 20 + 4);
+        assert_eq!(merged.placements.len(), 3);
+        assert_eq!(merged.placements[2].output, EH_FRAME.len());
+
+        let entries = EhFrameIterator::new(&merged.content, Addr(0))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(entries.len(), 3);
+        for entry in &entries[1..] {
+            let EhFrameEntry::Fde(fde) = entry else {
+                panic!("expected an FDE: {entry:?}");
+            };
+            assert_eq!(fde.cie_offset.u64(), 0);
+            assert_eq!(fde.pc_range, 8);
+        }
+    }
+
+    #[test]
+    fn fdes_of_unlinked_code_are_dropped() {
+        let pc_begin = Rela {
+            offset: Addr(0x20),
+            info: RelInfo::new(SymIdx(1), c::R_X86_64_PC32),
+            addend: 0,
+        };
+        let merged = merge_eh_frames(&[
+            part(0, vec![(pc_begin, false)]),
+            part(1, vec![(pc_begin, true)]),
+        ])
+        .unwrap();
+
+        // The CIE of the first part is kept, the FDE of the second one points to it.
+        assert_eq!(merged.content.len(), EH_FRAME.len() + 4);
+        assert_eq!(merged.placements.len(), 2);
+        assert_eq!(merged.placements[1].part, 1);
+        assert_eq!(merged.placements[1].input, 24..44);
+    }
+
+    #[test]
+    fn extended_records_keep_4_byte_cie_pointers() {
+        let extended = EhFramePart {
+            data: &EXTENDED_EH_FRAME,
+            ..part(1, Vec::new())
+        };
+        let merged = merge_eh_frames(&[part(0, Vec::new()), extended]).unwrap();
+        assert_eq!(merged.content.len(), EH_FRAME.len() + 60 + 4);
+        assert!(merged.content.ends_with(&[0; 4]));
+
+        let entries = EhFrameIterator::new(&merged.content, Addr(0))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        // The CIEs differ in their length field, so both are kept.
+        assert_eq!(entries.len(), 4);
+        let EhFrameEntry::Fde(fde) = entries[3] else {
+            panic!("expected an FDE: {:?}", entries[3]);
+        };
+        assert_eq!(fde.cie_offset.usize(), EH_FRAME.len());
+        // PC begin is relative to itself, after the 12 byte length and the CIE pointer.
+        assert_eq!(fde.pc_begin, Addr(fde.offset.u64() + 16 + 0x100));
+        assert_eq!(fde.pc_range, 8);
+    }
+}
@@ -0,0 +1,146 @@
+//! The global offset table. Position independent code loads the addresses of symbols from it
+//! instead of encoding them in instructions. Nothing is linked dynamically, so the slots are
+//! filled with the final addresses at link time. Shared objects are loaded at an unknown
+//! address, so they also get an `R_X86_64_RELATIVE` relocation for every slot that holds
+//! the address of a symbol in the object.
+
+use anyhow::{Context, Result};
+use bstr::BStr;
+use elven_parser::{
+    consts::{self as c, SectionIdx},
+    read::SymIdx,
+    Addr,
+};
+use indexmap::IndexSet;
+
+use crate::{opts::Target, utils::AlignExt, FileId, LinkCtxt, SectionId, DEFAULT_PAGE_ALIGN};
+
+pub const GOT_ENTRY_SIZE: u64 = 8;
+
+/// A symbol with a slot in the GOT. Global symbols share one slot for all files referencing them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GotSymbol<'a> {
+    Global(&'a BStr),
+    Local(FileId, SymIdx),
+}
+
+#[derive(Debug)]
+pub struct Got<'a> {
+    pub addr: Addr,
+    /// The symbol of every slot, in the order they were first referenced.
+    symbols: IndexSet<GotSymbol<'a>>,
+}
+
+impl Default for Got<'_> {
+    fn default() -> Self {
+        Self {
+            addr: Addr(0),
+            symbols: IndexSet::new(),
+        }
+    }
+}
+
+impl<'a> Got<'a> {
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn size(&self) -> u64 {
+        self.symbols.len() as u64 * GOT_ENTRY_SIZE
+    }
+
+    pub fn slot_addr(&self, sym: GotSymbol<'a>) -> Option<Addr> {
+        self.symbols
+            .get_index_of(&sym)
+            .map(|idx| self.addr + idx as u64 * GOT_ENTRY_SIZE)
+    }
+}
+
+/// Whether the relocation needs a GOT slot for its symbol. The relaxable variants could be
+/// turned into direct accesses, but going through the GOT is always correct.
+fn is_got_relocation(target: Target, r#type: u32) -> bool {
+    target == Target::X86_64
+        && matches!(
+            r#type,
+            c::R_X86_64_GOTPCREL | c::R_X86_64_GOTPCRELX | c::R_X86_64_REX_GOTPCRELX
+        )
+}
+
+impl<'a> LinkCtxt<'a> {
+    /// Collects the symbols referenced through the GOT from all live sections.
+    /// The GOT is placed on its own page after the allocated sections.
+    pub(crate) fn build_got(&self) -> Result<Got<'a>> {
+        let mut symbols = IndexSet::new();
+
+        for file in &self.elves {
+            for (rela_sh, rela) in file.elf.relas()? {
+                let target = SectionId {
+                    file: file.id,
+                    section: SectionIdx(rela_sh.info.try_into()?),
+                };
+                if !self.live.contains(&target)
+                    || !is_got_relocation(self.target, rela.info.r#type())
+                {
+                    continue;
+                }
+
+                symbols.insert(self.got_symbol(file.id, rela.info.sym())?);
+            }
+        }
+
+        let addr = self
+            .allocated_end()
+            .checked_align_up(DEFAULT_PAGE_ALIGN)
+            .context("no address space left for .got")?;
+
+        Ok(Got { addr, symbols })
+    }
+
+    pub(crate) fn got_symbol(&self, file: FileId, idx: SymIdx) -> Result<GotSymbol<'a>> {
+        let elf = &self.elves[file.0].elf;
+        let sym = elf.symbol(idx)?;
+
+        if sym.info.binding() == c::STB_LOCAL {
+            Ok(GotSymbol::Local(file, idx))
+        } else {
+            Ok(GotSymbol::Global(elf.string(sym.name)?))
+        }
+    }
+
+    /// The content of `.got`, the address of the symbol of every slot.
+    pub(crate) fn got_content(&self) -> Result<Vec<u8>> {
+        let mut content = Vec::new();
+
+        for &sym in &self.got.symbols {
+            content.extend(self.got_symbol_addr(sym)?.u64().to_le_bytes());
+        }
+
+        Ok(content)
+    }
+
+    /// The slots of a shared object that contain a link time address, with that address.
+    /// The dynamic linker has to add the load address to them.
+    pub(crate) fn got_relative_relocations(&self) -> Result<Vec<(Addr, Addr)>> {
+        let mut relocations = Vec::new();
+
+        for (idx, &sym) in self.got.symbols.iter().enumerate() {
+            let is_relative = match sym {
+                GotSymbol::Global(name) => self.is_relative_global_symbol(name),
+                GotSymbol::Local(file, idx) => self.is_relative_symbol(file, idx)?,
+            };
+            if is_relative {
+                let slot = self.got.addr + idx as u64 * GOT_ENTRY_SIZE;
+                relocations.push((slot, self.got_symbol_addr(sym)?));
+            }
+        }
+
+        Ok(relocations)
+    }
+
+    fn got_symbol_addr(&self, sym: GotSymbol<'a>) -> Result<Addr> {
+        match sym {
+            GotSymbol::Global(name) => self.global_symbol_addr(name),
+            GotSymbol::Local(file, idx) => self.relocation_symbol_addr(file, idx),
+        }
+    }
+}
@@ -1,46 +1,64 @@
+mod archive;
+mod eh_frame;
+mod got;
 pub mod opts;
+mod plt;
 mod storage;
 mod utils;
+mod version_script;
 
 #[macro_use]
 extern crate tracing;
 
 use anyhow::{bail, Context, Result};
 use bstr::BStr;
-use clap::Parser;
+use eh_frame::EhFrame;
 use elven_parser::{
-    consts::{self as c, PhFlags, SectionIdx, ShFlags, ShType, PT_LOAD, SHN_UNDEF, SHT_PROGBITS},
-    read::{ElfIdent, ElfReader},
-    write::{self, ElfWriter, ProgramHeader, Section, SectionRelativeAbsoluteAddr},
+    consts::{
+        self as c, PhFlags, SectionIdx, ShFlags, SymbolBinding, SymbolType, SymbolVisibility,
+        PT_DYNAMIC, PT_GNU_EH_FRAME, PT_GNU_STACK, PT_INTERP, PT_LOAD, PT_NOTE, SHN_UNDEF,
+        SHT_NOBITS,
+    },
+    read::{ElfIdent, ElfReader, ElfWarning, Rela, SymIdx},
+    write::{
+        self, DynamicSections, ElfWriter, ProgramHeader, ProgramHeaderExtent, Section,
+        SectionRelativeAbsoluteAddr,
+    },
     Addr, Offset,
 };
+use got::Got;
+use indexmap::{map::Entry, IndexMap};
 use memmap2::Mmap;
+use opts::{InputFile, Opts, Target};
+use plt::Plt;
 use std::{
     cell::RefCell,
-    collections::{hash_map::Entry, HashMap},
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet, HashSet},
     fmt::Debug,
     fs::{self, File},
+    hash::{Hash, Hasher},
     io::{BufWriter, Write},
-    iter,
     num::NonZeroU64,
     path::{Path, PathBuf},
 };
-use storage::StorageAllocation;
+use storage::{AllocatedSection, SegmentPart, StorageAllocation};
+use utils::AlignExt;
+use version_script::VersionScript;
 
 thread_local! {
-    static ELF_PATHS: RefCell<Vec<PathBuf>> = RefCell::new(Vec::new());
+    static ELF_PATHS: RefCell<Vec<PathBuf>> = const { RefCell::new(Vec::new()) };
 }
 
-#[derive(Debug, Clone, Parser)]
-pub struct Opts {
-    #[clap(long, short, default_value = "a.out")]
-    pub output: PathBuf,
-    pub objs: Vec<PathBuf>,
-}
-
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 struct FileId(usize);
 
+impl FileId {
+    fn path(self) -> PathBuf {
+        ELF_PATHS.with(|p| p.borrow()[self.0].clone())
+    }
+}
+
 impl Debug for FileId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         ELF_PATHS.with(|p| {
@@ -57,14 +75,34 @@ struct SectionId {
     section: SectionIdx,
 }
 
+impl Hash for SectionId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.file.hash(state);
+        self.section.0.hash(state);
+    }
+}
+
+impl PartialOrd for SectionId {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SectionId {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.file, self.section.0).cmp(&(other.file, other.section.0))
+    }
+}
+
 struct ElfFile<'a> {
     id: FileId,
     elf: ElfReader<'a>,
 }
 
 #[derive(Debug)]
-struct Symbol<'a> {
-    name: &'a BStr,
+struct Symbol {
+    /// The binding of the definition, or of the first reference while undefined.
+    binding: SymbolBinding,
     definition: Option<SymbolDefinition>,
 }
 
@@ -73,19 +111,60 @@ struct SymbolDefinition {
     location: SectionId,
     value: Addr,
     size: u64,
+    r#type: SymbolType,
 }
 
 struct LinkCtxt<'a> {
     elves: Vec<ElfFile<'a>>,
-    sym_defs: HashMap<&'a BStr, Symbol<'a>>,
+    /// Archive members that have not been pulled into the link (yet).
+    archive_members: Vec<ArchiveMember<'a>>,
+    /// In the order the symbols are first seen, to keep the output reproducible.
+    sym_defs: IndexMap<&'a BStr, Symbol>,
+    /// The named `STB_LOCAL` symbols of every file, indexed by [`FileId`].
+    /// They are only visible within their file and never collide with each other.
+    local_syms: Vec<IndexMap<&'a BStr, SymbolDefinition>>,
+    comdat_signatures: HashSet<&'a BStr>,
+    /// Sections of COMDAT groups that were already provided by an earlier file.
+    discarded: HashSet<SectionId>,
+    /// The input sections that end up in the output.
+    live: BTreeSet<SectionId>,
     storage: StorageAllocation,
+    got: Got<'a>,
+    plt: Plt<'a>,
+    eh_frame: EhFrame,
+    /// Whether undefined symbols are allowed and resolve to 0.
+    allow_undefined: bool,
+    /// Whether the output is a shared object, which is loaded at an unknown address.
+    shared: bool,
+    /// Hides the global symbols it doesn't export.
+    version_script: Option<VersionScript>,
+    target: Target,
+}
+
+struct ArchiveMember<'a> {
+    path: PathBuf,
+    elf: ElfReader<'a>,
 }
 
-pub fn run(opts: Opts) -> Result<()> {
-    ELF_PATHS.set(opts.objs.clone());
+/// Links the input files, which are object files and `ar` archives,
+/// recognized by their magic.
+pub fn run(opts: Opts, inputs: Vec<InputFile>) -> Result<()> {
+    let target = opts.target()?;
+    let version_script = opts
+        .version_script
+        .as_deref()
+        .map(VersionScript::from_file)
+        .transpose()?;
+
+    let mut objs = inputs
+        .into_iter()
+        .map(|input| input.name)
+        .collect::<Vec<_>>();
+    for name in &opts.libraries {
+        objs.push(find_library(&opts, name)?);
+    }
 
-    let mmaps = opts
-        .objs
+    let mmaps = objs
         .iter()
         .map(|path| {
             let file =
@@ -96,141 +175,1263 @@ pub fn run(opts: Opts) -> Result<()> {
         })
         .collect::<Result<Vec<_>, anyhow::Error>>()?;
 
-    if opts.objs.len() == 0 {
+    if objs.is_empty() {
         bail!("you gotta supply at least one object file");
     }
 
-    info!(objs=?opts.objs, "Linking files");
+    info!(?objs, "Linking files");
 
-    let elves = mmaps
-        .iter()
-        .zip(&opts.objs)
-        .enumerate()
-        .map(|(idx, (mmap, path))| {
-            Ok(ElfFile {
-                id: FileId(idx),
-                elf: ElfReader::new(mmap)
-                    .with_context(|| format!("parsing ELF file {}", path.display()))?,
-            })
-        })
-        .collect::<Result<Vec<_>, anyhow::Error>>()?;
+    let mut objects = Vec::new();
+    let mut members = Vec::new();
+    for (mmap, path) in mmaps.iter().zip(&objs) {
+        if archive::is_archive(mmap) {
+            let archive_members = archive::members(mmap)
+                .with_context(|| format!("reading archive {}", path.display()))?;
 
-    let storage =
-        storage::allocate_storage(BASE_EXEC_ADDR, &elves).context("while allocating storage")?;
+            members.extend(archive_members.into_iter().map(|member| {
+                let path = PathBuf::from(format!("{}({})", path.display(), member.name));
+                archive::OwnedMember::new(path, member.data)
+            }));
+        } else {
+            objects.push((path, &mmap[..]));
+        }
+    }
 
     let mut cx = LinkCtxt {
-        elves,
-        sym_defs: HashMap::new(),
-        storage,
+        elves: Vec::new(),
+        archive_members: Vec::new(),
+        sym_defs: IndexMap::new(),
+        local_syms: Vec::new(),
+        comdat_signatures: HashSet::new(),
+        discarded: HashSet::new(),
+        live: BTreeSet::new(),
+        storage: StorageAllocation::default(),
+        got: Got::default(),
+        plt: Plt::default(),
+        eh_frame: EhFrame::default(),
+        allow_undefined: allow_undefined(&opts),
+        shared: opts.shared,
+        version_script,
+        target,
     };
 
-    dbg!(&cx.storage);
+    for (path, data) in objects {
+        let elf =
+            ElfReader::new(data).with_context(|| format!("parsing ELF file {}", path.display()))?;
+        check_machine(&elf, target, path)?;
+        warn_inconsistent_headers(&elf, path)?;
+        cx.add_file(path.clone(), elf);
+    }
+
+    for member in &members {
+        let elf = ElfReader::new(member.data())
+            .with_context(|| format!("parsing ELF file {}", member.path.display()))?;
+        check_machine(&elf, target, &member.path)?;
+        warn_inconsistent_headers(&elf, &member.path)?;
+        cx.archive_members.push(ArchiveMember {
+            path: member.path.clone(),
+            elf,
+        });
+    }
 
     cx.sym_first_pass()?;
 
-    let mut writer = create_elf();
+    debug!(sym_defs = ?cx.sym_defs, "Resolved symbols");
+    debug!(discarded = ?cx.discarded, "Discarded duplicate COMDAT sections");
 
-    for section in &cx.storage.sections {
-        let exec = if section.name == b"text".as_slice() {
-            ShFlags::SHF_EXECINSTR
-        } else {
-            ShFlags::empty()
-        };
-        let mut content = Vec::new();
+    cx.live = if opts.gc_sections {
+        cx.reachable_sections(&opts)?
+    } else {
+        cx.all_sections()?
+    };
 
-        for part in &section.parts {
-            let elf = cx.elves[part.file.0].elf;
-            let shdr = elf.section_header_by_name(&section.name)?;
-            let data = elf.section_content(shdr)?;
-            content.extend(iter::repeat(0).take(part.pad_from_prev.try_into().unwrap()));
-            content.extend(data);
-            // TODO: relocations here
-        }
+    // The first page is taken up by the ELF header and the program headers.
+    cx.storage =
+        storage::allocate_storage(base_addr(&opts) + DEFAULT_PAGE_ALIGN, &cx.elves, &cx.live)
+            .context("while allocating storage")?;
+
+    debug!(storage = ?cx.storage, "Allocated storage");
 
-        let name = writer.add_sh_string(&section.name);
-        writer.add_section(Section {
-            name,
-            r#type: ShType(SHT_PROGBITS),
-            flags: ShFlags::SHF_ALLOC | exec,
-            fixed_entsize: None,
-            addr_align: NonZeroU64::new(
-                section
-                    .parts
-                    .first()
-                    .map(|p| p.align)
-                    .unwrap_or(DEFAULT_PAGE_ALIGN),
-            ),
-            content,
-        })?;
+    cx.define_array_bounds();
+
+    if !cx.allow_undefined {
+        cx.check_undefined_symbols()?;
     }
 
-    write_elf_to_file(writer, &opts.output)?;
+    cx.got = cx.build_got()?;
+    cx.plt = cx.build_plt(opts.shared)?;
 
-    dbg!(cx.sym_defs);
+    debug!(got = ?cx.got, plt = ?cx.plt, "Built the global offset table and PLT");
 
-    let text_sh = cx.elves[0].elf.section_header_by_name(b".text")?;
-    let text_content = cx.elves[0].elf.section_content(text_sh)?;
+    cx.eh_frame = cx.build_eh_frame().context("while merging .eh_frame")?;
 
-    let _start_sym = cx.elves[0].elf.symbol_by_name(b"_start")?;
+    cx.write_output(&opts)?;
 
-    write_output(&opts, text_content, _start_sym.value)?;
+    if let Some(map) = &opts.map {
+        cx.write_map(Path::new(map))
+            .with_context(|| format!("writing linker map {map}"))?;
+    }
 
     Ok(())
 }
 
+fn entry_symbol(opts: &Opts) -> &BStr {
+    BStr::new(opts.entry.as_deref().unwrap_or("_start"))
+}
+
+/// Like ld, shared objects may have undefined symbols unless `--no-undefined` is passed,
+/// since they could be provided by another object at runtime.
+fn allow_undefined(opts: &Opts) -> bool {
+    opts.allow_undefined || (opts.shared && !opts.no_undefined)
+}
+
+fn base_addr(opts: &Opts) -> Addr {
+    if opts.shared {
+        BASE_SHARED_ADDR
+    } else {
+        BASE_EXEC_ADDR
+    }
+}
+
 pub const BASE_EXEC_ADDR: Addr = Addr(0x400000); // whatever ld does
+/// Shared objects are position independent and get relocated by the dynamic linker.
+pub const BASE_SHARED_ADDR: Addr = Addr(0);
 pub const DEFAULT_PAGE_ALIGN: u64 = 0x1000;
 
 impl<'a> LinkCtxt<'a> {
+    fn add_file(&mut self, path: PathBuf, elf: ElfReader<'a>) -> FileId {
+        ELF_PATHS.with(|paths| paths.borrow_mut().push(path));
+
+        let id = FileId(self.elves.len());
+        self.elves.push(ElfFile { id, elf });
+        self.local_syms.push(IndexMap::new());
+        id
+    }
+
     fn sym_first_pass(&mut self) -> Result<()> {
-        for (elf_idx, elf) in self.elves.iter().enumerate() {
-            for e_sym in elf.elf.symbols()? {
-                let ty = e_sym.info.r#type();
+        for idx in 0..self.elves.len() {
+            self.resolve_file_symbols(FileId(idx))?;
+        }
 
-                if ty.0 == c::STT_SECTION {
-                    continue;
+        // Archive members are only linked if they define a symbol that is still undefined.
+        // Pulling in a member can introduce new undefined symbols, so repeat until nothing changes.
+        loop {
+            let mut pulled = None;
+            for (idx, member) in self.archive_members.iter().enumerate() {
+                if self.defines_undefined_symbol(&member.elf)? {
+                    pulled = Some(idx);
+                    break;
                 }
+            }
 
-                let name = elf.elf.string(e_sym.name)?;
+            let Some(idx) = pulled else {
+                return Ok(());
+            };
 
-                let definition = if e_sym.shndx == SHN_UNDEF {
-                    None
-                } else {
-                    Some(SymbolDefinition {
-                        location: SectionId {
-                            file: FileId(elf_idx),
-                            section: e_sym.shndx,
-                        },
-                        value: e_sym.value,
-                        size: e_sym.size,
-                    })
-                };
+            let member = self.archive_members.remove(idx);
+            debug!(path = %member.path.display(), "Pulling in archive member");
+
+            let id = self.add_file(member.path, member.elf);
+            self.resolve_file_symbols(id)?;
+        }
+    }
+
+    fn defines_undefined_symbol(&self, elf: &ElfReader<'a>) -> Result<bool> {
+        for e_sym in elf.symbols()? {
+            if e_sym.shndx == SHN_UNDEF || e_sym.info.binding() == c::STB_LOCAL {
+                continue;
+            }
+
+            let name = elf.string(e_sym.name)?;
+            if self
+                .sym_defs
+                .get(name)
+                .is_some_and(|sym| sym.definition.is_none())
+            {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    fn resolve_file_symbols(&mut self, file: FileId) -> Result<()> {
+        let elf = self.elves[file.0].elf.clone();
 
-                match self.sym_defs.entry(name) {
-                    Entry::Occupied(mut entry) => {
-                        match (&mut entry.get_mut().definition, definition) {
-                            (Some(_), Some(_)) => bail!("duplicate definition for symbol {name}"),
-                            (new @ None, def @ Some(_)) => *new = def,
-                            (Some(_), None) => {}
-                            (None, None) => {}
+        self.discard_duplicate_comdat_groups(file)?;
+
+        for e_sym in elf.symbols()? {
+            let ty = e_sym.info.r#type();
+
+            if ty.0 == c::STT_SECTION {
+                continue;
+            }
+
+            let name = elf.string(e_sym.name)?;
+            let binding = e_sym.info.binding();
+
+            let location = SectionId {
+                file,
+                section: e_sym.shndx,
+            };
+
+            // Symbols in discarded COMDAT sections are defined by the kept copy.
+            let definition = if e_sym.shndx == SHN_UNDEF || self.discarded.contains(&location) {
+                None
+            } else {
+                Some(SymbolDefinition {
+                    location,
+                    value: e_sym.value,
+                    size: e_sym.size,
+                    r#type: ty,
+                })
+            };
+
+            if binding == c::STB_LOCAL {
+                // Relocations refer to local symbols by index, so the names are only for
+                // the map. Of several locals with the same name, the first one is listed.
+                if let Some(definition) = definition {
+                    if !name.is_empty() && ty.0 != c::STT_FILE {
+                        self.local_syms[file.0].entry(name).or_insert(definition);
+                    }
+                }
+                continue;
+            }
+
+            match self.sym_defs.entry(name) {
+                Entry::Occupied(mut entry) => {
+                    let sym = entry.get_mut();
+                    match (&sym.definition, definition) {
+                        (_, None) => {}
+                        (None, def @ Some(_)) => {
+                            sym.definition = def;
+                            sym.binding = binding;
                         }
+                        // Strong definitions win over weak ones, of two weak ones the first wins.
+                        (Some(old), Some(def)) => match (sym.binding.0, binding.0) {
+                            (_, c::STB_WEAK) => {
+                                debug!(
+                                    %name,
+                                    kept = ?old.location.file,
+                                    ignored = ?file,
+                                    "Ignoring weak redefinition"
+                                );
+                            }
+                            (c::STB_WEAK, _) => {
+                                debug!(
+                                    %name,
+                                    replaced = ?old.location.file,
+                                    by = ?file,
+                                    "Overriding weak definition"
+                                );
+                                sym.definition = Some(def);
+                                sym.binding = binding;
+                            }
+                            _ => bail!(
+                                "duplicate definition for symbol {name}: \
+                                 first defined in {:?}, redefined in {file:?}",
+                                old.location.file
+                            ),
+                        },
                     }
-                    Entry::Vacant(entry) => {
-                        entry.insert(Symbol { name, definition });
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(Symbol {
+                        binding,
+                        definition,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn all_sections(&self) -> Result<BTreeSet<SectionId>> {
+        let mut sections = BTreeSet::new();
+        for elf in &self.elves {
+            for (idx, _) in elf.elf.section_headers_with_idx()? {
+                let id = SectionId {
+                    file: elf.id,
+                    section: idx,
+                };
+                if !self.discarded.contains(&id) {
+                    sections.insert(id);
+                }
+            }
+        }
+        Ok(sections)
+    }
+
+    /// Marks all sections that are transitively referenced by relocations,
+    /// starting from the section containing the entry point and the init and fini arrays,
+    /// which are never referenced but still run.
+    /// Shared objects don't need an entry point, but keep all global definitions.
+    fn reachable_sections(&self, opts: &Opts) -> Result<BTreeSet<SectionId>> {
+        let entry_name = entry_symbol(opts);
+        let entry = self
+            .sym_defs
+            .get(entry_name)
+            .and_then(|sym| sym.definition.as_ref());
+
+        let mut live = BTreeSet::new();
+        let mut worklist = Vec::new();
+
+        if opts.shared {
+            worklist.extend(
+                self.sym_defs
+                    .values()
+                    .filter_map(|sym| sym.definition.as_ref())
+                    .filter(|def| def.location.section.0 < c::SHN_LORESERVE)
+                    .map(|def| def.location),
+            );
+        } else {
+            let entry =
+                entry.with_context(|| format!("entry symbol {entry_name} is not defined"))?;
+            worklist.push(entry.location);
+        }
+
+        for elf in &self.elves {
+            for (idx, sh) in elf.elf.section_headers_with_idx()? {
+                if matches!(
+                    sh.r#type.0,
+                    c::SHT_INIT_ARRAY | c::SHT_FINI_ARRAY | c::SHT_PREINIT_ARRAY
+                ) {
+                    worklist.push(SectionId {
+                        file: elf.id,
+                        section: idx,
+                    });
+                }
+            }
+        }
+
+        while let Some(section) = worklist.pop() {
+            if self.discarded.contains(&section) || !live.insert(section) {
+                continue;
+            }
+
+            let elf = &self.elves[section.file.0].elf;
+            for (rela_sh, rela) in elf.relas()? {
+                if rela_sh.info != u32::from(section.section.0) {
+                    continue;
+                }
+
+                if let Some(target) =
+                    self.relocation_target_section(section.file, rela.info.sym())?
+                {
+                    worklist.push(target);
+                }
+            }
+        }
+
+        debug!(?live, "Marked live sections");
+
+        Ok(live)
+    }
+
+    /// Defines `__init_array_start` and friends if they are referenced but not defined,
+    /// which startup code uses to run the functions in the arrays.
+    fn define_array_bounds(&mut self) {
+        for section in &self.storage.sections {
+            let Some(array) = section.name.strip_prefix(b".") else {
+                continue;
+            };
+            if !array.ends_with(b"_array") {
+                continue;
+            }
+
+            let bounds = [
+                (format!("__{}_start", BStr::new(array)), section.addr),
+                (
+                    format!("__{}_end", BStr::new(array)),
+                    section.addr + section.size,
+                ),
+            ];
+            for (name, addr) in bounds {
+                let Some(sym) = self.sym_defs.get_mut(BStr::new(&name)) else {
+                    continue;
+                };
+                if sym.definition.is_some() {
+                    continue;
+                }
+
+                debug!(%name, %addr, "Defining array bound");
+                sym.definition = Some(SymbolDefinition {
+                    // Linker defined symbols don't come from any file.
+                    // They are absolute, so which one doesn't matter.
+                    location: SectionId {
+                        file: FileId(0),
+                        section: SectionIdx(c::SHN_ABS),
+                    },
+                    value: addr,
+                    size: 0,
+                    r#type: SymbolType(c::STT_NOTYPE),
+                });
+            }
+        }
+    }
+
+    /// Reports all symbols that are referenced by relocations in live sections but never defined,
+    /// together with the files referencing them, instead of stopping at the first one.
+    /// Undefined weak symbols are fine and resolve to 0.
+    fn check_undefined_symbols(&self) -> Result<()> {
+        let mut undefined = BTreeMap::<&BStr, BTreeSet<FileId>>::new();
+
+        for file in &self.elves {
+            let elf = &file.elf;
+            for (rela_sh, rela) in elf.relas()? {
+                let section = SectionId {
+                    file: file.id,
+                    section: SectionIdx(rela_sh.info.try_into()?),
+                };
+                if !self.live.contains(&section) {
+                    continue;
+                }
+
+                let sym = elf.symbol(rela.info.sym())?;
+                if sym.shndx != SHN_UNDEF {
+                    continue;
+                }
+                let name = elf.string(sym.name)?;
+                let is_undefined = self
+                    .sym_defs
+                    .get(name)
+                    .is_none_or(|sym| sym.definition.is_none() && sym.binding != c::STB_WEAK);
+                if is_undefined {
+                    undefined.entry(name).or_default().insert(file.id);
+                }
+            }
+        }
+
+        if undefined.is_empty() {
+            return Ok(());
+        }
+
+        let mut msg = String::from("undefined symbols:");
+        for (name, files) in undefined {
+            for file in files {
+                error!(%name, referenced_by = ?file, "Undefined symbol");
+                msg.push_str(&format!("\n  {name}, referenced by {file:?}"));
+            }
+        }
+        bail!(msg)
+    }
+
+    /// The section containing the symbol that a relocation refers to.
+    fn relocation_target_section(&self, file: FileId, idx: SymIdx) -> Result<Option<SectionId>> {
+        let elf = &self.elves[file.0].elf;
+        let sym = elf.symbol(idx)?;
+
+        let location = if sym.shndx == SHN_UNDEF {
+            let name = elf.string(sym.name)?;
+            let Some(def) = self
+                .sym_defs
+                .get(name)
+                .and_then(|sym| sym.definition.as_ref())
+            else {
+                return Ok(None);
+            };
+            def.location
+        } else {
+            SectionId {
+                file,
+                section: sym.shndx,
+            }
+        };
+
+        Ok(match location.section.0 {
+            c::SHN_ABS | c::SHN_COMMON => None,
+            _ => Some(location),
+        })
+    }
+
+    /// Only the first occurrence of a COMDAT group is kept, the members of all
+    /// later groups with the same signature are discarded.
+    fn discard_duplicate_comdat_groups(&mut self, file: FileId) -> Result<()> {
+        let elf = &self.elves[file.0].elf;
+
+        for group in elf.section_groups()? {
+            if !group.is_comdat() || self.comdat_signatures.insert(group.signature) {
+                continue;
+            }
+
+            self.discarded.extend(
+                group
+                    .members
+                    .iter()
+                    .map(|&section| SectionId { file, section }),
+            );
+        }
+
+        Ok(())
+    }
+
+    fn write_output(&self, opts: &Opts) -> Result<()> {
+        let r#type = if opts.shared { c::ET_DYN } else { c::ET_EXEC };
+        let mut writer = create_elf(r#type, self.target);
+        writer.set_page_size(DEFAULT_PAGE_ALIGN);
+        let mut program_headers = Vec::new();
+        let base_addr = base_addr(opts);
+        let mut end_addr = base_addr;
+        // The index of each allocated section in the output, if it is written.
+        let mut output_sections = Vec::new();
+
+        for section in &self.storage.sections {
+            // Truly empty, as opposed to zero-initialized sections that still need memory.
+            if section.size == 0 {
+                output_sections.push(None);
+                continue;
+            }
+
+            let flags = section.flags;
+            let name = writer.add_sh_string(&section.name);
+
+            // The storage allocation puts every section on its own page. Every section starts
+            // its own segment as well, which the writer places on the same page offset in the
+            // file, so the sections don't need to be aligned themselves.
+            let output_section = if section.nobits {
+                Section {
+                    name,
+                    ..Section::nobits(section.size)
+                }
+            } else {
+                let content = self.section_content(section)?;
+                Section {
+                    r#type: section.r#type,
+                    flags: ShFlags::SHF_ALLOC | flags,
+                    ..Section::progbits(name, content)
+                }
+            };
+            let idx = writer.add_section(output_section)?;
+            writer.set_section_addr(idx, section.addr);
+            output_sections.push(Some(idx));
+            end_addr = end_addr.max(section.addr + section.size);
+
+            program_headers.push((
+                ProgramHeader {
+                    r#type: PT_LOAD.into(),
+                    flags: segment_flags(flags),
+                    offset: SectionRelativeAbsoluteAddr {
+                        section: idx,
+                        rel_offset: Offset(0),
+                    },
+                    vaddr: section.addr,
+                    paddr: section.addr,
+                    filesz: 0,
+                    memsz: 0,
+                    align: DEFAULT_PAGE_ALIGN,
+                },
+                Some(ProgramHeaderExtent::Sections {
+                    start: idx,
+                    end: idx,
+                }),
+            ));
+        }
+
+        if !self.got.is_empty() {
+            program_headers.push(add_synthesized_section(
+                &mut writer,
+                b".got",
+                c::SHT_PROGBITS,
+                ShFlags::SHF_WRITE,
+                got::GOT_ENTRY_SIZE,
+                self.got.addr,
+                self.got_content()?,
+            )?);
+            end_addr = end_addr.max(self.got.addr + self.got.size());
+        }
+
+        if !self.plt.is_empty() {
+            program_headers.push(add_synthesized_section(
+                &mut writer,
+                b".plt",
+                c::SHT_PROGBITS,
+                ShFlags::SHF_EXECINSTR,
+                plt::PLT_ENTRY_SIZE,
+                self.plt.addr,
+                self.plt.content()?,
+            )?);
+            program_headers.push(add_synthesized_section(
+                &mut writer,
+                b".got.plt",
+                c::SHT_PROGBITS,
+                ShFlags::SHF_WRITE,
+                plt::GOT_PLT_ENTRY_SIZE,
+                self.plt.got_addr,
+                self.got_plt_content()?,
+            )?);
+            end_addr = end_addr.max(self.plt.got_addr + self.plt.got_size());
+        }
+
+        if !self.eh_frame.is_empty() {
+            program_headers.push(add_synthesized_section(
+                &mut writer,
+                b".eh_frame",
+                c::SHT_PROGBITS,
+                ShFlags::empty(),
+                0,
+                self.eh_frame.addr,
+                self.eh_frame.content.clone(),
+            )?);
+            let (load, extent) = add_synthesized_section(
+                &mut writer,
+                b".eh_frame_hdr",
+                c::SHT_PROGBITS,
+                ShFlags::empty(),
+                0,
+                self.eh_frame.hdr_addr,
+                self.eh_frame.hdr_content.clone(),
+            )?;
+            program_headers.push((
+                ProgramHeader {
+                    r#type: PT_GNU_EH_FRAME.into(),
+                    align: 4,
+                    ..load
+                },
+                extent,
+            ));
+            program_headers.push((load, extent));
+            end_addr =
+                end_addr.max(self.eh_frame.hdr_addr + self.eh_frame.hdr_content.len() as u64);
+        }
+
+        let build_id = opts.build_id()?;
+        if let Some(algo) = build_id {
+            let addr = end_addr
+                .checked_align_up(DEFAULT_PAGE_ALIGN)
+                .context("no address space left for the build ID")?;
+
+            let idx = writer.reserve_build_id(algo)?;
+            writer.set_section_addr(idx, addr);
+            let (load, extent) = load_program_header(idx, ShFlags::empty(), addr);
+            program_headers.push((
+                ProgramHeader {
+                    r#type: PT_NOTE.into(),
+                    align: 4,
+                    ..load
+                },
+                extent,
+            ));
+            program_headers.push((load, extent));
+            end_addr = addr + algo.note_size();
+        }
+
+        // PT_INTERP has to come before all PT_LOAD segments, so it is added first below.
+        let mut interp = None;
+        if let Some(path) = &opts.dynamic_linker {
+            let addr = end_addr
+                .checked_align_up(DEFAULT_PAGE_ALIGN)
+                .context("no address space left for .interp")?;
+            let mut content = path.as_bytes().to_vec();
+            content.push(0);
+            let size = content.len() as u64;
+
+            let (load, extent) = add_synthesized_section(
+                &mut writer,
+                b".interp",
+                c::SHT_PROGBITS,
+                ShFlags::empty(),
+                0,
+                addr,
+                content,
+            )?;
+            interp = Some((
+                ProgramHeader {
+                    r#type: PT_INTERP.into(),
+                    align: 1,
+                    ..load
+                },
+                extent,
+            ));
+            program_headers.push((load, extent));
+            end_addr = addr + size;
+        }
+
+        if opts.shared {
+            // The dynamic sections are placed together on their own pages.
+            let addr = end_addr.align_up(DEFAULT_PAGE_ALIGN);
+            let mut relative = self.pointer_relative_relocations()?;
+            relative.extend(self.got_relative_relocations()?);
+            relative.extend(self.got_plt_relative_relocations()?);
+            let dynamic = add_dynamic_sections(&mut writer, addr, opts, relative)?;
+            program_headers.push((
+                ProgramHeader {
+                    r#type: PT_LOAD.into(),
+                    flags: PhFlags::PF_R | PhFlags::PF_W,
+                    offset: SectionRelativeAbsoluteAddr {
+                        section: dynamic.dynsym,
+                        rel_offset: Offset(0),
+                    },
+                    vaddr: addr,
+                    paddr: addr,
+                    filesz: 0,
+                    memsz: 0,
+                    align: DEFAULT_PAGE_ALIGN,
+                },
+                Some(ProgramHeaderExtent::Sections {
+                    start: dynamic.dynsym,
+                    end: dynamic.dynamic,
+                }),
+            ));
+            program_headers.push((
+                ProgramHeader {
+                    r#type: PT_DYNAMIC.into(),
+                    flags: PhFlags::PF_R | PhFlags::PF_W,
+                    offset: SectionRelativeAbsoluteAddr {
+                        section: dynamic.dynamic,
+                        rel_offset: Offset(0),
+                    },
+                    vaddr: dynamic.dynamic_addr,
+                    paddr: dynamic.dynamic_addr,
+                    filesz: 0,
+                    memsz: 0,
+                    align: 8,
+                },
+                Some(ProgramHeaderExtent::Sections {
+                    start: dynamic.dynamic,
+                    end: dynamic.dynamic,
+                }),
+            ));
+        }
+
+        program_headers.push((
+            ProgramHeader {
+                r#type: PT_GNU_STACK.into(),
+                flags: self.stack_flags()?,
+                offset: SectionRelativeAbsoluteAddr {
+                    section: SectionIdx(0),
+                    rel_offset: Offset(0),
+                },
+                vaddr: Addr(0),
+                paddr: Addr(0),
+                filesz: 0,
+                memsz: 0,
+                align: 16,
+            },
+            None,
+        ));
+
+        let elf_header_and_program_headers = ProgramHeader {
+            r#type: PT_LOAD.into(),
+            flags: PhFlags::PF_R,
+            offset: SectionRelativeAbsoluteAddr {
+                section: SectionIdx(0),
+                rel_offset: Offset(0),
+            },
+            vaddr: base_addr,
+            paddr: base_addr,
+            filesz: 0,
+            memsz: 0,
+            align: DEFAULT_PAGE_ALIGN,
+        };
+
+        if let Some((ph, extent)) = interp {
+            let ph_idx = writer.add_program_header(ph);
+            if let Some(extent) = extent {
+                writer.set_program_header_extent(ph_idx, extent);
+            }
+        }
+        let ph_idx = writer.add_program_header(elf_header_and_program_headers);
+        writer.set_program_header_extent(ph_idx, ProgramHeaderExtent::Headers);
+        for (ph, extent) in program_headers {
+            let ph_idx = writer.add_program_header(ph);
+            if let Some(extent) = extent {
+                writer.set_program_header_extent(ph_idx, extent);
+            }
+        }
+
+        if !opts.strip_all {
+            self.add_symbol_table(&mut writer, &output_sections)?;
+        }
+
+        let entry = match self.global_symbol_addr(entry_symbol(opts)) {
+            // Like ld, shared objects without an entry point just get 0.
+            Err(_) if opts.shared && opts.entry.is_none() => Addr(0),
+            entry => entry?,
+        };
+        writer.set_entry(entry);
+
+        let mut content = writer.write()?;
+        writer.finalize_build_id(&mut content)?;
+        check_output(&content)?;
+
+        let output = Path::new(opts.output.as_deref().unwrap_or("a.out"));
+        fs::write(output, content)
+            .with_context(|| format!("writing output file {}", output.display()))?;
+        make_file_executable(output)
+    }
+
+    /// Adds `.symtab` with all global and local symbols that ended up in the output.
+    /// `output_sections` are the output indices of the allocated sections.
+    fn add_symbol_table(
+        &self,
+        writer: &mut ElfWriter,
+        output_sections: &[Option<SectionIdx>],
+    ) -> Result<()> {
+        let default = SymbolVisibility(c::STV_DEFAULT);
+        let globals = self.sym_defs.iter().filter_map(|(name, sym)| {
+            let def = sym.definition.as_ref()?;
+            // Like ld, symbols hidden by the version script become local.
+            let hidden = self
+                .version_script
+                .as_ref()
+                .is_some_and(|script| !script.is_exported(name));
+            let (binding, visibility) = if hidden {
+                (SymbolBinding(c::STB_LOCAL), SymbolVisibility(c::STV_HIDDEN))
+            } else {
+                (sym.binding, default)
+            };
+            Some((*name, binding, visibility, def))
+        });
+        let locals = self
+            .local_syms
+            .iter()
+            .flatten()
+            .map(|(name, def)| (*name, SymbolBinding(c::STB_LOCAL), default, def));
+
+        let mut symbols = Vec::new();
+        for (name, binding, visibility, def) in globals.chain(locals) {
+            let section = match def.location.section.0 {
+                c::SHN_ABS => def.location.section,
+                c::SHN_COMMON => continue,
+                _ => {
+                    // Not every section is linked, for example debug info or removed ones.
+                    let output = self.storage.sections.iter().position(|section| {
+                        section.parts.iter().any(|part| {
+                            part.file == def.location.file && part.section == def.location.section
+                        })
+                    });
+                    match output.and_then(|idx| output_sections[idx]) {
+                        Some(section) => section,
+                        None => continue,
                     }
                 }
+            };
+
+            let value = self.symbol_addr(def.location, def.value)?;
+            symbols.push(write::Symbol {
+                name,
+                r#type: def.r#type,
+                binding,
+                visibility,
+                section,
+                value,
+                size: def.size,
+            });
+        }
+        symbols.sort_by_key(|sym| (sym.value, sym.name));
+
+        let mut builder = writer.symbol_table_builder();
+        for sym in symbols {
+            builder.add_symbol(sym);
+        }
+        writer.finish_symbol_table(builder)?;
+
+        Ok(())
+    }
+
+    /// Writes a map of the output sections and the addresses of all defined symbols.
+    fn write_map(&self, path: &Path) -> Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+
+        writeln!(out, "Sections")?;
+        for section in &self.storage.sections {
+            if section.size == 0 {
+                continue;
+            }
+            writeln!(out, "{} {} {:#x}", section.name, section.addr, section.size)?;
+        }
+
+        let globals = self
+            .sym_defs
+            .iter()
+            .filter_map(|(name, sym)| Some((name, sym.definition.as_ref()?)));
+        let locals = self.local_syms.iter().flatten();
+
+        let mut symbols = Vec::new();
+        for (name, def) in globals.chain(locals) {
+            // Symbols in sections removed by --gc-sections.
+            if def.location.section.0 < c::SHN_LORESERVE && !self.live.contains(&def.location) {
+                continue;
             }
+            let addr = self.symbol_addr(def.location, def.value)?;
+            symbols.push((addr, *name, def));
+        }
+        symbols.sort_by_key(|&(addr, name, _)| (addr, name));
+
+        writeln!(out, "\nSymbols")?;
+        for (addr, name, def) in symbols {
+            writeln!(
+                out,
+                "{name} {addr} {:#x} {}",
+                def.size,
+                def.location.file.path().display()
+            )?;
         }
 
+        out.flush()?;
         Ok(())
     }
+
+    /// The stack is only executable if an input file asks for it.
+    fn stack_flags(&self) -> Result<PhFlags> {
+        let mut flags = PhFlags::PF_R | PhFlags::PF_W;
+        for elf in &self.elves {
+            if let Some(file_flags) = elf.elf.gnu_stack_flags()? {
+                flags |= file_flags;
+            }
+        }
+        Ok(flags)
+    }
+
+    /// Concatenates the contributions of all input files to an output section
+    /// and applies their relocations.
+    fn section_content(&self, section: &AllocatedSection) -> Result<Vec<u8>> {
+        let mut content = Vec::new();
+
+        for part in &section.parts {
+            let elf = &self.elves[part.file.0].elf;
+            let shdr = elf.section_header(part.section)?;
+
+            content.resize(content.len() + usize::try_from(part.pad_from_prev)?, 0);
+
+            let part_start = content.len();
+            if shdr.r#type == SHT_NOBITS {
+                content.resize(content.len() + usize::try_from(part.size)?, 0);
+            } else {
+                content.extend(elf.section_content(shdr)?);
+            }
+
+            self.apply_relocations(part, &mut content[part_start..])
+                .with_context(|| {
+                    format!(
+                        "applying relocations to {} of {:?}",
+                        section.name, part.file
+                    )
+                })?;
+        }
+
+        Ok(content)
+    }
+
+    fn apply_relocations(&self, part: &SegmentPart, content: &mut [u8]) -> Result<()> {
+        let elf = &self.elves[part.file.0].elf;
+
+        for (rela_sh, rela) in elf.relas()? {
+            if rela_sh.info != u32::from(part.section.0) {
+                continue;
+            }
+
+            self.apply_relocation(part, rela, content)?;
+        }
+
+        Ok(())
+    }
+
+    fn apply_relocation(&self, part: &SegmentPart, rela: &Rela, content: &mut [u8]) -> Result<()> {
+        match self.target {
+            Target::X86_64 => self.apply_x86_64_relocation(part, rela, content),
+            target => bail!("relocations for {target} are not supported yet"),
+        }
+    }
+
+    fn apply_x86_64_relocation(
+        &self,
+        part: &SegmentPart,
+        rela: &Rela,
+        content: &mut [u8],
+    ) -> Result<()> {
+        let ty = c::RX86_64(rela.info.r#type());
+
+        // S: the value of the symbol, A: the addend, P: the address of the place being relocated.
+        let s = self.relocation_symbol_addr(part.file, rela.info.sym())?;
+        let a = rela.addend;
+        let p = part.base + rela.offset;
+
+        let offset = rela.offset.usize();
+
+        match ty.0 {
+            c::R_X86_64_32 | c::R_X86_64_32S if self.shared => {
+                bail!(
+                    "{ty} relocation at {p} can't be used in a shared object, recompile with -fPIC"
+                )
+            }
+            c::R_X86_64_64 => {
+                let value = s.u64().wrapping_add_signed(a);
+                write_relocation(content, offset, &value.to_le_bytes())
+            }
+            c::R_X86_64_32S => {
+                let value = i64::try_from(s.u64())? + a;
+                let value = i32::try_from(value)
+                    .with_context(|| format!("{ty} relocation at {p} does not fit: {value:x}"))?;
+                write_relocation(content, offset, &value.to_le_bytes())
+            }
+            c::R_X86_64_PC32 => {
+                let value = i64::try_from(s.u64())? + a - i64::try_from(p.u64())?;
+                let value = i32::try_from(value)
+                    .with_context(|| format!("{ty} relocation at {p} does not fit: {value:x}"))?;
+                write_relocation(content, offset, &value.to_le_bytes())
+            }
+            c::R_X86_64_PLT32 => {
+                // L: the PLT stub of the symbol. Without one, the call goes to the symbol directly
+                // and this is just like R_X86_64_PC32.
+                let l = self
+                    .plt_entry_addr(part.file, rela.info.sym())?
+                    .unwrap_or(s);
+                let value = i64::try_from(l.u64())? + a - i64::try_from(p.u64())?;
+                let value = i32::try_from(value)
+                    .with_context(|| format!("{ty} relocation at {p} does not fit: {value:x}"))?;
+                write_relocation(content, offset, &value.to_le_bytes())
+            }
+            c::R_X86_64_GOTPCREL | c::R_X86_64_GOTPCRELX | c::R_X86_64_REX_GOTPCRELX => {
+                // G + GOT: the address of the slot of the symbol.
+                let slot = self
+                    .got
+                    .slot_addr(self.got_symbol(part.file, rela.info.sym())?)
+                    .context("symbol has no GOT slot")?;
+                let value = i64::try_from(slot.u64())? + a - i64::try_from(p.u64())?;
+                let value = i32::try_from(value)
+                    .with_context(|| format!("{ty} relocation at {p} does not fit: {value:x}"))?;
+                write_relocation(content, offset, &value.to_le_bytes())
+            }
+            _ => bail!("unsupported relocation type {ty}"),
+        }
+    }
+
+    /// The places in a shared object that `R_X86_64_64` relocations filled with a link time
+    /// address, with that address. The dynamic linker has to add the load address to them.
+    fn pointer_relative_relocations(&self) -> Result<Vec<(Addr, Addr)>> {
+        let mut relocations = Vec::new();
+
+        for file in &self.elves {
+            for (rela_sh, rela) in file.elf.relas()? {
+                let target = SectionId {
+                    file: file.id,
+                    section: SectionIdx(rela_sh.info.try_into()?),
+                };
+                // Sections that are not linked, like debug info, are never loaded.
+                let Some(base) = self.storage.section_va(target) else {
+                    continue;
+                };
+                if rela.info.r#type() != c::R_X86_64_64
+                    || !self.is_relative_symbol(file.id, rela.info.sym())?
+                {
+                    continue;
+                }
+
+                let s = self.relocation_symbol_addr(file.id, rela.info.sym())?;
+                let value = Addr(s.u64().wrapping_add_signed(rela.addend));
+                relocations.push((base + rela.offset, value));
+            }
+        }
+
+        Ok(relocations)
+    }
+
+    fn relocation_symbol_addr(&self, file: FileId, idx: SymIdx) -> Result<Addr> {
+        let elf = &self.elves[file.0].elf;
+        let sym = elf.symbol(idx)?;
+
+        if sym.shndx == SHN_UNDEF {
+            let name = elf.string(sym.name)?;
+            return self.global_symbol_addr(name);
+        }
+
+        self.symbol_addr(
+            SectionId {
+                file,
+                section: sym.shndx,
+            },
+            sym.value,
+        )
+    }
+
+    fn global_symbol_addr(&self, name: &BStr) -> Result<Addr> {
+        let sym = self
+            .sym_defs
+            .get(name)
+            .with_context(|| format!("symbol {name} not found"))?;
+        let Some(def) = &sym.definition else {
+            if sym.binding == c::STB_WEAK || self.allow_undefined {
+                return Ok(Addr(0));
+            }
+            bail!("undefined symbol {name}");
+        };
+
+        self.symbol_addr(def.location, def.value)
+    }
+
+    /// Whether the address of a symbol moves with the load address of a shared object.
+    /// Absolute symbols and undefined symbols, which are resolved to 0, stay where they are.
+    fn is_relative_symbol(&self, file: FileId, idx: SymIdx) -> Result<bool> {
+        let elf = &self.elves[file.0].elf;
+        let sym = elf.symbol(idx)?;
+
+        if sym.shndx == SHN_UNDEF {
+            return Ok(self.is_relative_global_symbol(elf.string(sym.name)?));
+        }
+        Ok(sym.shndx.0 != c::SHN_ABS)
+    }
+
+    fn is_relative_global_symbol(&self, name: &BStr) -> bool {
+        self.sym_defs
+            .get(name)
+            .and_then(|sym| sym.definition.as_ref())
+            .is_some_and(|def| def.location.section.0 != c::SHN_ABS)
+    }
+
+    fn symbol_addr(&self, location: SectionId, value: Addr) -> Result<Addr> {
+        match location.section.0 {
+            c::SHN_ABS => Ok(value),
+            c::SHN_COMMON => bail!("common symbols are not supported"),
+            _ => Ok(self.section_addr(location)? + value),
+        }
+    }
+
+    /// The end of the memory that is allocated so far.
+    fn allocated_end(&self) -> Addr {
+        let sections = self
+            .storage
+            .sections
+            .iter()
+            .map(|section| section.addr + section.size);
+        let got = (!self.got.is_empty()).then(|| self.got.addr + self.got.size());
+        let plt = (!self.plt.is_empty()).then(|| self.plt.got_addr + self.plt.got_size());
+        sections.chain(got).chain(plt).max().unwrap_or(Addr(0))
+    }
+
+    /// The address of the contribution of an input section to the output.
+    fn section_addr(&self, id: SectionId) -> Result<Addr> {
+        self.storage.section_va(id).with_context(|| {
+            let elf = &self.elves[id.file.0].elf;
+            let name = elf
+                .section_header(id.section)
+                .and_then(|sh| elf.sh_string(sh.name))
+                .unwrap_or(BStr::new("<unknown>"));
+            format!("section {name} of {:?} is not linked", id.file)
+        })
+    }
+}
+
+fn write_relocation(content: &mut [u8], offset: usize, value: &[u8]) -> Result<()> {
+    let place = content
+        .get_mut(offset..)
+        .and_then(|rest| rest.get_mut(..value.len()))
+        .with_context(|| format!("relocation offset {offset:#x} is out of bounds"))?;
+    place.copy_from_slice(value);
+    Ok(())
+}
+
+/// Adds a section created by the linker at `addr`, which must be on its own pages.
+/// Returns the `PT_LOAD` program header for it.
+fn add_synthesized_section(
+    writer: &mut ElfWriter,
+    name: &[u8],
+    r#type: u32,
+    flags: ShFlags,
+    entsize: u64,
+    addr: Addr,
+    content: Vec<u8>,
+) -> Result<(ProgramHeader, Option<ProgramHeaderExtent>)> {
+    let name = writer.add_sh_string(name);
+    let idx = writer.add_section(Section {
+        name,
+        r#type: c::ShType(r#type),
+        flags: ShFlags::SHF_ALLOC | flags,
+        fixed_entsize: NonZeroU64::new(entsize),
+        addr_align: None,
+        content,
+        nobits_size: 0,
+        link: 0,
+        info: 0,
+    })?;
+    writer.set_section_addr(idx, addr);
+
+    Ok(load_program_header(idx, flags, addr))
+}
+
+/// The `PT_LOAD` program header for a section at `addr`, which is on its own pages.
+fn load_program_header(
+    idx: SectionIdx,
+    flags: ShFlags,
+    addr: Addr,
+) -> (ProgramHeader, Option<ProgramHeaderExtent>) {
+    (
+        ProgramHeader {
+            r#type: PT_LOAD.into(),
+            flags: segment_flags(flags),
+            offset: SectionRelativeAbsoluteAddr {
+                section: idx,
+                rel_offset: Offset(0),
+            },
+            vaddr: addr,
+            paddr: addr,
+            filesz: 0,
+            memsz: 0,
+            align: DEFAULT_PAGE_ALIGN,
+        },
+        Some(ProgramHeaderExtent::Sections {
+            start: idx,
+            end: idx,
+        }),
+    )
+}
+
+fn segment_flags(flags: ShFlags) -> PhFlags {
+    let mut ph_flags = PhFlags::PF_R;
+    if flags.contains(ShFlags::SHF_EXECINSTR) {
+        ph_flags |= PhFlags::PF_X;
+    }
+    if flags.contains(ShFlags::SHF_WRITE) {
+        ph_flags |= PhFlags::PF_W;
+    }
+    ph_flags
 }
 
-fn create_elf() -> ElfWriter {
+/// Adds the dynamic sections at `addr`. Nothing is exported dynamically yet, so this only
+/// contains the soname and an `R_X86_64_RELATIVE` relocation for every `(place, addr)` in
+/// `relative`, which moves the link time address at the place by the load address.
+fn add_dynamic_sections(
+    writer: &mut ElfWriter,
+    addr: Addr,
+    opts: &Opts,
+    relative: Vec<(Addr, Addr)>,
+) -> Result<DynamicSections> {
+    let mut dynamic = writer.dynamic_section_builder();
+    if let Some(soname) = &opts.soname {
+        dynamic.set_soname(soname.as_bytes());
+    }
+    for (place, addr) in relative {
+        dynamic.add_relocation(place, c::R_X86_64_RELATIVE, addr.u64().try_into()?);
+    }
+    Ok(writer.finish_dynamic(dynamic, addr)?)
+}
+
+/// Linking objects for another architecture would produce garbage, so refuse early.
+fn check_machine(elf: &ElfReader<'_>, target: Target, path: &Path) -> Result<()> {
+    let machine = elf.header()?.machine;
+    if machine != target.machine() {
+        bail!(
+            "{} is for {machine}, but linking for {target}",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Inconsistent headers might still link fine, so they only get a warning.
+fn warn_inconsistent_headers(elf: &ElfReader<'_>, path: &Path) -> Result<()> {
+    for warning in elf.validate()? {
+        warn!("{}: {warning}", path.display());
+    }
+    Ok(())
+}
+
+fn create_elf(r#type: u16, target: Target) -> ElfWriter {
     let ident = ElfIdent {
         magic: *c::ELFMAG,
         class: c::Class(c::ELFCLASS64),
-        data: c::Data(c::ELFDATA2LSB),
+        data: target.data(),
         version: 1,
         osabi: c::OsAbi(c::ELFOSABI_SYSV),
         abiversion: 0,
@@ -239,86 +1440,67 @@ fn create_elf() -> ElfWriter {
 
     let header = write::Header {
         ident,
-        r#type: c::Type(c::ET_EXEC),
-        machine: c::Machine(c::EM_X86_64),
+        r#type: c::Type(r#type),
+        machine: target.machine(),
     };
 
-    ElfWriter::new(header)
+    let mut writer = ElfWriter::new(header);
+    // Like ld, so that the dynamic loader can find the load bias of executables.
+    writer.set_phdr_program_header(true);
+    writer
 }
 
-fn write_output(opts: &Opts, text: &[u8], entry_offset_from_text: Addr) -> Result<()> {
-    let mut write = create_elf();
-
-    let text_name = write.add_sh_string(b".text");
-    let text_section = write.add_section(Section {
-        name: text_name,
-        r#type: ShType(SHT_PROGBITS),
-        flags: ShFlags::SHF_ALLOC | ShFlags::SHF_EXECINSTR,
-        fixed_entsize: None,
-        content: text.to_vec(),
-        // align nicely
-        addr_align: Some(NonZeroU64::new(DEFAULT_PAGE_ALIGN).unwrap()),
-    })?;
-
-    let elf_header_and_program_headers = ProgramHeader {
-        r#type: PT_LOAD.into(),
-        flags: PhFlags::PF_R,
-        offset: SectionRelativeAbsoluteAddr {
-            section: SectionIdx(0),
-            rel_offset: Offset(0),
-        },
-        vaddr: BASE_EXEC_ADDR,
-        paddr: BASE_EXEC_ADDR,
-        filesz: 176, // FIXME: Do not hardocde this lol
-        memsz: 176,
-        align: DEFAULT_PAGE_ALIGN,
-    };
-
-    write.add_program_header(elf_header_and_program_headers);
-
-    let entry_addr = BASE_EXEC_ADDR + DEFAULT_PAGE_ALIGN + entry_offset_from_text;
-
-    let text_program_header = ProgramHeader {
-        r#type: PT_LOAD.into(),
-        flags: PhFlags::PF_X | PhFlags::PF_R,
-        offset: SectionRelativeAbsoluteAddr {
-            section: text_section,
-            rel_offset: Offset(0),
-        },
-        vaddr: entry_addr,
-        paddr: entry_addr,
-        filesz: text.len() as u64,
-        memsz: text.len() as u64,
-        align: DEFAULT_PAGE_ALIGN,
-    };
-
-    write.add_program_header(text_program_header);
-
-    write.set_entry(entry_addr);
+/// Finds `lib<name>.a` for `-l<name>` in the `-L` directories, in order.
+/// Only static archives can be linked, shared objects are just found for a better error.
+fn find_library(opts: &Opts, name: &str) -> Result<PathBuf> {
+    let archive = format!("lib{name}.a");
+    if let Some(path) = opts
+        .library_paths
+        .iter()
+        .map(|dir| dir.join(&archive))
+        .find(|path| path.is_file())
+    {
+        debug!(?path, "Found library");
+        return Ok(path);
+    }
 
-    write_elf_to_file(write, &opts.output)?;
+    let shared = format!("lib{name}.so");
+    if let Some(path) = opts
+        .library_paths
+        .iter()
+        .map(|dir| dir.join(&shared))
+        .find(|path| path.is_file())
+    {
+        bail!(
+            "only found shared library {} for -l{name}, which is not supported",
+            path.display()
+        );
+    }
 
-    Ok(())
+    bail!("library -l{name} not found in the library search paths")
 }
 
-fn write_elf_to_file(elf: ElfWriter, path: &Path) -> Result<()> {
-    let output = elf.write().context("writing output file")?;
-
-    let mut output_file = fs::File::create(path).context("creating ./a.out")?;
-    BufWriter::new(&mut output_file).write_all(&output)?;
-
-    make_file_executable(&output_file)
+/// Catches layout bugs before they turn into a file that crashes the loader.
+fn check_output(content: &[u8]) -> Result<()> {
+    let elf = ElfReader::new(content).context("reading back the output")?;
+    for warning in elf.validate()? {
+        match warning {
+            ElfWarning::OverlappingSections { .. } => bail!("invalid output: {warning}"),
+            _ => warn!("output: {warning}"),
+        }
+    }
+    Ok(())
 }
 
-fn make_file_executable(file: &File) -> Result<()> {
+fn make_file_executable(path: &Path) -> Result<()> {
     #[allow(unused_mut)]
-    let mut permissions = file.metadata()?.permissions();
+    let mut permissions = fs::metadata(path)?.permissions();
     #[cfg(unix)]
     {
         use std::os::unix::fs::PermissionsExt;
         let mode = permissions.mode();
         permissions.set_mode(mode | 0o111);
     };
-    file.set_permissions(permissions)?;
+    fs::set_permissions(path, permissions)?;
     Ok(())
 }
@@ -1,10 +1,8 @@
-use clap::Parser;
 use tracing::metadata::LevelFilter;
 use tracing_subscriber::EnvFilter;
 
 fn main() -> anyhow::Result<()> {
-    let opts = elven_wald::Opts::parse();
-    let (_opts, _input) = elven_wald::opts::parse(std::env::args().skip(1))?;
+    let (opts, inputs) = elven_wald::opts::parse(std::env::args().skip(1))?;
 
     tracing_subscriber::fmt()
         .with_env_filter(
@@ -14,5 +12,5 @@ fn main() -> anyhow::Result<()> {
         )
         .init();
 
-    elven_wald::run(opts)
+    elven_wald::run(opts, inputs)
 }
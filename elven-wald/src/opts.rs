@@ -7,9 +7,17 @@
 //!
 //! Bless the linker writers of the past for the mess they have constructed.
 
-use std::path::PathBuf;
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
 
-use anyhow::bail;
+use anyhow::{bail, Context};
+use elven_parser::{
+    consts::{self as c, Data, Machine},
+    write::BuildIdAlgo,
+};
 
 #[derive(Debug)]
 pub struct InputFile {
@@ -20,34 +28,50 @@ struct Opt {
     short: Option<char>,
     long: &'static str,
     takes_value: Option<fn(&mut Opts, value: String)>,
+    /// The value of a long option given without `=value`, for options where the value is optional.
+    default_value: Option<&'static str>,
     set: fn(&mut Opts),
 }
 
 macro_rules! define_opts {
     ($(
-        $field:ident: $long:literal $(, $short:literal)? $(, $value:ident)? ;
+        $field:ident: $long:literal $(, $short:literal)? $(, $value:ident $(= $default:literal)?)? $(, [$multi:ident])? ;
     )*) => {
         #[derive(Debug, Default)]
         pub struct Opts {
             $(
-                pub $field: Option<String>,
+                pub $field: field_ty!($($value)? $([$multi])?),
             )*
         }
 
         const OPTS: &[Opt] = &[
             $(
                 Opt {
-                    short: short_opt!($($short)?),
+                    short: optional!($($short)?),
                     long: $long,
-                    takes_value: takes_value!($field, $($value)?),
-                    set: set!($field, $($value)?)
+                    takes_value: takes_value!($field, $($value)? $([$multi])?),
+                    default_value: optional!($($($default)?)?),
+                    set: set!($field, $($value)? $([$multi])?)
                 },
             )*
         ];
     };
 }
 
-macro_rules! short_opt {
+macro_rules! field_ty {
+    () => {
+        bool
+    };
+    // Options that can be given multiple times.
+    ([$value:ident]) => {
+        Vec<$value>
+    };
+    ($value:ident) => {
+        Option<$value>
+    };
+}
+
+macro_rules! optional {
     () => {
         None
     };
@@ -60,14 +84,17 @@ macro_rules! takes_value {
     ($field:ident, ) => {
         None
     };
+    ($field:ident, [$value:ident]) => {
+        Some(|opts, value| opts.$field.push(value.into()))
+    };
     ($field:ident, $opt:tt) => {
-        Some(|opts, value| opts.$field = Some(value))
+        Some(|opts, value| opts.$field = Some(value.into()))
     };
 }
 
 macro_rules! set {
     ($field:ident, ) => {
-        |opts| opts.$field = true;
+        |opts| opts.$field = true
     };
     ($field:ident, $opt:tt) => {
         |_| {
@@ -81,17 +108,154 @@ macro_rules! set {
 
 define_opts! {
     entry: "entry", 'e', String;
+    // ld only has -m, the long name is just for consistency.
+    emulation: "emulation", 'm', String;
     output: "output", 'o', String;
+    map: "Map", String;
+    build_id: "build-id", String = "sha1";
+    gc_sections: "gc-sections";
+    // The layout is always deterministic, this is accepted for compatibility.
+    no_randomize_section_layout: "no-randomize-section-layout";
+    strip_all: "strip-all", 's';
+    // Debug info of the inputs is never copied to the output, so there is nothing to strip.
+    strip_debug: "strip-debug", 'S';
+    shared: "shared";
+    soname: "soname", 'h', String;
+    dynamic_linker: "dynamic-linker", 'I', String;
+    version_script: "version-script", PathBuf;
+    allow_undefined: "allow-undefined";
+    no_undefined: "no-undefined";
+    library_paths: "library-path", 'L', [PathBuf];
+    libraries: "library", 'l', [String];
+}
+
+/// The architecture to link for, from the GNU ld emulation name passed with `-m`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Target {
+    #[default]
+    X86_64,
+    AArch64,
+    RiscV64,
+}
+
+impl Target {
+    pub fn from_emulation(emulation: &str) -> anyhow::Result<Self> {
+        Ok(match emulation {
+            "elf_x86_64" => Self::X86_64,
+            "aarch64elf" | "aarch64linux" => Self::AArch64,
+            "elf64lriscv" => Self::RiscV64,
+            _ => bail!("unrecognized emulation mode: {emulation}"),
+        })
+    }
+
+    pub fn machine(self) -> Machine {
+        Machine(match self {
+            Self::X86_64 => c::EM_X86_64,
+            Self::AArch64 => c::EM_AARCH64,
+            Self::RiscV64 => c::EM_RISCV,
+        })
+    }
+
+    /// All supported targets are little endian.
+    pub fn data(self) -> Data {
+        Data(c::ELFDATA2LSB)
+    }
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::X86_64 => "x86-64",
+            Self::AArch64 => "AArch64",
+            Self::RiscV64 => "RISC-V 64",
+        })
+    }
+}
+
+/// The build ID algorithm from `--build-id=<style>`, or `None` for `none` which disables an
+/// earlier `--build-id`.
+fn parse_build_id(name: &str) -> anyhow::Result<Option<BuildIdAlgo>> {
+    Ok(Some(match name {
+        "sha1" | "tree" => BuildIdAlgo::Sha1,
+        "md5" => BuildIdAlgo::Md5,
+        "uuid" => BuildIdAlgo::Uuid,
+        "none" => return Ok(None),
+        _ => bail!("unrecognized build ID style: {name}"),
+    }))
+}
+
+impl Opts {
+    pub fn target(&self) -> anyhow::Result<Target> {
+        self.emulation
+            .as_deref()
+            .map_or(Ok(Target::default()), Target::from_emulation)
+    }
+
+    pub fn build_id(&self) -> anyhow::Result<Option<BuildIdAlgo>> {
+        self.build_id.as_deref().map_or(Ok(None), parse_build_id)
+    }
+}
+
+/// Response files including other response files more deeply than this are probably cycles.
+const MAX_RESPONSE_FILE_DEPTH: usize = 16;
+
+struct Arg {
+    value: String,
+    /// How many response files deep this argument comes from.
+    depth: usize,
+    /// The directory of the response file containing this argument, if any.
+    dir: Option<PathBuf>,
 }
 
-pub fn parse(mut args: impl Iterator<Item = String>) -> anyhow::Result<(Opts, Vec<InputFile>)> {
+pub fn parse(args: impl Iterator<Item = String>) -> anyhow::Result<(Opts, Vec<InputFile>)> {
     let mut opts = Opts::default();
     let mut files = Vec::new();
     let mut require_value: Option<fn(_, _)> = None;
 
-    while let Some(arg) = args.next() {
-        if arg.starts_with("@") {
-            bail!("@file parsing syntax is not implemented yet.");
+    let mut args = args
+        .map(|value| Arg {
+            value,
+            depth: 0,
+            dir: None,
+        })
+        .collect::<VecDeque<_>>();
+
+    while let Some(Arg {
+        value: arg,
+        depth,
+        dir,
+    }) = args.pop_front()
+    {
+        if let Some(path) = arg.strip_prefix('@') {
+            if depth >= MAX_RESPONSE_FILE_DEPTH {
+                bail!("response files are nested too deeply, is there a cycle? at {arg}");
+            }
+            let path = match &dir {
+                Some(dir) => dir.join(path),
+                None => PathBuf::from(path),
+            };
+            let content = std::fs::read_to_string(&path)
+                .with_context(|| format!("reading response file {}", path.display()))?;
+            let tokens = split_response_file(&content)
+                .with_context(|| format!("parsing response file {}", path.display()))?;
+
+            let dir = path.parent().map(Path::to_owned);
+            for value in tokens.into_iter().rev() {
+                args.push_front(Arg {
+                    value,
+                    depth: depth + 1,
+                    dir: dir.clone(),
+                });
+            }
+        } else if let Some(linker_args) = arg.strip_prefix("-Wl,") {
+            // Compiler driver syntax, `-Wl,-soname,libfoo.so` passes `-soname libfoo.so`.
+            for value in linker_args.split(',').rev() {
+                args.push_front(Arg {
+                    value: value.to_owned(),
+                    depth,
+                    dir: dir.clone(),
+                });
+            }
         } else if let Some(apply_value) = require_value {
             apply_value(&mut opts, arg);
             require_value = None;
@@ -118,6 +282,8 @@ pub fn parse(mut args: impl Iterator<Item = String>) -> anyhow::Result<(Opts, Ve
                     if long_end != arg.len() {
                         let value = &arg[(long_end + 1)..];
                         takes_value(&mut opts, value.to_owned());
+                    } else if let Some(default) = long.default_value {
+                        takes_value(&mut opts, default.to_owned());
                     } else {
                         require_value = Some(takes_value);
                     }
@@ -162,12 +328,51 @@ pub fn parse(mut args: impl Iterator<Item = String>) -> anyhow::Result<(Opts, Ve
     Ok((opts, files))
 }
 
+/// Splits a response file into whitespace separated arguments.
+/// Double quotes group arguments with spaces, and backslashes escape the next character.
+fn split_response_file(content: &str) -> anyhow::Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut in_quotes = false;
+    let mut chars = content.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let Some(escaped) = chars.next() else {
+                    bail!("response file ends with a backslash");
+                };
+                current.get_or_insert_with(String::new).push(escaped);
+            }
+            '"' => {
+                in_quotes = !in_quotes;
+                current.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                args.extend(current.take());
+            }
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if in_quotes {
+        bail!("unterminated quote in response file");
+    }
+    args.extend(current);
+
+    Ok(args)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{InputFile, Opts};
+    use std::path::PathBuf;
+
+    use elven_parser::write::BuildIdAlgo;
+
+    use super::{InputFile, Opts, Target};
 
     fn parse(cmd: impl AsRef<[&'static str]>) -> anyhow::Result<(Opts, Vec<InputFile>)> {
-        super::parse(cmd.as_ref().into_iter().map(|&s| s.to_owned()))
+        super::parse(cmd.as_ref().iter().map(|&s| s.to_owned()))
     }
 
     #[test]
@@ -220,6 +425,142 @@ mod tests {
         assert_eq!(opts.entry, Some("start".to_owned()));
     }
 
+    #[test]
+    fn map_single_dash() {
+        let cmd = ["-Map=out.map", "main.o"];
+        let (opts, files) = parse(cmd).unwrap();
+        assert_eq!(opts.map, Some("out.map".to_owned()));
+        assert_eq!(files.len(), 1);
+    }
+
+    #[test]
+    fn optional_value() {
+        let cmd = ["--build-id", "main.o"];
+        let (opts, files) = parse(cmd).unwrap();
+        assert_eq!(opts.build_id().unwrap(), Some(BuildIdAlgo::Sha1));
+        assert_eq!(files.len(), 1);
+
+        let cmd = ["--build-id=md5"];
+        let (opts, _) = parse(cmd).unwrap();
+        assert_eq!(opts.build_id().unwrap(), Some(BuildIdAlgo::Md5));
+
+        let cmd = ["--build-id", "--build-id=none"];
+        let (opts, _) = parse(cmd).unwrap();
+        assert_eq!(opts.build_id().unwrap(), None);
+
+        let cmd = ["--build-id=sha256"];
+        let (opts, _) = parse(cmd).unwrap();
+        opts.build_id().unwrap_err();
+    }
+
+    #[test]
+    fn flag_without_value() {
+        let cmd = ["--gc-sections"];
+        let (opts, _) = parse(cmd).unwrap();
+        assert!(opts.gc_sections);
+
+        let cmd = ["--gc-sections=yes"];
+        parse(cmd).unwrap_err();
+    }
+
+    #[test]
+    fn dynamic_linker() {
+        let interp = Some("/lib64/ld-linux-x86-64.so.2".to_owned());
+        let (opts, _) = parse(["-dynamic-linker", "/lib64/ld-linux-x86-64.so.2"]).unwrap();
+        assert_eq!(opts.dynamic_linker, interp);
+        let (opts, _) = parse(["--dynamic-linker=/lib64/ld-linux-x86-64.so.2"]).unwrap();
+        assert_eq!(opts.dynamic_linker, interp);
+        let (opts, _) = parse(["-I/lib64/ld-linux-x86-64.so.2"]).unwrap();
+        assert_eq!(opts.dynamic_linker, interp);
+    }
+
+    #[test]
+    fn shared() {
+        let (opts, _) = parse(["-shared"]).unwrap();
+        assert!(opts.shared);
+        let (opts, _) = parse(["--shared"]).unwrap();
+        assert!(opts.shared);
+        let (opts, _) = parse(["main.o"]).unwrap();
+        assert!(!opts.shared);
+    }
+
+    #[test]
+    fn soname() {
+        let (opts, _) = parse(["-shared", "-soname", "libfoo.so.1"]).unwrap();
+        assert_eq!(opts.soname, Some("libfoo.so.1".to_owned()));
+
+        let (opts, files) = parse(["-Wl,-soname,libfoo.so.1", "foo.o"]).unwrap();
+        assert_eq!(opts.soname, Some("libfoo.so.1".to_owned()));
+        assert_eq!(files.len(), 1);
+    }
+
+    #[test]
+    fn target() {
+        let (opts, _) = parse(["main.o"]).unwrap();
+        assert_eq!(opts.target().unwrap(), Target::X86_64);
+
+        let (opts, _) = parse(["-m", "aarch64elf"]).unwrap();
+        assert_eq!(opts.target().unwrap(), Target::AArch64);
+
+        let (opts, _) = parse(["-melf64lriscv"]).unwrap();
+        assert_eq!(opts.target().unwrap(), Target::RiscV64);
+
+        let (opts, _) = parse(["-m", "elf_i386"]).unwrap();
+        opts.target().unwrap_err();
+    }
+
+    #[test]
+    fn libraries() {
+        let cmd = [
+            "-lfoo",
+            "-L",
+            "/lib",
+            "--library=bar",
+            "-L/usr/lib",
+            "-l",
+            "baz",
+        ];
+        let (opts, _) = parse(cmd).unwrap();
+        assert_eq!(opts.libraries, ["foo", "bar", "baz"]);
+        assert_eq!(
+            opts.library_paths,
+            [PathBuf::from("/lib"), PathBuf::from("/usr/lib")]
+        );
+    }
+
+    #[test]
+    fn response_file() {
+        let dir = std::env::temp_dir().join(format!("elven-wald-opts-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(
+            dir.join("args"),
+            "-e start \"with space.o\"\n@nested/more\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("nested/more"), "--gc-sections last.o").unwrap();
+
+        let arg = format!("@{}", dir.join("args").display());
+        let result = super::parse([arg, "after.o".to_owned()].into_iter());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let (opts, files) = result.unwrap();
+        assert_eq!(opts.entry, Some("start".to_owned()));
+        assert!(opts.gc_sections);
+        let files = files
+            .iter()
+            .map(|f| f.name.to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(files, ["with space.o", "last.o", "after.o"]);
+    }
+
+    #[test]
+    fn response_file_splitting() {
+        let args = super::split_response_file("a  \"b c\"\td\\ e \"\"\n").unwrap();
+        assert_eq!(args, ["a", "b c", "d e", ""]);
+
+        super::split_response_file("\"unterminated").unwrap_err();
+    }
+
     #[test]
     fn bad_option() {
         let cmd = ["--meow"];
@@ -0,0 +1,161 @@
+//! The procedure linkage table for calls to functions in shared objects. Each call goes to a
+//! stub that jumps to the address in the `.got.plt` slot of the function.
+//!
+//! Executables call functions directly, all of them are known at link time.
+//! There is no lazy binding, so `.got.plt` has no reserved entries for the dynamic linker.
+//! Every slot contains the link time address of its function, which the dynamic linker moves
+//! by the load address with an `R_X86_64_RELATIVE` relocation. Nothing is exported through
+//! `.dynsym` yet, so the functions can't be interposed and `R_X86_64_JUMP_SLOT` isn't needed.
+
+use anyhow::{Context, Result};
+use bstr::BStr;
+use elven_parser::{
+    consts::{self as c, SectionIdx},
+    read::SymIdx,
+    Addr,
+};
+use indexmap::IndexSet;
+
+use crate::{opts::Target, utils::AlignExt, FileId, LinkCtxt, SectionId, DEFAULT_PAGE_ALIGN};
+
+pub const PLT_ENTRY_SIZE: u64 = 16;
+pub const GOT_PLT_ENTRY_SIZE: u64 = 8;
+
+#[derive(Debug)]
+pub struct Plt<'a> {
+    /// The address of `.plt`.
+    pub addr: Addr,
+    /// The address of `.got.plt`, on the page after `.plt`.
+    pub got_addr: Addr,
+    /// The function of every entry, in the order they were first called.
+    symbols: IndexSet<&'a BStr>,
+}
+
+impl Default for Plt<'_> {
+    fn default() -> Self {
+        Self {
+            addr: Addr(0),
+            got_addr: Addr(0),
+            symbols: IndexSet::new(),
+        }
+    }
+}
+
+impl<'a> Plt<'a> {
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn got_size(&self) -> u64 {
+        self.symbols.len() as u64 * GOT_PLT_ENTRY_SIZE
+    }
+
+    /// The address of the stub of the function, if calls to it go through the PLT.
+    pub fn entry_addr(&self, name: &BStr) -> Option<Addr> {
+        self.symbols
+            .get_index_of(name)
+            .map(|idx| self.addr + idx as u64 * PLT_ENTRY_SIZE)
+    }
+
+    /// The content of `.plt`. Every stub is `jmp [rip + slot]`, padded with `int3`.
+    pub fn content(&self) -> Result<Vec<u8>> {
+        let mut content = Vec::new();
+
+        for idx in 0..self.symbols.len() as u64 {
+            let entry = self.addr + idx * PLT_ENTRY_SIZE;
+            let slot = self.got_addr + idx * GOT_PLT_ENTRY_SIZE;
+            // The jump is relative to the end of the 6 byte instruction.
+            let rel = i64::try_from(slot.u64())? - i64::try_from(entry.u64() + 6)?;
+            let rel = i32::try_from(rel).context(".got.plt is too far away from .plt")?;
+
+            content.extend([0xff, 0x25]);
+            content.extend(rel.to_le_bytes());
+            content.resize(content.len() + 10, 0xcc);
+        }
+
+        Ok(content)
+    }
+}
+
+impl<'a> LinkCtxt<'a> {
+    /// Collects the global functions called through `R_X86_64_PLT32` from all live sections.
+    /// Only shared objects need the PLT, since their global functions may be interposed.
+    pub(crate) fn build_plt(&self, shared: bool) -> Result<Plt<'a>> {
+        let mut symbols = IndexSet::new();
+
+        if shared && self.target == Target::X86_64 {
+            for file in &self.elves {
+                let elf = &file.elf;
+                for (rela_sh, rela) in elf.relas()? {
+                    let target = SectionId {
+                        file: file.id,
+                        section: SectionIdx(rela_sh.info.try_into()?),
+                    };
+                    if !self.live.contains(&target) || rela.info.r#type() != c::R_X86_64_PLT32 {
+                        continue;
+                    }
+
+                    let sym = elf.symbol(rela.info.sym())?;
+                    if sym.info.binding() != c::STB_LOCAL {
+                        symbols.insert(elf.string(sym.name)?);
+                    }
+                }
+            }
+        }
+
+        let addr = self
+            .allocated_end()
+            .checked_align_up(DEFAULT_PAGE_ALIGN)
+            .context("no address space left for .plt")?;
+        let got_addr = (addr + symbols.len() as u64 * PLT_ENTRY_SIZE)
+            .checked_align_up(DEFAULT_PAGE_ALIGN)
+            .context("no address space left for .got.plt")?;
+
+        Ok(Plt {
+            addr,
+            got_addr,
+            symbols,
+        })
+    }
+
+    /// The address of the PLT stub to call instead of the symbol, if there is one.
+    pub(crate) fn plt_entry_addr(&self, file: FileId, idx: SymIdx) -> Result<Option<Addr>> {
+        if self.plt.is_empty() {
+            return Ok(None);
+        }
+
+        let elf = &self.elves[file.0].elf;
+        let sym = elf.symbol(idx)?;
+        if sym.info.binding() == c::STB_LOCAL {
+            return Ok(None);
+        }
+        Ok(self.plt.entry_addr(elf.string(sym.name)?))
+    }
+
+    /// The content of `.got.plt`, the address of the function of every entry.
+    pub(crate) fn got_plt_content(&self) -> Result<Vec<u8>> {
+        let mut content = Vec::new();
+
+        for &name in &self.plt.symbols {
+            let addr = self.global_symbol_addr(name)?;
+            content.extend(addr.u64().to_le_bytes());
+        }
+
+        Ok(content)
+    }
+
+    /// The `.got.plt` slots that contain a link time address, with that address.
+    /// Functions that are undefined and resolved to 0 don't move with the load address.
+    pub(crate) fn got_plt_relative_relocations(&self) -> Result<Vec<(Addr, Addr)>> {
+        let mut relocations = Vec::new();
+
+        for (idx, &name) in self.plt.symbols.iter().enumerate() {
+            if self.is_relative_global_symbol(name) {
+                let slot = self.plt.got_addr + idx as u64 * GOT_PLT_ENTRY_SIZE;
+                relocations.push((slot, self.global_symbol_addr(name)?));
+            }
+        }
+
+        Ok(relocations)
+    }
+}
@@ -1,92 +1,237 @@
-use anyhow::Result;
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::{bail, Context, Result};
 use bstr::{BStr, BString};
-use elven_parser::{read::ElfReadError, Addr};
+use elven_parser::{
+    consts::{self as c, SectionIdx, ShFlags, ShType, SHT_NOBITS, SHT_PROGBITS},
+    read::Shdr,
+    Addr,
+};
 use indexmap::IndexMap;
 
-use crate::{utils::AlignExt, ElfFile, FileId, DEFAULT_PAGE_ALIGN};
+use crate::{utils::AlignExt, ElfFile, FileId, SectionId, DEFAULT_PAGE_ALIGN};
 
 #[derive(Debug)]
 pub struct Allocation {
     pub file: FileId,
-    pub section: BString,
+    pub section: SectionIdx,
     pub size: u64,
     pub align: u64,
+    pub nobits: bool,
+    pub flags: ShFlags,
+    pub r#type: ShType,
 }
 
 #[derive(Debug)]
 pub struct SegmentPart {
     pub pad_from_prev: u64,
     pub base: Addr,
-    pub align: u64,
     pub file: FileId,
+    pub section: SectionIdx,
     pub size: u64,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct StorageAllocation {
     pub sections: Vec<AllocatedSection>,
+    /// The address of every linked input section, the same as the `base` of its part.
+    part_addrs: HashMap<SectionId, Addr>,
+}
+
+impl StorageAllocation {
+    /// The address of an input section in the output, if it is linked.
+    pub fn section_va(&self, id: SectionId) -> Option<Addr> {
+        self.part_addrs.get(&id).copied()
+    }
 }
 
 #[derive(Debug)]
 pub struct AllocatedSection {
     pub name: BString,
+    pub addr: Addr,
+    /// The size in memory, including padding between the parts.
+    pub size: u64,
+    /// Whether all parts are zero-initialized and don't need any space in the file.
+    pub nobits: bool,
+    /// The memory flags of all parts combined.
+    pub flags: ShFlags,
+    /// The type of the first part, `.init_array` and `.fini_array` need to keep theirs.
+    pub r#type: ShType,
     pub parts: Vec<SegmentPart>,
 }
 
-pub fn allocate_storage<'a>(base_addr: Addr, files: &[ElfFile<'a>]) -> Result<StorageAllocation> {
+const OUTPUT_SECTIONS: [&[u8]; 7] = [
+    b".text",
+    b".rodata",
+    b".preinit_array",
+    b".init_array",
+    b".fini_array",
+    b".data",
+    b".bss",
+];
+
+/// The output section an input section is placed into, if any.
+/// `.text.foo` goes into `.text` and so on, other sections keep their name.
+fn output_section_name<'a>(name: &'a [u8], sh: &Shdr) -> Option<&'a [u8]> {
+    if !should_link_section(name, sh) {
+        return None;
+    }
+
+    let known = OUTPUT_SECTIONS.into_iter().find(|&output| {
+        name.strip_prefix(output)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(b"."))
+    });
+    Some(known.unwrap_or(name))
+}
+
+/// Whether the section has content for the output, as opposed to metadata for the linker.
+/// Only allocated sections are linked, non-allocated ones like debug info are never loaded.
+fn should_link_section(name: &[u8], sh: &Shdr) -> bool {
+    if matches!(name, b".symtab" | b".strtab" | b".shstrtab") {
+        return false;
+    }
+    // Merged separately, see `eh_frame.rs`.
+    if name == b".eh_frame" {
+        return false;
+    }
+    if matches!(
+        sh.r#type.0,
+        c::SHT_NULL | c::SHT_SYMTAB | c::SHT_STRTAB | c::SHT_RELA | c::SHT_REL | c::SHT_GROUP
+    ) {
+        return false;
+    }
+    // Thread local storage needs a PT_TLS segment, which isn't written yet.
+    sh.flags.contains(ShFlags::SHF_ALLOC) && !sh.flags.contains(ShFlags::SHF_TLS)
+}
+
+/// All parts of an output section need the same type, for example one object can't have a
+/// `SHT_NOBITS` `.data` when another one has `.data` with content.
+fn check_section_types(name: &BStr, parts: &[Allocation]) -> Result<()> {
+    let Some(first) = parts.first() else {
+        return Ok(());
+    };
+    if let Some(conflict) = parts.iter().find(|part| part.r#type != first.r#type) {
+        bail!(
+            "conflicting section types for {name}: {} in {:?} and {} in {:?}",
+            first.r#type,
+            first.file,
+            conflict.r#type,
+            conflict.file
+        );
+    }
+    Ok(())
+}
+
+pub fn allocate_storage<'a>(
+    base_addr: Addr,
+    files: &[ElfFile<'a>],
+    live: &BTreeSet<SectionId>,
+) -> Result<StorageAllocation> {
     let mut allocs = IndexMap::<_, Vec<Allocation>>::new();
+    for name in OUTPUT_SECTIONS {
+        allocs.insert(BStr::new(name), Vec::new());
+    }
 
     for file in files {
-        let elf = file.elf;
-
-        for name in [b".text".as_slice(), b".data", b".bss"] {
-            let section = elf.section_header_by_name(name);
-            match section {
-                Ok(section) => {
-                    allocs.entry(BStr::new(name)).or_default().push(Allocation {
-                        file: file.id,
-                        section: name.into(),
-                        size: section.size,
-                        align: section.addralign,
-                    });
-                }
-                Err(ElfReadError::NotFoundByName(_, _)) => {}
-                Err(e) => return Err(e.into()),
+        let elf = &file.elf;
+
+        for (section_idx, section) in elf.section_headers_with_idx()? {
+            if !live.contains(&SectionId {
+                file: file.id,
+                section: section_idx,
+            }) {
+                continue;
             }
+
+            let Some(name) = output_section_name(elf.sh_string(section.name)?, section) else {
+                continue;
+            };
+
+            allocs.entry(BStr::new(name)).or_default().push(Allocation {
+                file: file.id,
+                section: section_idx,
+                size: section.size,
+                align: section.addralign,
+                nobits: section.r#type == SHT_NOBITS,
+                flags: section.flags
+                    & (ShFlags::SHF_ALLOC | ShFlags::SHF_WRITE | ShFlags::SHF_EXECINSTR),
+                r#type: section.r#type,
+            });
         }
     }
 
+    // The parts of every output section are in input file order, which keeps the layout
+    // reproducible.
     debug!(?allocs, "Allocation pass one completed");
 
+    for (name, parts) in &allocs {
+        check_section_types(name, parts)?;
+    }
+
     let mut current_addr = base_addr;
     let mut section_parts = Vec::new();
+    let mut part_addrs = HashMap::new();
     for section in allocs {
         let mut segment_parts = Vec::new();
 
-        current_addr = current_addr.align_up(DEFAULT_PAGE_ALIGN);
+        current_addr = current_addr
+            .checked_align_up(DEFAULT_PAGE_ALIGN)
+            .with_context(|| format!("no address space left for {}", section.0))?;
+        let section_addr = current_addr;
+        let nobits = section.1.iter().all(|alloc| alloc.nobits);
+        let flags = section
+            .1
+            .iter()
+            .fold(ShFlags::empty(), |flags, alloc| flags | alloc.flags);
+        let r#type = section
+            .1
+            .first()
+            .map_or(ShType(SHT_PROGBITS), |alloc| alloc.r#type);
         for alloc in section.1 {
-            let align = alloc.align;
-            let addr = current_addr.align_up(align);
+            // Input files are untrusted, so don't let their sizes and alignments wrap around.
+            // An alignment of zero means no alignment, just like one.
+            let align = alloc.align.max(1);
+            let addr = current_addr.checked_align_up(align).with_context(|| {
+                format!(
+                    "invalid alignment {align} or no address space left for {}",
+                    section.0
+                )
+            })?;
             let pad = addr.u64() - current_addr.u64();
 
-            current_addr = addr + alloc.size;
+            current_addr = addr
+                .checked_add(alloc.size)
+                .with_context(|| format!("no address space left for {}", section.0))?;
 
+            part_addrs.insert(
+                SectionId {
+                    file: alloc.file,
+                    section: alloc.section,
+                },
+                addr,
+            );
             segment_parts.push(SegmentPart {
                 pad_from_prev: pad,
                 base: addr,
-                align: align,
                 file: alloc.file,
+                section: alloc.section,
                 size: alloc.size,
             });
         }
 
         section_parts.push(AllocatedSection {
             name: section.0.to_owned(),
+            addr: section_addr,
+            size: current_addr.u64() - section_addr.u64(),
+            nobits,
+            flags,
+            r#type,
             parts: segment_parts,
         })
     }
 
     Ok(StorageAllocation {
         sections: section_parts,
+        part_addrs,
     })
 }
@@ -1,32 +1,50 @@
 use elven_parser::Addr;
 
 pub trait AlignExt<T>: Copy {
-    fn align_down(self, align: T) -> Self;
     fn align_up(self, align: T) -> Self;
+    /// Like `align_up`, but `None` if the result overflows or `align` is not a power of two.
+    fn checked_align_up(self, align: T) -> Option<Self>;
 }
 
 impl AlignExt<u64> for u64 {
-    fn align_down(self, align: Self) -> Self {
-        assert!(align.is_power_of_two() && align > 0);
-        // We want to set all the aligment bits to zero.
-        // 0b0101 aligned to 0b0100 => 0b0100
-        // mask is !0b0011 = 0b1100
-        let mask = !(align - 1);
-        self & mask
-    }
     fn align_up(self, align: Self) -> Self {
         assert!(align.is_power_of_two() && align > 0);
         // 0b0101 aligned to 0b0100 => 0b1000
         (self + align - 1) & !(align - 1)
     }
+    fn checked_align_up(self, align: Self) -> Option<Self> {
+        if !align.is_power_of_two() {
+            return None;
+        }
+        Some(self.checked_add(align - 1)? & !(align - 1))
+    }
 }
 
 impl AlignExt<u64> for Addr {
-    fn align_down(self, align: u64) -> Self {
-        Addr(self.u64().align_down(align))
-    }
-
     fn align_up(self, align: u64) -> Self {
         Addr(self.u64().align_up(align))
     }
+
+    fn checked_align_up(self, align: u64) -> Option<Self> {
+        self.u64().checked_align_up(align).map(Addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use elven_parser::Addr;
+
+    use super::AlignExt;
+
+    #[test]
+    fn checked_align_up() {
+        assert_eq!(Addr(0x1001).checked_align_up(0x1000), Some(Addr(0x2000)));
+        assert_eq!(Addr(0x1000).checked_align_up(1), Some(Addr(0x1000)));
+        assert_eq!(Addr(u64::MAX - 2).checked_align_up(8), None);
+        assert_eq!(Addr(0x1001).checked_align_up(3), None);
+        assert_eq!(Addr(0x1001).checked_align_up(0), None);
+
+        assert_eq!(Addr(u64::MAX).checked_add(1), None);
+        assert_eq!(Addr(0).checked_sub(1), None);
+    }
 }
@@ -0,0 +1,206 @@
+//! Version scripts from `--version-script`, which decide which global symbols are exported.
+//!
+//! Only the `global:` and `local:` lists are supported. The names of the version nodes are
+//! accepted but ignored, since no symbol versions are written.
+//!
+//! See <https://sourceware.org/binutils/docs/ld/VERSION.html>.
+
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct VersionScript {
+    /// The patterns of the symbols that are exported.
+    global: Vec<String>,
+    /// The patterns of the symbols that are hidden, usually just `*`.
+    local: Vec<String>,
+}
+
+impl VersionScript {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("reading version script {}", path.display()))?;
+        Self::parse(&content).with_context(|| format!("parsing version script {}", path.display()))
+    }
+
+    pub fn parse(content: &str) -> Result<Self> {
+        let tokens = tokenize(content)?;
+        let mut tokens = tokens.iter().map(String::as_str).peekable();
+        let mut script = Self::default();
+
+        while let Some(token) = tokens.next() {
+            // Anonymous version nodes start with the brace directly.
+            if token != "{" && tokens.next() != Some("{") {
+                bail!("expected {{ after version node {token}");
+            }
+
+            // Symbols before the first `global:` or `local:` are global.
+            let mut global = true;
+            loop {
+                match tokens.next() {
+                    Some("}") => break,
+                    Some("global") if tokens.peek() == Some(&":") => {
+                        tokens.next();
+                        global = true;
+                    }
+                    Some("local") if tokens.peek() == Some(&":") => {
+                        tokens.next();
+                        global = false;
+                    }
+                    Some("extern") => bail!("extern blocks are not supported"),
+                    Some(pattern @ ("{" | ";" | ":")) => bail!("unexpected {pattern}"),
+                    Some(pattern) => {
+                        if tokens.next() != Some(";") {
+                            bail!("expected ; after {pattern}");
+                        }
+                        let list = if global {
+                            &mut script.global
+                        } else {
+                            &mut script.local
+                        };
+                        list.push(pattern.to_owned());
+                    }
+                    None => bail!("unterminated version node"),
+                }
+            }
+
+            // The versions this node depends on, which don't matter without symbol versions.
+            loop {
+                match tokens.next() {
+                    Some(";") => break,
+                    Some(token @ ("{" | "}" | ":")) => bail!("unexpected {token}"),
+                    Some(_) => {}
+                    None => bail!("expected ; after version node"),
+                }
+            }
+        }
+
+        Ok(script)
+    }
+
+    /// Whether a global symbol stays exported. Like ld, exact names win over wildcards, and
+    /// symbols that aren't matched at all are exported.
+    pub fn is_exported(&self, name: &[u8]) -> bool {
+        let exact = |patterns: &[String]| patterns.iter().any(|p| p.as_bytes() == name);
+        let wildcard =
+            |patterns: &[String]| patterns.iter().any(|p| glob_match(p.as_bytes(), name));
+
+        if exact(&self.global) {
+            true
+        } else if exact(&self.local) {
+            false
+        } else if wildcard(&self.global) {
+            true
+        } else {
+            !wildcard(&self.local)
+        }
+    }
+}
+
+/// Splits the script into words and the punctuation `{`, `}`, `;` and `:`, without comments.
+fn tokenize(content: &str) -> Result<Vec<String>> {
+    let is_punct = |c: char| matches!(c, '{' | '}' | ';' | ':');
+    let mut tokens = Vec::new();
+    let mut rest = content.trim_start();
+
+    while !rest.is_empty() {
+        if let Some(comment) = rest.strip_prefix('#') {
+            rest = comment.find('\n').map_or("", |end| &comment[end..]);
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            let end = comment.find("*/").context("unterminated comment")?;
+            rest = &comment[(end + 2)..];
+        } else if rest.starts_with(is_punct) {
+            tokens.push(rest[..1].to_owned());
+            rest = &rest[1..];
+        } else {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || is_punct(c) || c == '#')
+                .unwrap_or(rest.len());
+            tokens.push(rest[..end].to_owned());
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+/// Matches shell style patterns with `*` and `?`.
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|skip| glob_match(rest, &name[skip..])),
+        Some((b'?', rest)) => !name.is_empty() && glob_match(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && glob_match(rest, &name[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{glob_match, VersionScript};
+
+    #[test]
+    fn anonymous_node() {
+        let script = VersionScript::parse("{ global: foo; bar_*; local: *; };").unwrap();
+        assert_eq!(script.global, ["foo", "bar_*"]);
+        assert_eq!(script.local, ["*"]);
+
+        assert!(script.is_exported(b"foo"));
+        assert!(script.is_exported(b"bar_baz"));
+        assert!(!script.is_exported(b"foobar"));
+        assert!(!script.is_exported(b"internal"));
+    }
+
+    #[test]
+    fn named_nodes_and_comments() {
+        let script = VersionScript::parse(
+            "
+            # The first version.
+            VERS_1.0 {
+                global:
+                    foo; /* Exported since forever. */
+                local:
+                    hidden;
+            };
+            VERS_2.0 {
+                bar;
+            } VERS_1.0;
+            ",
+        )
+        .unwrap();
+        assert_eq!(script.global, ["foo", "bar"]);
+        assert_eq!(script.local, ["hidden"]);
+
+        assert!(script.is_exported(b"foo"));
+        assert!(script.is_exported(b"bar"));
+        assert!(!script.is_exported(b"hidden"));
+        // Not mentioned at all, so it stays exported.
+        assert!(script.is_exported(b"other"));
+    }
+
+    #[test]
+    fn exact_names_win() {
+        let script = VersionScript::parse("{ global: lib_*; local: lib_private; };").unwrap();
+        assert!(script.is_exported(b"lib_public"));
+        assert!(!script.is_exported(b"lib_private"));
+    }
+
+    #[test]
+    fn invalid_scripts() {
+        VersionScript::parse("{ global: foo }").unwrap_err();
+        VersionScript::parse("{ global: foo; }").unwrap_err();
+        VersionScript::parse("VERS_1.0 global: foo; };").unwrap_err();
+        VersionScript::parse("{ extern \"C++\" { foo::bar; }; };").unwrap_err();
+        VersionScript::parse("{ foo; /* unterminated }; ").unwrap_err();
+    }
+
+    #[test]
+    fn globs() {
+        assert!(glob_match(b"*", b""));
+        assert!(glob_match(b"a*c", b"abbc"));
+        assert!(glob_match(b"a?c", b"abc"));
+        assert!(!glob_match(b"a?c", b"ac"));
+        assert!(!glob_match(b"abc", b"abcd"));
+    }
+}
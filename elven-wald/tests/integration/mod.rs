@@ -18,6 +18,17 @@ pub fn run(mut cmd: Command) {
     }
 }
 
+pub fn run_exit_code(mut cmd: Command) -> i32 {
+    let out = cmd.output().expect("failed to spawn command");
+    out.status.code().unwrap_or_else(|| {
+        panic!(
+            "{} was terminated by a signal: {}",
+            cmd.get_program().to_str().unwrap(),
+            out.status
+        )
+    })
+}
+
 macro_rules! elven_wald {
     ($ctx:expr; $($args:expr),*) => {{
         let ctx = &$ctx;
@@ -27,11 +38,43 @@ macro_rules! elven_wald {
         cmd.arg(&output);
         $( cmd.arg($args); )*
         $crate::integration::run(cmd);
+        $crate::integration::check_segments(&output);
         output
     }};
 }
 pub(crate) use elven_wald;
 
+/// Like `elven_wald!`, but expects the link to fail and returns what it printed to stderr.
+macro_rules! elven_wald_fails {
+    ($ctx:expr; $($args:expr),*) => {{
+        let ctx = &$ctx;
+        let mut cmd = std::process::Command::new("../target/debug/elven-wald");
+        cmd.arg("-o");
+        cmd.arg(ctx.file_ref("elven-wald-output"));
+        $( cmd.arg($args); )*
+        let out = cmd.output().expect("failed to spawn elven-wald");
+        assert!(!out.status.success(), "linking succeeded unexpectedly");
+        String::from_utf8_lossy(&out.stderr).into_owned()
+    }};
+}
+pub(crate) use elven_wald_fails;
+
+/// Makes sure that every allocated section of a linked file is mapped by a `PT_LOAD` segment.
+pub fn check_segments(path: &File) {
+    let data = std::fs::read(&path.0).expect("failed to read the output");
+    let elf = elven_parser::read::ElfReader::new(&data).expect("invalid output");
+    let uncovered = elf
+        .sections_outside_load_segments()
+        .expect("invalid output")
+        .into_iter()
+        .map(|(_, sh)| elf.sh_string(sh.name).unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert!(
+        uncovered.is_empty(),
+        "sections outside of PT_LOAD segments: {uncovered:?}"
+    );
+}
+
 pub struct Ctx {
     _tempdir: tempfile::TempDir,
     path: PathBuf,
@@ -93,4 +136,44 @@ impl Ctx {
         run(cmd);
         File(out)
     }
+
+    /// Creates a GNU `ar` archive by hand, without a symbol index.
+    pub fn archive(&self, filename: &str, members: &[&File]) -> File {
+        let mut content = b"!<arch>\n".to_vec();
+        for member in members {
+            let data = std::fs::read(&member.0).expect("failed to read archive member");
+            let name = format!("{}/", member.0.file_name().unwrap().to_str().unwrap());
+            assert!(name.len() <= 16, "archive member name {name} is too long");
+
+            let header = format!(
+                "{name:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+                0,
+                0,
+                0,
+                644,
+                data.len()
+            );
+            content.extend(header.as_bytes());
+            content.extend(&data);
+            if data.len() % 2 == 1 {
+                content.push(b'\n');
+            }
+        }
+
+        let out = self.path.join(filename);
+        std::fs::write(&out, content).expect("failed to write archive");
+        File(out)
+    }
+
+    /// Assembles GNU `as` syntax, for things that nasm cannot express.
+    pub fn gnu_as(&self, filename: &str, content: &str) -> File {
+        let input = self.file(&format!("{filename}.s"), content);
+        let out = self.path.join(filename);
+        let mut cmd = Command::new("as");
+        cmd.args(["--64", "-o"]);
+        cmd.arg(&out);
+        cmd.arg(input);
+        run(cmd);
+        File(out)
+    }
 }
@@ -1,8 +1,14 @@
 use std::process::Command;
 
+use elven_parser::{
+    consts::{self as c, PhFlags},
+    eh_frame::EhFrameEntry,
+    read::ElfReader,
+};
+
 use crate::prelude::*;
 
-use super::run;
+use super::{run, run_exit_code, File};
 
 #[test]
 fn single_object_file() {
@@ -54,3 +60,1257 @@ fn two_object_files() {
     let out = elven_wald!(ctx; start, exit);
     run(Command::new(out));
 }
+
+#[test]
+fn call_returns_value() {
+    let ctx = ctx();
+
+    let start = ctx.nasm(
+        "start",
+        "
+        global _start
+        extern get_code
+
+        section .text
+        _start:
+            call get_code
+            mov rdi, rax
+            mov rax, 60
+            syscall
+    ",
+    );
+    let get_code = ctx.nasm(
+        "get_code",
+        "
+        global get_code
+        section .text
+        get_code:
+            mov rax, 42
+            ret
+    ",
+    );
+
+    let out = elven_wald!(ctx; start, get_code);
+    assert_eq!(run_exit_code(Command::new(out)), 42);
+}
+
+#[test]
+fn absolute_data_pointer() {
+    let ctx = ctx();
+
+    let start = ctx.nasm(
+        "start",
+        "
+        global _start
+
+        section .data
+        value:
+            dq 42
+        pointer:
+            dq value
+
+        section .text
+        _start:
+            mov rax, [pointer]
+            mov rdi, [rax]
+            mov rax, 60
+            syscall
+    ",
+    );
+
+    let out = elven_wald!(ctx; start);
+    assert_eq!(run_exit_code(Command::new(out)), 42);
+}
+
+#[test]
+fn absolute_address_above_2gib_does_not_fit() {
+    let ctx = ctx();
+
+    let start = ctx.gnu_as(
+        "start",
+        "
+        .globl _start
+        .text
+        _start:
+            movq far_away, %rdi
+            mov $60, %rax
+            syscall
+    ",
+    );
+    let far_away = ctx.gnu_as(
+        "far_away",
+        "
+        .globl far_away
+        .set far_away, 0x90000000
+    ",
+    );
+
+    let stderr = elven_wald_fails!(ctx; &start, &far_away);
+    assert!(stderr.contains("R_X86_64_32S"), "{stderr}");
+    assert!(stderr.contains("does not fit"), "{stderr}");
+}
+
+#[test]
+fn comdat_group_is_deduplicated() {
+    let ctx = ctx();
+
+    let start = ctx.gnu_as(
+        "start",
+        "
+        .globl _start
+        .text
+        _start:
+            call shared_fn
+            mov %rax, %rdi
+            mov $60, %rax
+            syscall
+
+        .section .text.shared_fn,\"axG\",@progbits,shared_fn,comdat
+        .globl shared_fn
+        shared_fn:
+            mov $42, %rax
+            ret
+    ",
+    );
+    let other = ctx.gnu_as(
+        "other",
+        "
+        .section .text.shared_fn,\"axG\",@progbits,shared_fn,comdat
+        .globl shared_fn
+        shared_fn:
+            mov $43, %rax
+            ret
+    ",
+    );
+
+    let out = elven_wald!(ctx; start, other);
+    assert_eq!(run_exit_code(Command::new(out)), 42);
+}
+
+#[test]
+fn archive_pulls_in_needed_member() {
+    let ctx = ctx();
+
+    let start = ctx.nasm(
+        "start",
+        "
+        global _start
+        extern get_value
+
+        section .text
+        _start:
+            call get_value
+            mov rdi, rax
+            mov rax, 60
+            syscall
+    ",
+    );
+    let value = ctx.nasm(
+        "value.o",
+        "
+        global get_value
+        section .text
+        get_value:
+            mov rax, 42
+            ret
+    ",
+    );
+    // Would cause a duplicate definition of `_start` if it was pulled in.
+    let unused = ctx.nasm(
+        "unused.o",
+        "
+        global _start
+        global unused
+        section .text
+        _start:
+        unused:
+            ret
+    ",
+    );
+    let archive = ctx.archive("libvalue.a", &[&unused, &value]);
+
+    let out = elven_wald!(ctx; start, archive);
+    assert_eq!(run_exit_code(Command::new(out)), 42);
+}
+
+#[test]
+fn library_is_found_in_search_path() {
+    let ctx = ctx();
+
+    let start = ctx.nasm(
+        "start",
+        "
+        global _start
+        extern get_value
+
+        section .text
+        _start:
+            call get_value
+            mov rdi, rax
+            mov rax, 60
+            syscall
+    ",
+    );
+    let value = ctx.nasm(
+        "value.o",
+        "
+        global get_value
+        section .text
+        get_value:
+            mov rax, 42
+            ret
+    ",
+    );
+    let libs = ctx.file_ref("libs");
+    std::fs::create_dir(&libs.0).unwrap();
+    ctx.archive("libs/libvalue.a", &[&value]);
+
+    let out = elven_wald!(ctx; start, "-L", libs, "-lvalue");
+    assert_eq!(run_exit_code(Command::new(out)), 42);
+}
+
+#[test]
+fn strong_definition_wins_over_weak() {
+    let ctx = ctx();
+
+    let start = ctx.nasm(
+        "start",
+        "
+        global _start
+        extern get_value
+
+        section .text
+        _start:
+            call get_value
+            mov rdi, rax
+            mov rax, 60
+            syscall
+    ",
+    );
+    let weak = ctx.gnu_as(
+        "weak",
+        "
+        .weak get_value
+        .text
+        get_value:
+            mov $1, %rax
+            ret
+    ",
+    );
+    let strong = ctx.nasm(
+        "strong",
+        "
+        global get_value
+        section .text
+        get_value:
+            mov rax, 42
+            ret
+    ",
+    );
+
+    let out = elven_wald!(ctx; &start, &weak, &strong);
+    assert_eq!(run_exit_code(Command::new(out)), 42);
+
+    let out = elven_wald!(ctx; &start, &strong, &weak);
+    assert_eq!(run_exit_code(Command::new(out)), 42);
+}
+
+#[test]
+fn bss_is_zeroed_and_writable() {
+    let ctx = ctx();
+
+    let start = ctx.nasm(
+        "start",
+        "
+        global _start
+
+        section .bss
+        zeroed: resq 1
+        buffer: resb 0x2000
+
+        section .text
+        _start:
+            mov rax, [zeroed]
+            add rax, 42
+            mov [buffer + 0x1fff], al
+            movzx rdi, byte [buffer + 0x1fff]
+            mov rax, 60
+            syscall
+    ",
+    );
+
+    let out = elven_wald!(ctx; start);
+    assert_eq!(run_exit_code(Command::new(out)), 42);
+}
+
+#[test]
+fn stack_is_executable_only_when_requested() {
+    let ctx = ctx();
+
+    let start = ctx.nasm(
+        "start",
+        "
+        global _start
+        section .text
+        _start:
+            mov rax, 60
+            mov rdi, 0
+            syscall
+    ",
+    );
+    let exec_stack = ctx.gnu_as(
+        "exec_stack",
+        "
+        .globl noop
+        .text
+        noop:
+            ret
+
+        .section .note.GNU-stack,\"x\",@progbits
+    ",
+    );
+
+    let stack_flags = |out: &super::File| {
+        let data = std::fs::read(out.to_string()).unwrap();
+        let elf = ElfReader::new(&data).unwrap();
+        elf.gnu_stack_flags().unwrap()
+    };
+
+    let out = elven_wald!(ctx; &start);
+    run(Command::new(&out));
+    assert_eq!(stack_flags(&out), Some(PhFlags::PF_R | PhFlags::PF_W));
+
+    let out = elven_wald!(ctx; &start, &exec_stack);
+    run(Command::new(&out));
+    assert_eq!(
+        stack_flags(&out),
+        Some(PhFlags::PF_R | PhFlags::PF_W | PhFlags::PF_X)
+    );
+}
+
+#[test]
+fn linker_map_lists_symbols() {
+    let ctx = ctx();
+
+    let start = ctx.nasm(
+        "start",
+        "
+        global _start
+        section .text
+        _start:
+            mov rax, 60
+            mov rdi, 0
+            syscall
+    ",
+    );
+    let map = ctx.file_ref("output.map");
+
+    let out = elven_wald!(ctx; format!("--Map={map}"), start);
+    run(Command::new(out));
+
+    let map = std::fs::read_to_string(map.to_string()).unwrap();
+    assert!(map.lines().any(|line| line.starts_with(".text ")), "{map}");
+
+    let start_line = map
+        .lines()
+        .find(|line| line.starts_with("_start "))
+        .unwrap_or_else(|| panic!("_start not in map: {map}"));
+    let addr = start_line.split_whitespace().nth(1).unwrap();
+    let addr = u64::from_str_radix(addr.trim_start_matches("0x"), 16).unwrap();
+    assert_ne!(addr, 0);
+}
+
+#[test]
+fn gc_sections_removes_unused_function() {
+    let ctx = ctx();
+
+    let start = ctx.nasm(
+        "start",
+        "
+        global _start
+
+        section .text.start progbits alloc exec nowrite
+        _start:
+            call used
+            mov rdi, rax
+            mov rax, 60
+            syscall
+
+        section .text.used progbits alloc exec nowrite
+        used:
+            mov rax, 42
+            ret
+
+        section .text.unused progbits alloc exec nowrite
+        unused:
+            mov rax, 0x1122334455667788
+            ret
+    ",
+    );
+    let unused_code = 0x1122334455667788_u64.to_le_bytes();
+    let contains_unused = |out: &super::File| {
+        let data = std::fs::read(out.to_string()).unwrap();
+        data.windows(unused_code.len())
+            .any(|window| window == unused_code)
+    };
+
+    let out = elven_wald!(ctx; &start);
+    assert!(contains_unused(&out));
+
+    let out = elven_wald!(ctx; "--gc-sections", &start);
+    assert!(!contains_unused(&out));
+    assert_eq!(run_exit_code(Command::new(&out)), 42);
+}
+
+#[test]
+fn init_array_is_kept() {
+    let ctx = ctx();
+
+    let start = ctx.nasm(
+        "start",
+        "
+        global _start
+
+        section .init_array
+        init_array: dq init
+
+        section .text.start progbits alloc exec nowrite
+        _start:
+            call qword [init_array]
+            mov rdi, rax
+            mov rax, 60
+            syscall
+
+        section .text.init progbits alloc exec nowrite
+        init:
+            mov rax, 42
+            ret
+    ",
+    );
+
+    let out = elven_wald!(ctx; "--gc-sections", &start);
+    assert_eq!(run_exit_code(Command::new(&out)), 42);
+
+    let data = std::fs::read(out.to_string()).unwrap();
+    let elf = ElfReader::new(&data).unwrap();
+    assert_eq!(elf.init_array().unwrap().len(), 1);
+}
+
+#[test]
+fn shared_object_is_dyn() {
+    let ctx = ctx();
+
+    let value = ctx.nasm(
+        "value",
+        "
+        global get_value
+        section .text
+        get_value:
+            mov rax, 42
+            ret
+    ",
+    );
+
+    let out = elven_wald!(ctx; value, "-shared", "-soname", "libvalue.so.1");
+
+    let data = std::fs::read(out.to_string()).unwrap();
+    let elf = ElfReader::new(&data).unwrap();
+    assert_eq!(elf.header().unwrap().r#type, c::ET_DYN);
+    assert!(elf
+        .program_headers()
+        .unwrap()
+        .iter()
+        .any(|ph| ph.r#type == c::PT_DYNAMIC));
+
+    let soname = elf.dyn_entry_by_tag(c::DT_SONAME).unwrap();
+    let dynstr = elf.section_header_by_name(b".dynstr").unwrap();
+    let strings = elf.section_content(dynstr).unwrap();
+    let soname = &strings[soname.val as usize..];
+    assert!(soname.starts_with(b"libvalue.so.1\0"));
+}
+
+#[test]
+fn version_script_hides_unexported_symbols() {
+    let ctx = ctx();
+
+    let value = ctx.nasm(
+        "value",
+        "
+        global get_value, helper
+        section .text
+        get_value:
+            call helper
+            ret
+        helper:
+            mov rax, 42
+            ret
+    ",
+    );
+    let script = ctx.file("exports.map", "{ global: get_value; local: *; };");
+
+    let out = elven_wald!(ctx; value, "-shared", "--version-script", script);
+
+    let data = std::fs::read(out.to_string()).unwrap();
+    let elf = ElfReader::new(&data).unwrap();
+
+    let get_value = elf.symbol_by_name(b"get_value").unwrap();
+    assert_eq!(get_value.info.binding(), c::STB_GLOBAL);
+    assert_eq!(get_value.other, c::STV_DEFAULT);
+
+    let helper = elf.symbol_by_name(b"helper").unwrap();
+    assert_eq!(helper.info.binding(), c::STB_LOCAL);
+    assert_eq!(helper.other, c::STV_HIDDEN);
+}
+
+#[test]
+fn undefined_symbol_is_reported() {
+    let ctx = ctx();
+
+    let start = ctx.nasm(
+        "start",
+        "
+        global _start
+        extern missing
+        extern also_missing
+
+        section .text
+        _start:
+            call missing
+            call also_missing
+            mov rax, 60
+            mov rdi, 0
+            syscall
+    ",
+    );
+
+    let stderr = elven_wald_fails!(ctx; &start);
+    assert!(stderr.contains("\n  missing, referenced by"), "{stderr}");
+    assert!(stderr.contains("also_missing, referenced by"), "{stderr}");
+    assert!(stderr.contains("start"), "{stderr}");
+
+    elven_wald!(ctx; &start, "--allow-undefined");
+}
+
+#[test]
+fn duplicate_definition_names_both_files() {
+    let ctx = ctx();
+
+    let first = ctx.nasm(
+        "first",
+        "
+        global _start
+        section .text
+        _start:
+            ret
+    ",
+    );
+    let second = ctx.nasm(
+        "second",
+        "
+        global _start
+        section .text
+        _start:
+            ret
+    ",
+    );
+
+    let stderr = elven_wald_fails!(ctx; &first, &second);
+    assert!(
+        stderr.contains("duplicate definition for symbol _start"),
+        "{stderr}"
+    );
+    assert!(stderr.contains(&first.to_string()), "{stderr}");
+    assert!(stderr.contains(&second.to_string()), "{stderr}");
+}
+
+#[test]
+fn custom_sections_are_linked() {
+    let ctx = ctx();
+
+    let start = ctx.nasm(
+        "start",
+        "
+        global _start
+
+        section .custom
+        value:
+            dq 42
+
+        section .text
+        _start:
+            mov rdi, [rel value]
+            mov rax, 60
+            syscall
+    ",
+    );
+
+    let out = elven_wald!(ctx; &start);
+    let data = std::fs::read(out.to_string()).unwrap();
+    let elf = ElfReader::new(&data).unwrap();
+    let custom = elf.section_header_by_name(b".custom").unwrap();
+    assert_eq!(elf.section_content(custom).unwrap(), 42_u64.to_le_bytes());
+
+    assert_eq!(run_exit_code(Command::new(out)), 42);
+}
+
+#[test]
+fn conflicting_section_types_are_rejected() {
+    let ctx = ctx();
+
+    let start = ctx.nasm(
+        "start",
+        "
+        global _start
+
+        section .data
+            dq 1
+
+        section .text
+        _start:
+            mov rax, 60
+            syscall
+    ",
+    );
+    let zeroed = ctx.nasm(
+        "zeroed",
+        "
+        global zero
+
+        section .data.zeroed nobits alloc write noexec
+        zero:
+            resq 1
+    ",
+    );
+
+    let stderr = elven_wald_fails!(ctx; &start, &zeroed);
+    assert!(
+        stderr.contains("conflicting section types for .data"),
+        "{stderr}"
+    );
+    assert!(stderr.contains("SHT_NOBITS"), "{stderr}");
+    assert!(stderr.contains("zeroed"), "{stderr}");
+}
+
+#[test]
+fn local_symbols_do_not_collide() {
+    let ctx = ctx();
+
+    let start = ctx.nasm(
+        "start",
+        "
+        global _start
+        extern get_value
+
+        section .data
+        value:
+            dq 1
+
+        section .text
+        _start:
+            call get_value
+            add rax, [rel value]
+            mov rdi, rax
+            mov rax, 60
+            syscall
+    ",
+    );
+    let get_value = ctx.nasm(
+        "get_value",
+        "
+        global get_value
+
+        section .data
+        value:
+            dq 41
+
+        section .text
+        get_value:
+            mov rax, [rel value]
+            ret
+    ",
+    );
+
+    let out = elven_wald!(ctx; start, get_value);
+    assert_eq!(run_exit_code(Command::new(out)), 42);
+}
+
+#[test]
+fn symbol_table_is_written() {
+    let ctx = ctx();
+
+    let start = ctx.gnu_as(
+        "start",
+        "
+        .globl _start
+        .text
+        _start:
+            mov $60, %rax
+            mov $0, %rdi
+            syscall
+
+        .section .debug_info, \"\", @progbits
+            .byte 1, 2, 3, 4
+    ",
+    );
+    let input = std::fs::read(start.to_string()).unwrap();
+    let input = ElfReader::new(&input).unwrap();
+    assert!(input.section_header_by_name(b".debug_info").is_ok());
+
+    let out = elven_wald!(ctx; &start);
+    run(Command::new(&out));
+
+    let data = std::fs::read(out.to_string()).unwrap();
+    let elf = ElfReader::new(&data).unwrap();
+    // Debug info is never linked, with or without --strip-debug.
+    assert!(elf.section_header_by_name(b".debug_info").is_err());
+    let start_sym = elf
+        .symbols()
+        .unwrap()
+        .iter()
+        .find(|sym| elf.string(sym.name).unwrap() == "_start")
+        .unwrap();
+    assert_eq!(start_sym.value, elf.header().unwrap().entry);
+    assert_ne!(start_sym.value.u64(), 0);
+
+    let out = elven_wald!(ctx; &start, "--strip-debug");
+    let debug_stripped = std::fs::read(out.to_string()).unwrap();
+    let elf = ElfReader::new(&debug_stripped).unwrap();
+    assert!(elf.section_header_by_name(b".debug_info").is_err());
+    assert!(elf.section_header_by_type_first(c::SHT_SYMTAB).is_ok());
+
+    let out = elven_wald!(ctx; &start, "--strip-all");
+    run(Command::new(&out));
+    let stripped = std::fs::read(out.to_string()).unwrap();
+    let elf = ElfReader::new(&stripped).unwrap();
+    assert!(elf.section_header_by_type_first(c::SHT_SYMTAB).is_err());
+    assert!(stripped.len() < data.len());
+}
+
+#[test]
+fn build_id_note_is_written() {
+    let ctx = ctx();
+
+    let exit = |code| {
+        ctx.nasm(
+            &format!("exit_{code}"),
+            &format!(
+                "
+        global _start
+        section .text
+        _start:
+            mov rax, 60
+            mov rdi, {code}
+            syscall
+    "
+            ),
+        )
+    };
+    let exit_0 = exit(0);
+
+    let build_id = |out: String| {
+        let data = std::fs::read(out).unwrap();
+        let elf = ElfReader::new(&data).unwrap();
+        assert!(elf
+            .program_headers()
+            .unwrap()
+            .iter()
+            .any(|ph| ph.r#type == c::PT_NOTE));
+        elf.gnu_build_id().unwrap().map(<[u8]>::to_vec)
+    };
+
+    let out = elven_wald!(ctx; &exit_0, "--build-id");
+    run(Command::new(&out));
+    let sha1 = build_id(out.to_string()).unwrap();
+    assert_eq!(sha1.len(), 20);
+    assert_ne!(sha1, [0; 20]);
+
+    let out = elven_wald!(ctx; &exit_0, "--build-id=sha1");
+    assert_eq!(build_id(out.to_string()).unwrap(), sha1);
+
+    let out = elven_wald!(ctx; &exit(1), "--build-id");
+    assert_ne!(build_id(out.to_string()).unwrap(), sha1);
+
+    let out = elven_wald!(ctx; &exit_0, "--build-id=md5");
+    assert_eq!(build_id(out.to_string()).unwrap().len(), 16);
+
+    let out = elven_wald!(ctx; &exit_0, "--build-id=uuid");
+    assert_eq!(build_id(out.to_string()).unwrap().len(), 16);
+}
+
+#[test]
+fn init_array_bounds_are_defined() {
+    let ctx = ctx();
+
+    let start = ctx.nasm(
+        "start",
+        "
+        global _start
+        extern __init_array_start, __init_array_end
+
+        section .init_array
+            dq init
+
+        section .data
+        value:
+            dq 0
+
+        section .text
+        init:
+            add qword [rel value], 42
+            ret
+
+        _start:
+            lea rbx, [rel __init_array_start]
+            lea r12, [rel __init_array_end]
+        next:
+            cmp rbx, r12
+            je done
+            call qword [rbx]
+            add rbx, 8
+            jmp next
+        done:
+            mov rdi, [rel value]
+            mov rax, 60
+            syscall
+    ",
+    );
+
+    let out = elven_wald!(ctx; start);
+    assert_eq!(run_exit_code(Command::new(out)), 42);
+}
+
+#[test]
+fn output_is_reproducible() {
+    let ctx = ctx();
+
+    let start = ctx.nasm(
+        "start",
+        "
+        global _start
+        extern get_value, other
+
+        section .data
+        local_value:
+            dq 1
+
+        section .text
+        _start:
+            call get_value
+            call other
+            mov rdi, rax
+            mov rax, 60
+            syscall
+    ",
+    );
+    let value = ctx.nasm(
+        "value",
+        "
+        global get_value, other, unused_a, unused_b, unused_c
+        section .text
+        get_value:
+            mov rax, 42
+            ret
+        other:
+            ret
+        unused_a:
+        unused_b:
+        unused_c:
+            ret
+    ",
+    );
+
+    let link = || {
+        let out = elven_wald!(ctx; &start, &value);
+        std::fs::read(out.to_string()).unwrap()
+    };
+    let output1 = link();
+    let output2 = link();
+    assert_eq!(output1, output2);
+}
+
+#[test]
+fn got_relocation_loads_address() {
+    let ctx = ctx();
+
+    let start = ctx.nasm(
+        "start",
+        "
+        global _start
+        extern value
+
+        section .text
+        _start:
+            mov rax, [rel value wrt ..gotpcrel]
+            mov rbx, [rel value wrt ..gotpcrel]
+            mov rdi, [rax]
+            add rdi, [rbx]
+            mov rax, 60
+            syscall
+    ",
+    );
+    let value = ctx.nasm(
+        "value",
+        "
+        global value
+        section .data
+        value:
+            dq 21
+    ",
+    );
+
+    let out = elven_wald!(ctx; start, value);
+    let data = std::fs::read(out.to_string()).unwrap();
+    let elf = ElfReader::new(&data).unwrap();
+    let got = elf.section_header_by_name(b".got").unwrap();
+    // Both loads share the slot.
+    assert_eq!(got.size, 8);
+    assert!(got.flags.contains(c::ShFlags::SHF_WRITE));
+
+    assert_eq!(run_exit_code(Command::new(out)), 42);
+}
+
+#[test]
+fn shared_object_relocates_got_slots() {
+    let ctx = ctx();
+
+    let value = ctx.gnu_as(
+        "value",
+        "
+        .globl get_value, value
+
+        .text
+        get_value:
+            movq value@GOTPCREL(%rip), %rax
+            movq local@GOTPCREL(%rip), %rcx
+            movq absolute_value@GOTPCREL(%rip), %rdx
+            ret
+
+        .data
+        value:
+            .quad 21
+        local:
+            .quad 21
+    ",
+    );
+    let absolute = ctx.gnu_as(
+        "absolute",
+        "
+        .globl absolute_value
+        .set absolute_value, 0x1234
+    ",
+    );
+
+    let out = elven_wald!(ctx; value, absolute, "-shared");
+    let data = std::fs::read(out.to_string()).unwrap();
+    let elf = ElfReader::new(&data).unwrap();
+
+    // The slots contain link time addresses, which have to be moved by the load address.
+    // The absolute symbol stays where it is.
+    let got = elf.section_header_by_name(b".got").unwrap();
+    assert_eq!(got.size, 24);
+    let data_addr = elf.symbol_by_name(b"value").unwrap().value.u64();
+    let relocations = elf
+        .relas()
+        .unwrap()
+        .map(|(_, rela)| (rela.info.r#type(), rela.offset, rela.addend))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        relocations,
+        [
+            (c::R_X86_64_RELATIVE, got.addr, data_addr as i64),
+            (c::R_X86_64_RELATIVE, got.addr + 8, data_addr as i64 + 8),
+        ]
+    );
+
+    let rela = elf.section_header_by_name(b".rela.dyn").unwrap();
+    assert_eq!(
+        elf.dyn_entry_by_tag(c::DT_RELA).unwrap().val,
+        rela.addr.u64()
+    );
+    assert_eq!(elf.dyn_entry_by_tag(c::DT_RELASZ).unwrap().val, rela.size);
+    assert_eq!(elf.dyn_entry_by_tag(c::DT_RELAENT).unwrap().val, 24);
+}
+
+#[test]
+fn shared_object_relocates_pointers() {
+    let ctx = ctx();
+
+    let value = ctx.gnu_as(
+        "value",
+        "
+        .globl value, value_ptr
+
+        .data
+        value:
+            .quad 21
+        value_ptr:
+            .quad value + 8
+    ",
+    );
+
+    let out = elven_wald!(ctx; value, "-shared");
+    let data = std::fs::read(out.to_string()).unwrap();
+    let elf = ElfReader::new(&data).unwrap();
+
+    let value = elf.symbol_by_name(b"value").unwrap();
+    let value_ptr = elf.symbol_by_name(b"value_ptr").unwrap();
+    let (_, rela) = elf.relas().unwrap().next().unwrap();
+    assert_eq!(rela.info.r#type(), c::R_X86_64_RELATIVE);
+    assert_eq!(rela.offset, value_ptr.value);
+    assert_eq!(rela.addend, value.value.u64() as i64 + 8);
+}
+
+#[test]
+fn shared_object_rejects_32_bit_addresses() {
+    let ctx = ctx();
+
+    let value = ctx.gnu_as(
+        "value",
+        "
+        .globl get_value
+        .text
+        get_value:
+            movq $value, %rax
+            ret
+        .data
+        value:
+            .quad 21
+    ",
+    );
+
+    let stderr = elven_wald_fails!(ctx; &value, "-shared");
+    assert!(stderr.contains("R_X86_64_32S"), "{stderr}");
+    assert!(
+        stderr.contains("can't be used in a shared object"),
+        "{stderr}"
+    );
+
+    elven_wald!(ctx; &value, "-e", "get_value");
+}
+
+#[test]
+fn plt_call_in_executable_is_direct() {
+    let ctx = ctx();
+
+    let start = ctx.nasm(
+        "start",
+        "
+        global _start
+        extern get_code
+
+        section .text
+        _start:
+            call get_code wrt ..plt
+            mov rdi, rax
+            mov rax, 60
+            syscall
+    ",
+    );
+    let get_code = ctx.nasm(
+        "get_code",
+        "
+        global get_code
+        section .text
+        get_code:
+            mov rax, 42
+            ret
+    ",
+    );
+
+    let out = elven_wald!(ctx; start, get_code);
+    let data = std::fs::read(out.to_string()).unwrap();
+    let elf = ElfReader::new(&data).unwrap();
+    assert!(elf.section_header_by_name(b".plt").is_err());
+
+    assert_eq!(run_exit_code(Command::new(out)), 42);
+}
+
+#[test]
+fn shared_object_calls_through_plt() {
+    let ctx = ctx();
+
+    let value = ctx.nasm(
+        "value",
+        "
+        global get_value, get_value_twice
+        section .text
+        get_value:
+            mov rax, 21
+            ret
+        get_value_twice:
+            call get_value wrt ..plt
+            add rax, rax
+            ret
+    ",
+    );
+
+    let out = elven_wald!(ctx; value, "-shared");
+    let data = std::fs::read(out.to_string()).unwrap();
+    let elf = ElfReader::new(&data).unwrap();
+
+    let plt = elf.section_header_by_name(b".plt").unwrap();
+    let got_plt = elf.section_header_by_name(b".got.plt").unwrap();
+    assert_eq!(plt.size, 16);
+    assert!(plt.flags.contains(c::ShFlags::SHF_EXECINSTR));
+
+    // The slot contains the link time address of the function, which is moved by the
+    // load address.
+    let get_value = elf.symbol_by_name(b"get_value").unwrap();
+    let slot = elf.section_content(got_plt).unwrap();
+    assert_eq!(slot, get_value.value.u64().to_le_bytes());
+    let (_, rela) = elf.relas().unwrap().next().unwrap();
+    assert_eq!(rela.info.r#type(), c::R_X86_64_RELATIVE);
+    assert_eq!(rela.offset, got_plt.addr);
+    assert_eq!(rela.addend, get_value.value.u64() as i64);
+
+    // The call goes to the stub, which jumps through the slot.
+    let get_value_twice = elf.symbol_by_name(b"get_value_twice").unwrap();
+    let text = elf.section_header_by_name(b".text").unwrap();
+    let call = (get_value_twice.value.u64() - text.addr.u64()) as usize;
+    let text = elf.section_content(text).unwrap();
+    assert_eq!(text[call], 0xe8);
+    let rel = i32::from_le_bytes(text[call + 1..call + 5].try_into().unwrap());
+    let call_end = get_value_twice.value.u64() + 5;
+    assert_eq!(call_end.wrapping_add_signed(rel.into()), plt.addr.u64());
+
+    let stub = elf.section_content(plt).unwrap();
+    assert_eq!(stub[..2], [0xff, 0x25]);
+    let rel = i32::from_le_bytes(stub[2..6].try_into().unwrap());
+    assert_eq!(
+        (plt.addr.u64() + 6).wrapping_add_signed(rel.into()),
+        got_plt.addr.u64()
+    );
+}
+
+#[test]
+fn eh_frames_are_merged() {
+    let ctx = ctx();
+
+    let start = ctx.gnu_as(
+        "start",
+        "
+        .globl _start
+        .text
+        _start:
+        .cfi_startproc
+            call get_value
+            mov %rax, %rdi
+            mov $60, %rax
+            syscall
+        .cfi_endproc
+
+        .section .text.unused,\"ax\",@progbits
+        unused:
+        .cfi_startproc
+            ret
+        .cfi_endproc
+    ",
+    );
+    let value = ctx.gnu_as(
+        "value",
+        "
+        .globl get_value
+        .text
+        get_value:
+        .cfi_startproc
+            mov $42, %rax
+            ret
+        .cfi_endproc
+    ",
+    );
+
+    let check = |out: File, fdes: usize| {
+        assert_eq!(run_exit_code(Command::new(&out)), 42);
+
+        let data = std::fs::read(out.to_string()).unwrap();
+        let elf = ElfReader::new(&data).unwrap();
+        let entries = elf
+            .eh_frame_entries()
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let cies = entries
+            .iter()
+            .filter(|entry| matches!(entry, EhFrameEntry::Cie(_)))
+            .count();
+        assert_eq!(cies, 1, "{entries:?}");
+        assert_eq!(entries.len(), fdes + 1, "{entries:?}");
+
+        let eh_frame = elf.section_header_by_name(b".eh_frame").unwrap();
+        let hdr = elf.eh_frame_hdr().unwrap().expect("no .eh_frame_hdr");
+        assert_eq!(hdr.eh_frame_ptr, eh_frame.addr);
+        let table = hdr.entries().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(table.len(), fdes);
+        assert!(table.windows(2).all(|w| w[0].initial_pc < w[1].initial_pc));
+        for name in [&b"_start"[..], b"get_value"] {
+            let sym = elf.symbol_by_name(name).unwrap();
+            let entry = table
+                .iter()
+                .find(|entry| entry.initial_pc == sym.value)
+                .expect("no table entry");
+            let fde = entries.iter().find_map(|e| match e {
+                EhFrameEntry::Fde(fde) if eh_frame.addr + fde.offset.u64() == entry.fde => {
+                    Some(fde)
+                }
+                _ => None,
+            });
+            assert_eq!(fde.unwrap().pc_begin, sym.value);
+        }
+
+        let hdr_sh = elf.section_header_by_name(b".eh_frame_hdr").unwrap();
+        let ph = elf
+            .program_headers()
+            .unwrap()
+            .iter()
+            .find(|ph| ph.r#type == c::PT_GNU_EH_FRAME)
+            .expect("no PT_GNU_EH_FRAME");
+        assert_eq!(ph.vaddr, hdr_sh.addr);
+    };
+
+    check(elven_wald!(ctx; &start, &value), 3);
+    // The FDE of `unused` is dropped together with its code.
+    check(elven_wald!(ctx; "--gc-sections", &start, &value), 2);
+}
+
+#[test]
+fn dynamic_linker_sets_interpreter() {
+    let ctx = ctx();
+
+    let start = ctx.nasm(
+        "start",
+        "
+        global _start
+        section .text
+        _start:
+            mov rax, 60
+            mov rdi, 0
+            syscall
+    ",
+    );
+
+    let out = elven_wald!(ctx; &start, "-dynamic-linker", "/lib64/ld-linux-x86-64.so.2");
+    let data = std::fs::read(out.to_string()).unwrap();
+    let elf = ElfReader::new(&data).unwrap();
+    assert_eq!(
+        elf.interpreter().unwrap().unwrap(),
+        "/lib64/ld-linux-x86-64.so.2"
+    );
+
+    let interp = elf.section_header_by_name(b".interp").unwrap();
+    let program_headers = elf.program_headers().unwrap();
+    let interp_idx = program_headers
+        .iter()
+        .position(|ph| ph.r#type == c::PT_INTERP)
+        .unwrap();
+    assert_eq!(program_headers[interp_idx].vaddr, interp.addr);
+    let first_load = program_headers
+        .iter()
+        .position(|ph| ph.r#type == c::PT_LOAD)
+        .unwrap();
+    assert!(interp_idx < first_load);
+
+    let out = elven_wald!(ctx; &start);
+    let data = std::fs::read(out.to_string()).unwrap();
+    let elf = ElfReader::new(&data).unwrap();
+    assert_eq!(elf.interpreter().unwrap(), None);
+}
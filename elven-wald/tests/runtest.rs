@@ -1,5 +1,5 @@
 mod integration;
 
 mod prelude {
-    pub(crate) use crate::integration::{ctx, elven_wald};
+    pub(crate) use crate::integration::{ctx, elven_wald, elven_wald_fails};
 }
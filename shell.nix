@@ -3,6 +3,7 @@
     rustup
     gcc
     clang_16
+    llvmPackages_16.llvm
     nasm
     gnumake
   ];
@@ -11,6 +11,29 @@ for c_obj_file in $SCRIPT_DIR/*_obj.c; do
     cc "$c_obj_file" -c -o "$SCRIPT_DIR/out/$(basename $c_obj_file .c).o"
 done
 
+for c_lib_file in $SCRIPT_DIR/*_lib.c; do
+    version_script="$SCRIPT_DIR/$(basename $c_lib_file .c).map"
+    version_args=()
+    if [ -f "$version_script" ]; then
+        version_args=("-Wl,--version-script=$version_script")
+    fi
+    # Emit both hash table styles so that both can be tested
+    cc "$c_lib_file" -shared -fPIC -Wl,--hash-style=both "${version_args[@]}" -o "$SCRIPT_DIR/out/$(basename $c_lib_file .c).so"
+done
+
 for asm_file in $SCRIPT_DIR/*.asm; do
     nasm "$asm_file" -felf64 -o "$SCRIPT_DIR/out/$(basename $asm_file .asm).o"
 done
+
+for riscv_file in $SCRIPT_DIR/*_riscv.s; do
+    llvm-mc "$riscv_file" -triple=riscv64 -filetype=obj -o "$SCRIPT_DIR/out/$(basename $riscv_file .s).o"
+done
+
+for aarch64_file in $SCRIPT_DIR/*_aarch64.s; do
+    llvm-mc "$aarch64_file" -triple=aarch64 -filetype=obj -o "$SCRIPT_DIR/out/$(basename $aarch64_file .s).o"
+done
+
+# 32-bit ARM uses SHT_REL instead of SHT_RELA
+for arm_file in $SCRIPT_DIR/*_arm.s; do
+    llvm-mc "$arm_file" -triple=armv7 -filetype=obj -o "$SCRIPT_DIR/out/$(basename $arm_file .s).o"
+done
@@ -0,0 +1,11 @@
+int exported_function(int x)
+{
+    return x + 1;
+}
+
+int exported_data = 3;
+
+int hidden_function(int x)
+{
+    return x * 2;
+}
@@ -0,0 +1,11 @@
+    .text
+    .globl f
+f:
+    bl g
+    adrp x0, sym
+    add x0, x0, :lo12:sym
+    ldr x1, [x0, :lo12:sym]
+    b g
+
+    .data
+    .quad sym
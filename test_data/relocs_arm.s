@@ -0,0 +1,8 @@
+    .text
+    .globl f
+f:
+    bl g
+    b g
+
+    .data
+    .word sym
@@ -0,0 +1,13 @@
+    .text
+    .globl f
+f:
+    call g
+    lui a0, %hi(sym)
+    addi a0, a0, %lo(sym)
+1:
+    auipc a1, %pcrel_hi(sym)
+    addi a1, a1, %pcrel_lo(1b)
+    ret
+
+    .data
+    .quad sym